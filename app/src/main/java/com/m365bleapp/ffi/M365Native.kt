@@ -1,5 +1,45 @@
 package com.m365bleapp.ffi
 
+// First byte of processHandshake/register/login/encrypt/encryptBatch/decrypt results, mirrors FfiStatus in ninebot-ffi
+object FfiStatus {
+    const val SUCCESS = 0
+    const val AUTH_FAILURE = 1
+    const val MALFORMED_INPUT = 2
+    const val NULL_SESSION = 3
+    const val CONTEXT_ALREADY_USED = 4
+    const val UNSUPPORTED_KEY_SCHEDULE = 5
+
+    fun describe(status: Int): String = when (status) {
+        SUCCESS -> "success"
+        AUTH_FAILURE -> "authentication failed"
+        MALFORMED_INPUT -> "malformed input"
+        NULL_SESSION -> "no session"
+        CONTEXT_ALREADY_USED -> "handshake context already used"
+        UNSUPPORTED_KEY_SCHEDULE -> "key schedule not supported"
+        else -> "unknown status $status"
+    }
+}
+
+// keySchedule argument of the *WithKeySchedule calls, mirrors KeySchedule in ninebot-ffi
+// V2 returns UNSUPPORTED_KEY_SCHEDULE until its derivation is known, calls without the argument use V1
+object KeySchedule {
+    const val V1 = 1
+    const val V2 = 2
+}
+
+class NativeException(val status: Int, operation: String) :
+    Exception("$operation failed: ${FfiStatus.describe(status)}")
+
+// Status byte of a native result, -1 if the result is empty
+fun ByteArray.ffiStatus(): Int = if (isEmpty()) -1 else this[0].toInt()
+
+// Payload after the status byte, throws NativeException unless status is SUCCESS
+fun ByteArray.unwrapFfi(operation: String): ByteArray {
+    val status = ffiStatus()
+    if (status != FfiStatus.SUCCESS) throw NativeException(status, operation)
+    return copyOfRange(1, size)
+}
+
 class M365Native {
     companion object {
         init {
@@ -16,22 +56,69 @@ class M365Native {
     // Initialize library (logger etc)
     external fun init()
 
-    // Returns [8 bytes Ptr][Public Key Bytes...]
+    // Returns [8 bytes context id][Public Key Bytes...], the id is valid for one successful handshake
     external fun prepareHandshake(): ByteArray
 
-    // ctxPtr is the first 8 bytes returned from prepareHandshake
-    // Returns [12 bytes Token][DID Ciphertext...] or empty if failed
+    // ctxPtr is the first 8 bytes returned from prepareHandshake, reusing it returns CONTEXT_ALREADY_USED
+    // Returns [status][12 bytes Token][DID Ciphertext...]
+    // Context older than 60s is rejected, see processHandshakeWithTimeout
+    // MALFORMED_INPUT keeps the context, retry with corrected remoteKey instead of calling prepareHandshake again
     external fun processHandshake(ctxPtr: Long, remoteKey: ByteArray, remoteInfo: ByteArray): ByteArray
 
-    // Returns [8 bytes Ptr][Login Data...] or empty
+    // Returns NULL_SESSION (and frees the context) if prepareHandshake was called more than maxAgeMs ago
+    external fun processHandshakeWithTimeout(ctxPtr: Long, remoteKey: ByteArray, remoteInfo: ByteArray, maxAgeMs: Long): ByteArray
+
+    // Unknown keySchedule gives MALFORMED_INPUT, UNSUPPORTED_KEY_SCHEDULE keeps the context like MALFORMED_INPUT does
+    external fun processHandshakeWithKeySchedule(
+        ctxPtr: Long, remoteKey: ByteArray, remoteInfo: ByteArray, maxAgeMs: Long, keySchedule: Int
+    ): ByteArray
+
+    // First connect to a fresh (or reset) scooter: takes the 64 byte key exactly as the scooter sent it
+    // Returns [status][12 bytes Token][DID Ciphertext...], store the token and use login on every later connect
+    external fun register(ctxPtr: Long, remoteKey: ByteArray, remoteInfo: ByteArray): ByteArray
+
+    external fun registerWithKeySchedule(ctxPtr: Long, remoteKey: ByteArray, remoteInfo: ByteArray, keySchedule: Int): ByteArray
+
+    // Returns [status][8 bytes Session Id][Login Data...]
+    // AUTH_FAILURE when remoteInfo doesn't match the token, scooter was registered with other token or link is tampered with
     external fun login(token: ByteArray, randKey: ByteArray, remoteKey: ByteArray, remoteInfo: ByteArray): ByteArray
 
-    // Encrypt payload using session pointer
-    external fun encrypt(sessionPtr: Long, payload: ByteArray, counter: Long): ByteArray
+    external fun loginWithKeySchedule(
+        token: ByteArray, randKey: ByteArray, remoteKey: ByteArray, remoteInfo: ByteArray, keySchedule: Int
+    ): ByteArray
+
+    // Encrypt payload using session id, returns [status][frame...]
+    // Unknown or freed id gives NULL_SESSION
+    // Negative counter uses (and advances) the counter kept in the session, which wraps to 0 after 0xFFFF.
+    // Otherwise counter is used as is, counter past 0xFFFFFFFF fails with MALFORMED_INPUT
+    external fun encrypt(sessionId: Long, payload: ByteArray, counter: Long): ByteArray
+
+    // Encrypt several commands at once with sequential session counters, returns [status][frames...]
+    // Commands and frames are each prefixed with 2 bytes big endian length, frames come in command order
+    external fun encryptBatch(sessionId: Long, commands: ByteArray): ByteArray
+
+    // Decrypt payload using session id, returns [status][plaintext...]
+    external fun decrypt(sessionId: Long, encrypted: ByteArray): ByteArray
+
+    // Returns [status][keychain...], keep it in encrypted storage to skip login after restart
+    external fun exportSession(sessionId: Long): ByteArray
+
+    // Registers session from exportSession blob, returns its id or 0 if the blob is invalid
+    external fun importSession(keychain: ByteArray): Long
+
+    // Returns [status][state...] with keychain and counter, restoring it resumes with the exact counter
+    external fun exportSessionState(sessionId: Long): ByteArray
+
+    // Registers session from exportSessionState blob, returns its id or 0 if the blob is invalid
+    external fun importSessionState(state: ByteArray): Long
+
+    // Counter for the next frame, -1 if session is unknown. Persist it to restore with resetCounter
+    external fun getCounter(sessionId: Long): Long
 
-    // Decrypt payload using session pointer
-    external fun decrypt(sessionPtr: Long, encrypted: ByteArray): ByteArray
+    // Returns status, NULL_SESSION if session is unknown and MALFORMED_INPUT if value is outside 0..0xFFFF,
+    // session counter wraps around to 0 after 0xFFFF
+    external fun resetCounter(sessionId: Long, value: Long): Int
 
-    // Free the session pointer
-    external fun freeSession(sessionPtr: Long)
+    // Forget the session, id is invalid afterwards
+    external fun freeSession(sessionId: Long)
 }
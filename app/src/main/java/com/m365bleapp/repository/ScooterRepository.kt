@@ -14,6 +14,9 @@ import androidx.security.crypto.MasterKey
 import com.m365bleapp.R
 import com.m365bleapp.ble.BleManager
 import com.m365bleapp.ffi.M365Native
+import com.m365bleapp.ffi.FfiStatus
+import com.m365bleapp.ffi.ffiStatus
+import com.m365bleapp.ffi.unwrapFfi
 import kotlinx.coroutines.*
 import kotlinx.coroutines.channels.Channel
 import kotlinx.coroutines.flow.Flow
@@ -299,10 +302,7 @@ class ScooterRepository private constructor(private val context: Context) {
         val remoteKeyBytes = readMiParcelWithProtocol()
         // delay(200) removed
         
-        val fullRemoteKey = byteArrayOf(0x04) + remoteKeyBytes
-        
-        val tokenAndDid = native.processHandshake(ctxPtr, fullRemoteKey, remoteInfo)
-        if (tokenAndDid.isEmpty()) throw Exception("Handshake failed")
+        val tokenAndDid = native.register(ctxPtr, remoteKeyBytes, remoteInfo).unwrapFfi("Register")
         
         val token = tokenAndDid.sliceArray(0 until 12)
         val didCiphertext = tokenAndDid.sliceArray(12 until tokenAndDid.size)
@@ -357,8 +357,7 @@ class ScooterRepository private constructor(private val context: Context) {
         val remoteInfo = readMiParcelWithProtocol()
         
         // 4. Native Login
-        val res = native.login(token, randKey, remoteKey, remoteInfo)
-        if (res.isEmpty()) throw Exception("Login calc failed")
+        val res = native.login(token, randKey, remoteKey, remoteInfo).unwrapFfi("Login calc")
         
         sessionPtr = res.sliceArray(0 until 8).toLong()
         val loginData = res.sliceArray(8 until res.size)
@@ -435,7 +434,7 @@ class ScooterRepository private constructor(private val context: Context) {
                 
                 Log.d("ScooterRepo", "Loop: Query 0x${attribute.toString(16)}: ${packet.toHex()}")
                 
-                val encrypted = native.encrypt(sessionPtr, packet, counter)
+                val encrypted = native.encrypt(sessionPtr, packet, counter).unwrapFfi("Encrypt")
                 Log.d("ScooterRepo", "Encrypted (${encrypted.size} bytes): ${encrypted.toHex()}")
                 
                 // Write Encrypted to UART TX
@@ -444,13 +443,15 @@ class ScooterRepository private constructor(private val context: Context) {
                 val frame = readEncryptedFrame()
                 if (frame.isNotEmpty()) {
                     Log.d("ScooterRepo", "Rx Encrypted (${frame.size} bytes): ${frame.toHex()}")
-                    val decrypted = native.decrypt(sessionPtr, frame)
-                    if (decrypted.isNotEmpty()) {
+                    val result = native.decrypt(sessionPtr, frame)
+                    val status = result.ffiStatus()
+                    if (status == FfiStatus.SUCCESS) {
+                         val decrypted = result.copyOfRange(1, result.size)
                          Log.d("ScooterRepo", "Rx Decrypted: ${decrypted.toHex()}")
                          parseTelemetry(decrypted)
                          consecutiveFailures = 0 // Reset on success
                     } else {
-                         Log.w("ScooterRepo", "Decryption failed")
+                         Log.w("ScooterRepo", "Decryption failed: ${FfiStatus.describe(status)}")
                          consecutiveFailures++
                     }
                 } else {
@@ -714,7 +715,7 @@ class ScooterRepository private constructor(private val context: Context) {
      */
     private suspend fun sendCommand(packet: ByteArray, commandName: String = "Command") {
         val counter = 0L  // Always use counter=0 (scooter doesn't track)
-        val encrypted = native.encrypt(sessionPtr, packet, counter)
+        val encrypted = native.encrypt(sessionPtr, packet, counter).unwrapFfi("Encrypt")
         Log.d("ScooterRepo", "Command Encrypted (${encrypted.size} bytes): ${encrypted.toHex()}")
         
         // Log the command to CSV
@@ -1227,5 +1228,6 @@ class ScooterRepository private constructor(private val context: Context) {
     private fun String.hexToBytes(): ByteArray = chunked(2).map { it.toInt(16).toByte() }.toByteArray()
     private fun ByteArray.sliceArray(range: IntRange): ByteArray = copyOfRange(range.first, range.last + 1)
     private fun Long.toByteArray(): ByteArray = ByteArray(8) // dummy
+    // Native handles (handshake context, session id) are big endian, ByteBuffer's default order
     private fun ByteArray.toLong(): Long = ByteBuffer.wrap(this).long
 }
@@ -12,7 +12,7 @@ use chrono::{Local, DateTime};
 
 use ninebot_ble::{
     ScooterScanner, ConnectionHelper, LoginRequest, session::MiSession,
-    AuthToken, session::TailLight
+    AuthToken, session::TailLightMode
 };
 
 // Data structures for logging
@@ -65,7 +65,7 @@ enum Command {
     Help,
     Status,
     Cruise(bool),
-    TailLight(TailLight),
+    TailLight(TailLightMode),
     Log(bool),
     Interval(u64),
     Unknown(String),
@@ -95,9 +95,9 @@ fn parse_command(input: &str) -> Command {
         "light" | "tail" | "taillight" => {
             if parts.len() > 1 {
                 match parts[1].to_lowercase().as_str() {
-                    "off" | "0" => Command::TailLight(TailLight::Off),
-                    "brake" | "1" => Command::TailLight(TailLight::OnBrake),
-                    "on" | "always" | "2" => Command::TailLight(TailLight::Always),
+                    "off" | "0" => Command::TailLight(TailLightMode::Off),
+                    "brake" | "1" => Command::TailLight(TailLightMode::OnBrake),
+                    "on" | "always" | "2" => Command::TailLight(TailLightMode::Always),
                     _ => Command::Unknown(format!("Invalid light mode: {}. Use: off, brake, always", parts[1]))
                 }
             } else {
@@ -15,7 +15,7 @@ use ninebot_ble::{
   ScooterScanner,
   LoginRequest,
   ConnectionHelper,
-  session::TailLight
+  session::TailLightMode
 };
 
 async fn load_token() -> Result<AuthToken> {
@@ -72,11 +72,11 @@ async fn main() -> Result<()>{
   tracing::info!("  Supplementary info {:?}", session.supplementary_info().await?);
 
   tracing::info!("  Tail light enabled: {:?}, Switching on", session.tail_light().await?);
-  session.set_tail_light(TailLight::Always).await?;
+  session.set_tail_light(TailLightMode::Always).await?;
   tracing::info!("  Supplementary info {:?}", session.supplementary_info().await?);
   tracing::info!("  Tail light enabled: {:?}, Switching off", session.tail_light().await?);
   time::sleep(Duration::from_secs(2)).await;
-  session.set_tail_light(TailLight::Off).await?;
+  session.set_tail_light(TailLightMode::Off).await?;
   tracing::info!("  Tail light enabled: {:?}", session.tail_light().await?);
   tracing::info!("  Supplementary info {:?}", session.supplementary_info().await?);
 
@@ -0,0 +1,59 @@
+use anyhow::{Result, Context};
+use btleplug::api::BDAddr;
+use tokio::io::AsyncReadExt;
+use std::path::Path;
+use tokio::fs::File;
+use std::env;
+use ninebot_ble::{
+  AuthToken,
+  ScooterScanner,
+  LoginRequest,
+  ConnectionHelper
+};
+use ninebot_ble::cli::{self, CliCommand, USAGE};
+
+async fn load_token() -> Result<AuthToken> {
+  let path = Path::new(".mi-token");
+
+  let mut f = File::open(path).await?;
+  let mut buffer : AuthToken = [0; 12];
+
+  f.read(&mut buffer).await?;
+
+  Ok(buffer)
+}
+
+#[tokio::main(flavor = "multi_thread")]
+async fn main() -> Result<()> {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if args.len() < 2 {
+    eprintln!("{}", USAGE);
+    std::process::exit(2);
+  }
+
+  // fail on typo before spending time on scanning and login
+  let command = match CliCommand::parse(&args[1..]) {
+    Ok(command) => command,
+    Err(err) => {
+      eprintln!("{}", err);
+      std::process::exit(2);
+    }
+  };
+
+  let mac = BDAddr::from_str_delim(&args[0])
+    .with_context(|| format!("Invalid mac address: {}", args[0]))?;
+
+  let token = load_token().await
+    .with_context(|| "Could not load registration token, register with `cargo run --example register` first")?;
+
+  let mut scanner = ScooterScanner::new().await?;
+  let scooter = scanner.wait_for(&mac).await?;
+  let device = scanner.peripheral(&scooter).await?;
+  let connection = ConnectionHelper::new(&device);
+  connection.reconnect().await?;
+
+  let mut request = LoginRequest::new(&device, &token).await?;
+  let mut session = request.start().await?;
+
+  cli::run(&mut session, command, &mut std::io::stdout()).await
+}
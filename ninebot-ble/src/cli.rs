@@ -0,0 +1,197 @@
+use crate::session::{MiSession, Transport, RideMode, SecurityClass};
+
+use std::io::Write;
+use anyhow::{Result, anyhow};
+
+pub const USAGE : &str = "\
+Usage: ninebot-cli <MAC> <COMMAND>
+
+Commands:
+  info                     serial, firmware versions, odometer and battery cycles
+  telemetry                speed, battery, odometer and temperature
+  lock                     lock motor
+  unlock                   unlock motor
+  light on|off             tail light
+  set-mode eco|drive|sport ride mode";
+
+/**
+ * One read or write for quick testing against real scooter, e.g. `ninebot-cli <MAC> light on`
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CliCommand {
+  Info,
+  Telemetry,
+  Lock,
+  Unlock,
+  Light(bool),
+  SetMode(RideMode)
+}
+
+impl CliCommand {
+  /**
+   * Parse arguments after the MAC address, unknown command is an error with usage in it
+   */
+  pub fn parse<S: AsRef<str>>(args: &[S]) -> Result<CliCommand> {
+    let args : Vec<&str> = args.iter().map(|arg| arg.as_ref()).collect();
+
+    match args.as_slice() {
+      ["info"] => Ok(CliCommand::Info),
+      ["telemetry"] => Ok(CliCommand::Telemetry),
+      ["lock"] => Ok(CliCommand::Lock),
+      ["unlock"] => Ok(CliCommand::Unlock),
+      ["light", "on"] => Ok(CliCommand::Light(true)),
+      ["light", "off"] => Ok(CliCommand::Light(false)),
+      ["set-mode", mode] => Ok(CliCommand::SetMode(parse_ride_mode(mode)?)),
+      _ => Err(anyhow!("Unknown command: {:?}\n\n{}", args.join(" "), USAGE))
+    }
+  }
+}
+
+fn parse_ride_mode(mode: &str) -> Result<RideMode> {
+  match mode {
+    "eco" => Ok(RideMode::Eco),
+    "drive" => Ok(RideMode::Drive),
+    "sport" => Ok(RideMode::Sport),
+    _ => Err(anyhow!("Unknown ride mode: {}, expected eco, drive or sport", mode))
+  }
+}
+
+fn ride_mode_name(mode: RideMode) -> &'static str {
+  match mode {
+    RideMode::Eco => "eco",
+    RideMode::Drive => "drive",
+    RideMode::Sport => "sport"
+  }
+}
+
+/**
+ * Run command on logged in session and print result as `name: value` lines. Lock and unlock raise
+ * session authorization to Restricted, typing the command is the explicit permission
+ */
+pub async fn run<T: Transport, W: Write>(session: &mut MiSession<T>, command: CliCommand, out: &mut W) -> Result<()> {
+  match command {
+    CliCommand::Info => {
+      let info = session.read_device_info().await?;
+      writeln!(out, "serial: {}", info.serial)?;
+      writeln!(out, "ble version: {}", info.ble_version)?;
+      writeln!(out, "esc version: {}", info.esc_version)?;
+      writeln!(out, "bms version: {}", info.bms_version)?;
+      writeln!(out, "odometer: {:.1} km", info.odometer_km)?;
+      writeln!(out, "battery cycles: {}", info.battery_cycles)?;
+    },
+    CliCommand::Telemetry => {
+      let telemetry = session.read_telemetry().await?;
+      writeln!(out, "speed: {:.1} km/h", telemetry.speed_kmh)?;
+      writeln!(out, "battery: {}%", telemetry.battery_percent)?;
+      writeln!(out, "odometer: {:.1} km", telemetry.odometer_km)?;
+      writeln!(out, "temperature: {:.1} °C", telemetry.temperature_c)?;
+    },
+    CliCommand::Lock => {
+      session.set_authorization_level(SecurityClass::Restricted);
+      session.lock().await?;
+      writeln!(out, "locked")?;
+    },
+    CliCommand::Unlock => {
+      session.set_authorization_level(SecurityClass::Restricted);
+      session.unlock().await?;
+      writeln!(out, "unlocked")?;
+    },
+    CliCommand::Light(on) => {
+      session.set_light(on).await?;
+      writeln!(out, "light {}", if on { "on" } else { "off" })?;
+    },
+    CliCommand::SetMode(mode) => {
+      session.set_ride_mode(mode).await?;
+      writeln!(out, "ride mode: {}", ride_mode_name(mode))?;
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::session::MockTransport;
+  use crate::mi_crypto::{decrypt_uart, EncryptionKey, LoginKeychain};
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  /**
+   * Run command against scooter which acknowledges everything, returns output and decrypted commands
+   */
+  async fn run_acknowledged(command: CliCommand) -> (String, Vec<Vec<u8>>) {
+    let keys = test_keys();
+    let mut transport = MockTransport::default();
+    transport.auto_reply_with_simulated_responses(&keys);
+    let mut session = MiSession::with_transport(transport, &keys);
+
+    let mut out = Vec::new();
+    run(&mut session, command, &mut out).await.unwrap();
+
+    let written = session.transport().written.iter()
+      .map(|frame| decrypt_uart(&keys.app, frame).unwrap())
+      .collect();
+    (String::from_utf8(out).unwrap(), written)
+  }
+
+  #[test]
+  fn test_parse() {
+    assert_eq!(CliCommand::parse(&["info"]).unwrap(), CliCommand::Info);
+    assert_eq!(CliCommand::parse(&["telemetry"]).unwrap(), CliCommand::Telemetry);
+    assert_eq!(CliCommand::parse(&["lock"]).unwrap(), CliCommand::Lock);
+    assert_eq!(CliCommand::parse(&["unlock"]).unwrap(), CliCommand::Unlock);
+    assert_eq!(CliCommand::parse(&["light", "off"]).unwrap(), CliCommand::Light(false));
+    assert_eq!(CliCommand::parse(&["set-mode", "eco"]).unwrap(), CliCommand::SetMode(RideMode::Eco));
+  }
+
+  #[test]
+  fn test_parse_unknown_command() {
+    let err = CliCommand::parse(&["honk"]).unwrap_err();
+    assert!(err.to_string().contains("Usage"));
+
+    assert!(CliCommand::parse(&["light"]).is_err());
+    assert!(CliCommand::parse(&["set-mode", "turbo"]).is_err());
+    assert!(CliCommand::parse::<&str>(&[]).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_lock() {
+    let (out, written) = run_acknowledged(CliCommand::Lock).await;
+
+    assert_eq!(out, "locked\n");
+    assert_eq!(&written[0][..5], &[0x20, 0x03, 0x70, 0x01, 0x00]);
+  }
+
+  #[tokio::test]
+  async fn test_unlock() {
+    let (out, written) = run_acknowledged(CliCommand::Unlock).await;
+
+    assert_eq!(out, "unlocked\n");
+    assert_eq!(&written[0][..5], &[0x20, 0x03, 0x71, 0x01, 0x00]);
+  }
+
+  #[tokio::test]
+  async fn test_light() {
+    let (out, written) = run_acknowledged(CliCommand::Light(true)).await;
+    assert_eq!(out, "light on\n");
+    assert_eq!(&written[0][..5], &[0x20, 0x03, 0x7D, 0x02, 0x00]);
+
+    let (out, written) = run_acknowledged(CliCommand::Light(false)).await;
+    assert_eq!(out, "light off\n");
+    assert_eq!(&written[0][..5], &[0x20, 0x03, 0x7D, 0x00, 0x00]);
+  }
+
+  #[tokio::test]
+  async fn test_set_mode() {
+    let (out, written) = run_acknowledged(CliCommand::SetMode(RideMode::Sport)).await;
+
+    assert_eq!(out, "ride mode: sport\n");
+    assert_eq!(&written[0][..5], &[0x20, 0x03, 0x75, 0x04, 0x00]);
+  }
+}
@@ -0,0 +1,44 @@
+/**
+ * Checksum of plain (not encrypted) UART frame, used for commands we send and responses we parse alike.
+ * Sum of all bytes inverted, stored as little endian. Frame body is everything between 0x55 0xAA header
+ * and checksum itself: length byte, direction, read/write, attribute and payload
+ */
+pub(super) fn compute(frame_body: &[u8]) -> [u8; 2] {
+  let sum : u32 = frame_body.iter().map(|byte| *byte as u32).sum();
+  let checksum = !(sum as u16);
+  checksum.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_read_serial_request() {
+    // 55 AA 03 20 01 10 0E BD FF
+    assert_eq!(compute(&[0x03, 0x20, 0x01, 0x10, 0x0E]), [0xBD, 0xFF]);
+  }
+
+  #[test]
+  fn test_read_firmware_request() {
+    // 55 AA 03 20 01 1A 10 B1 FF
+    assert_eq!(compute(&[0x03, 0x20, 0x01, 0x1A, 0x10]), [0xB1, 0xFF]);
+  }
+
+  #[test]
+  fn test_battery_percent_response() {
+    // 55 AA 04 25 01 32 40 00 63 FF, BMS answering 64%
+    assert_eq!(compute(&[0x04, 0x25, 0x01, 0x32, 0x40, 0x00]), [0x63, 0xFF]);
+  }
+
+  #[test]
+  fn test_sum_wraps_around_16_bits() {
+    // 300 * 0xFF = 0x12AD4, only low 16 bits are inverted
+    assert_eq!(compute(&[0xFF; 300]), (!0x2AD4u16).to_le_bytes());
+  }
+
+  #[test]
+  fn test_empty_body() {
+    assert_eq!(compute(&[]), [0xFF, 0xFF]);
+  }
+}
@@ -0,0 +1,622 @@
+use core::fmt;
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::{Result, anyhow};
+
+use super::checksum;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+  MasterToMotor,
+  MasterToBattery,
+  MotorToMaster,
+  BatteryToMaster,
+}
+
+impl Direction {
+  pub(crate) fn value(&self) -> u8 {
+    match self {
+      Direction::MasterToMotor      => 0x20,
+      Direction::MasterToBattery    => 0x22,
+      Direction::MotorToMaster      => 0x23,
+      Direction::BatteryToMaster    => 0x25,
+    }
+  }
+
+  /**
+   * Direction in which scooter answers command sent in this direction (and the other way around)
+   */
+  pub fn reply(&self) -> Direction {
+    match self {
+      Direction::MasterToMotor      => Direction::MotorToMaster,
+      Direction::MasterToBattery    => Direction::BatteryToMaster,
+      Direction::MotorToMaster      => Direction::MasterToMotor,
+      Direction::BatteryToMaster    => Direction::MasterToBattery,
+    }
+  }
+
+  pub(crate) fn from_value(value: u8) -> Option<Self> {
+    match value {
+      0x20 => Some(Direction::MasterToMotor),
+      0x22 => Some(Direction::MasterToBattery),
+      0x23 => Some(Direction::MotorToMaster),
+      0x25 => Some(Direction::BatteryToMaster),
+      _    => None
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReadWrite {
+  Read,
+  Write
+}
+
+impl ReadWrite {
+  pub(crate) fn value(&self) -> u8 {
+    match self {
+      ReadWrite::Read     => 0x01,
+      ReadWrite::Write    => 0x03
+    }
+  }
+
+  pub(crate) fn from_value(value: u8) -> Option<Self> {
+    match value {
+      0x01 => Some(ReadWrite::Read),
+      0x03 => Some(ReadWrite::Write),
+      _    => None
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Attribute {
+  GeneralInfo,
+  MotorInfo,
+  DistanceLeft,
+  Speed,
+  TripDistance,
+  BatteryVoltage,
+  BatteryCurrent,
+  BatteryPercent,
+  BatteryCellVoltages,
+  Supplementary,
+  Cruise,
+  TailLight,
+  Headlight,
+  BatteryInfo,
+  Lock,
+  Unlock,
+  RideMode,
+  BleVersion,
+  EscVersion,
+  BmsVersion,
+  TotalMileage,
+  Beep,
+  PowerOff,
+  Reboot,
+  FrameTemperature,
+  BatteryCycles,
+  BatteryHealth,
+  AlarmStatus,
+  LockStatus,
+  TripStatistics,
+  RidingTime,
+  Uptime,
+  /**
+   * Address received from scooter which has no variant yet
+   */
+  Unknown(u8)
+}
+
+/**
+ * Inverse of `Attribute::address`, fails for addresses which have no variant
+ */
+impl TryFrom<u8> for Attribute {
+  type Error = anyhow::Error;
+
+  fn try_from(value: u8) -> Result<Self, Self::Error> {
+    // looked up through `address`, so the table is written only once
+    Attribute::ALL.iter()
+      .find(|attribute| attribute.address() == value)
+      .cloned()
+      .ok_or_else(|| anyhow!("Unknown attribute: 0x{:02X}", value))
+  }
+}
+
+/**
+ * Name with address, e.g. "TailLight(0x7D)"
+ */
+impl fmt::Display for Attribute {
+  fn fmt(&self, form: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Attribute::Unknown(address) => write!(form, "Unknown(0x{:02X})", address),
+      attribute => write!(form, "{:?}(0x{:02X})", attribute, attribute.address())
+    }
+  }
+}
+
+/**
+ * Declares `Attribute::ALL` and `Attribute::name` from one list. `name` matches every variant without wildcard,
+ * so variant missing from the list fails the build ("pattern `&Attribute::X` not covered") instead of silently
+ * missing from `TryFrom<u8>` and the address check below
+ */
+macro_rules! attribute_list {
+  ($($variant:ident),* $(,)?) => {
+    impl Attribute {
+      /**
+       * Every variant except Unknown
+       */
+      pub const ALL: [Attribute; [$(stringify!($variant)),*].len()] = [$(Attribute::$variant),*];
+
+      /**
+       * Variant name, same as Debug prints, but usable in const context
+       */
+      pub const fn name(&self) -> &'static str {
+        match self {
+          $(Attribute::$variant => stringify!($variant),)*
+          Attribute::Unknown(_) => "Unknown"
+        }
+      }
+    }
+  };
+}
+
+attribute_list![
+  GeneralInfo,
+  MotorInfo,
+  DistanceLeft,
+  Speed,
+  TripDistance,
+  BatteryVoltage,
+  BatteryCurrent,
+  BatteryPercent,
+  BatteryCellVoltages,
+  Supplementary,
+  Cruise,
+  TailLight,
+  Headlight,
+  BatteryInfo,
+  Lock,
+  Unlock,
+  RideMode,
+  BleVersion,
+  EscVersion,
+  BmsVersion,
+  TotalMileage,
+  Beep,
+  PowerOff,
+  Reboot,
+  FrameTemperature,
+  BatteryCycles,
+  BatteryHealth,
+  AlarmStatus,
+  LockStatus,
+  TripStatistics,
+  RidingTime,
+  Uptime
+];
+
+impl Attribute {
+
+  /**
+   * Register address sent in commands and received in responses
+   */
+  pub const fn address(&self) -> u8 {
+    match self {
+      Attribute::GeneralInfo          => 0x10,
+      Attribute::DistanceLeft         => 0x25,
+      Attribute::Speed                => 0xB5,
+      Attribute::TripDistance         => 0xB9,
+      Attribute::BatteryVoltage       => 0x34,
+      Attribute::BatteryCurrent       => 0x33,
+      Attribute::BatteryPercent       => 0x32,
+      Attribute::MotorInfo            => 0xB0,
+      Attribute::BatteryCellVoltages  => 0x40,
+      Attribute::Supplementary        => 0x7B,
+      Attribute::Cruise               => 0x7C,
+      Attribute::TailLight            => 0x7D,
+      Attribute::Headlight            => 0x7E,
+      Attribute::BatteryInfo          => 0x31,
+      Attribute::Lock                 => 0x70,
+      Attribute::Unlock               => 0x71,
+      Attribute::RideMode             => 0x75,
+      Attribute::BleVersion           => 0x9B,
+      Attribute::EscVersion           => 0x1A,
+      Attribute::BmsVersion           => 0x17,
+      Attribute::TotalMileage         => 0x29,
+      Attribute::Beep                 => 0x7A,
+      Attribute::PowerOff             => 0x79,
+      Attribute::Reboot               => 0x78,
+      Attribute::FrameTemperature     => 0x3E,
+      Attribute::BatteryCycles        => 0x1B,
+      Attribute::BatteryHealth        => 0x3B,
+      Attribute::AlarmStatus          => 0xB1,
+      Attribute::LockStatus           => 0xB2,
+      Attribute::TripStatistics       => 0x3A,
+      Attribute::RidingTime           => 0x3C,
+      Attribute::Uptime               => 0x3D,
+      Attribute::Unknown(value)       => *value
+    }
+  }
+
+  /**
+   * Known variant for address, Attribute::Unknown for anything else. Use `try_from` to reject unknown addresses
+   */
+  pub fn from_address(address: u8) -> Self {
+    Attribute::try_from(address).unwrap_or(Attribute::Unknown(address))
+  }
+
+  /**
+   * Controller which owns the register. Battery registers live in BMS, everything else in motor controller,
+   * command sent to wrong one is silently ignored
+   */
+  pub fn destination(&self) -> Direction {
+    match self {
+      Attribute::BatteryVoltage |
+      Attribute::BatteryCurrent |
+      Attribute::BatteryPercent |
+      Attribute::BatteryCellVoltages |
+      Attribute::BatteryInfo |
+      Attribute::BmsVersion |
+      Attribute::BatteryCycles |
+      Attribute::BatteryHealth => Direction::MasterToBattery,
+      _ => Direction::MasterToMotor
+    }
+  }
+
+  /**
+   * Values which can't change while connected (serial, firmware versions), their reads are answered
+   * from session cache after the first one
+   */
+  pub fn is_static(&self) -> bool {
+    matches!(self,
+      Attribute::GeneralInfo |
+      Attribute::BleVersion |
+      Attribute::EscVersion |
+      Attribute::BmsVersion
+    )
+  }
+
+  /**
+   * Settings which rider can change from the app. Every other register is either telemetry or can only
+   * be changed by firmware (e.g. mileage is reset only in factory mode)
+   */
+  pub fn writable_by_user(&self) -> bool {
+    matches!(self,
+      Attribute::Supplementary |
+      Attribute::Cruise |
+      Attribute::TailLight |
+      Attribute::Headlight |
+      Attribute::Lock |
+      Attribute::Unlock |
+      Attribute::RideMode |
+      Attribute::Beep |
+      Attribute::PowerOff |
+      Attribute::Reboot
+    )
+  }
+}
+
+const CONFLICT_MESSAGE_LENGTH: usize = 64;
+
+/**
+ * "TailLight and FakeAttr both use 0x7D". Written byte by byte, because const check can't use `format!`
+ */
+const fn conflict_message<'a>(buffer: &'a mut [u8; CONFLICT_MESSAGE_LENGTH], first: &Attribute, second: &Attribute) -> &'a str {
+  const HEX: &[u8; 16] = b"0123456789ABCDEF";
+  let parts: [&[u8]; 4] = [first.name().as_bytes(), b" and ", second.name().as_bytes(), b" both use 0x"];
+
+  let mut length = 0;
+  let mut part = 0;
+  while part < parts.len() {
+    let mut i = 0;
+    // two bytes are left for the address
+    while i < parts[part].len() && length < CONFLICT_MESSAGE_LENGTH - 2 {
+      buffer[length] = parts[part][i];
+      length += 1;
+      i += 1;
+    }
+    part += 1;
+  }
+  buffer[length] = HEX[(first.address() >> 4) as usize];
+  buffer[length + 1] = HEX[(first.address() & 0x0F) as usize];
+
+  match core::str::from_utf8(buffer.split_at(length + 2).0) {
+    Ok(message) => message,
+    Err(_) => "Two attributes share the same address"
+  }
+}
+
+/**
+ * Two variants on the same address would make `TryFrom<u8>` ambiguous, so the conflict fails the build
+ * naming both of them and the address
+ */
+const _: () = {
+  let mut i = 0;
+  while i < Attribute::ALL.len() {
+    let mut j = i + 1;
+    while j < Attribute::ALL.len() {
+      if Attribute::ALL[i].address() == Attribute::ALL[j].address() {
+        let mut buffer = [0u8; CONFLICT_MESSAGE_LENGTH];
+        panic!("{}", conflict_message(&mut buffer, &Attribute::ALL[i], &Attribute::ALL[j]));
+      }
+      j += 1;
+    }
+    i += 1;
+  }
+};
+
+/**
+ * How much authorization is required to send command. Ordered from least to most privileged, so
+ * session authorized for Admin can also send Restricted and Public commands
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecurityClass {
+  Public,
+  Restricted,
+  Admin
+}
+
+impl SecurityClass {
+  /**
+   * Can session authorized on this level send given command
+   */
+  pub fn allows(&self, cmd: &ScooterCommand) -> bool {
+    cmd.security_class() <= *self
+  }
+}
+
+/**
+ * Length byte is payload length + 2 (attribute and read/write), so it fits at most 253 bytes of payload
+ */
+pub const MAX_PAYLOAD_LENGTH : usize = u8::MAX as usize - 2;
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ScooterCommand {
+  pub direction: Direction,
+  pub read_write: ReadWrite,
+  pub attribute: Attribute,
+  pub payload: Vec<u8>
+}
+
+/**
+ * Without std there is no hex dump, command is printed as plain serialized bytes
+ */
+#[cfg(not(feature = "std"))]
+impl fmt::Debug for ScooterCommand {
+  fn fmt(&self, form: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.try_as_bytes() {
+      Ok(bytes) => write!(form, "{:02X?}", bytes),
+      Err(_) => write!(form, "{:?} {:?} {:?} (oversized: {} bytes of payload)", self.direction, self.read_write, self.attribute, self.payload.len())
+    }
+  }
+}
+
+impl ScooterCommand {
+  /**
+   * Read command, payload of read is number of bytes scooter should send back
+   */
+  pub fn read(direction: Direction, attribute: Attribute, length: u8) -> ScooterCommand {
+    ScooterCommand {
+      direction,
+      read_write: ReadWrite::Read,
+      attribute,
+      payload: vec![length]
+    }
+  }
+
+  /**
+   * Start building command, e.g. `ScooterCommand::builder().attribute(Attribute::TailLight).write_u16(0x0002).build()`
+   */
+  pub fn builder() -> ScooterCommandBuilder {
+    ScooterCommandBuilder::default()
+  }
+
+  /**
+   * Serialized command: length, direction, read/write, attribute and payload. Length byte covers attribute
+   * and payload, so payload over `MAX_PAYLOAD_LENGTH` bytes can't be encoded and is an error.
+   * There is no checksum trailer: this is the input of `encrypt_uart`, which encrypts everything after the length
+   * byte and appends its own crc16 over the ciphertext, so a trailer here would end up inside the encrypted payload.
+   * Use `as_frame` for plain UART frames
+   */
+  pub fn try_as_bytes(&self) -> Result<Vec<u8>> {
+    if self.payload.len() > MAX_PAYLOAD_LENGTH {
+      return Err(anyhow!("Payload of {} bytes does not fit into length byte, limit is {}", self.payload.len(), MAX_PAYLOAD_LENGTH))
+    }
+
+    let mut bytes : Vec<u8> = Vec::new();
+    bytes.push(self.payload.len() as u8 + 2u8);
+    bytes.push(self.direction.value());
+    bytes.push(self.read_write.value());
+    bytes.push(self.attribute.address());
+    for byte in &self.payload {
+      bytes.push(*byte);
+    }
+    Ok(bytes)
+  }
+
+  /**
+   * Command as it goes over plain (not encrypted) UART: header, command bytes and checksum trailer.
+   * Fails like `try_as_bytes` for payload over `MAX_PAYLOAD_LENGTH`
+   */
+  pub fn as_frame(&self) -> Result<Vec<u8>> {
+    let bytes = self.try_as_bytes()?;
+
+    let mut frame : Vec<u8> = vec![0x55, 0xAA];
+    frame.extend_from_slice(&bytes);
+    frame.extend_from_slice(&checksum::compute(&bytes));
+    Ok(frame)
+  }
+
+  /**
+   * Reads and rider settings are public. Locking changes whether scooter can be ridden at all, so it
+   * is restricted. Writing registers rider can't change from the app (see `Attribute::writable_by_user`)
+   * is reserved for admin, session authorized for Admin is the only one which can send such writes
+   */
+  pub fn security_class(&self) -> SecurityClass {
+    match self.read_write {
+      ReadWrite::Read => SecurityClass::Public,
+      ReadWrite::Write => match self.attribute {
+        Attribute::Lock | Attribute::Unlock | Attribute::PowerOff | Attribute::Reboot => SecurityClass::Restricted,
+        _ if self.attribute.writable_by_user() => SecurityClass::Public,
+        _ => SecurityClass::Admin
+      }
+    }
+  }
+
+  /**
+   * Generate plausible response for this command, so tests don't need hand written fixtures. Reads
+   * are answered with zero filled payload of requested length, writes with ack (0x01) for the same attribute
+   */
+  pub fn simulate_response(&self) -> ScooterCommand {
+    let payload = match self.read_write {
+      ReadWrite::Read => vec![0u8; self.payload.first().copied().unwrap_or(0) as usize],
+      ReadWrite::Write => vec![0x01]
+    };
+
+    ScooterCommand {
+      direction: self.direction.reply(),
+      read_write: self.read_write.clone(),
+      attribute: self.attribute.clone(),
+      payload
+    }
+  }
+
+  /**
+   * Parse serialized command. Bytes can come with or without checksum trailer, when trailer is
+   * present it has to match. Address without variant is decoded as `Attribute::Unknown`
+   */
+  pub fn try_from_bytes(bytes: &[u8]) -> Result<Self> {
+    if bytes.len() < 4 {
+      return Err(anyhow!("Command is too short: {} bytes", bytes.len()))
+    }
+
+    let length = bytes[0] as usize;
+    if length < 2 {
+      return Err(anyhow!("Invalid length byte: {}", length))
+    }
+
+    // length byte covers attribute and payload, so frame also has length, direction and read/write bytes
+    let frame_length = length + 2;
+    let has_checksum = match bytes.len() {
+      size if size == frame_length => false,
+      size if size == frame_length + 2 => true,
+      size => return Err(anyhow!("Length byte says {} bytes, but received {}", frame_length, size))
+    };
+
+    let direction = Direction::from_value(bytes[1])
+      .ok_or_else(|| anyhow!("Unknown direction: 0x{:02X}", bytes[1]))?;
+    let read_write = ReadWrite::from_value(bytes[2])
+      .ok_or_else(|| anyhow!("Unknown read/write: 0x{:02X}", bytes[2]))?;
+    let attribute = Attribute::from_address(bytes[3]);
+
+    let command = ScooterCommand {
+      direction,
+      read_write,
+      attribute,
+      payload: bytes[4..frame_length].to_vec()
+    };
+
+    if has_checksum && command.checksum()? != bytes[frame_length..] {
+      return Err(anyhow!("Invalid checksum: {:02X?}", &bytes[frame_length..]))
+    }
+
+    Ok(command)
+  }
+
+  /**
+   * Ninebot checksum of serialized command: sum of all bytes inverted, stored as little endian.
+   * Fails like `try_as_bytes` for payload over `MAX_PAYLOAD_LENGTH`
+   */
+  pub fn checksum(&self) -> Result<[u8; 2]> {
+    Ok(checksum::compute(&self.try_as_bytes()?))
+  }
+}
+
+
+/**
+ * 16 bit register value as scooter expects it, least significant byte first (0x0002 is 02 00)
+ */
+pub(crate) fn u16_le_payload(value: u16) -> Vec<u8> {
+  value.to_le_bytes().to_vec()
+}
+
+/**
+ * Builds ScooterCommand without spelling out every field. Direction defaults to controller which owns
+ * the attribute (see `Attribute::destination`) and command is read until payload is written
+ */
+#[derive(Clone, Debug, Default)]
+pub struct ScooterCommandBuilder {
+  direction: Option<Direction>,
+  read_write: Option<ReadWrite>,
+  attribute: Option<Attribute>,
+  payload: Vec<u8>
+}
+
+impl ScooterCommandBuilder {
+  pub fn direction(mut self, direction: Direction) -> Self {
+    self.direction = Some(direction);
+    self
+  }
+
+  pub fn read_write(mut self, read_write: ReadWrite) -> Self {
+    self.read_write = Some(read_write);
+    self
+  }
+
+  pub fn attribute(mut self, attribute: Attribute) -> Self {
+    self.attribute = Some(attribute);
+    self
+  }
+
+  pub fn payload(mut self, payload: &[u8]) -> Self {
+    self.payload = payload.to_vec();
+    self
+  }
+
+  /**
+   * Write 16 bit value, scooter expects it little endian (0x0002 is sent as 02 00)
+   */
+  pub fn write_u16(mut self, value: u16) -> Self {
+    self.read_write = Some(ReadWrite::Write);
+    self.payload = u16_le_payload(value);
+    self
+  }
+
+  /**
+   * Fails when attribute was not set
+   */
+  pub fn build(self) -> Result<ScooterCommand> {
+    let attribute = self.attribute
+      .ok_or_else(|| anyhow!("Command needs attribute"))?;
+
+    Ok(ScooterCommand {
+      direction: self.direction.unwrap_or_else(|| attribute.destination()),
+      read_write: self.read_write.unwrap_or(ReadWrite::Read),
+      attribute,
+      payload: self.payload
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_conflict_message() {
+    let mut buffer = [0u8; CONFLICT_MESSAGE_LENGTH];
+    assert_eq!(conflict_message(&mut buffer, &Attribute::TailLight, &Attribute::Unknown(0x7D)), "TailLight and Unknown both use 0x7D");
+
+    let mut buffer = [0u8; CONFLICT_MESSAGE_LENGTH];
+    assert_eq!(conflict_message(&mut buffer, &Attribute::Unknown(0x0A), &Attribute::Unknown(0x0A)), "Unknown and Unknown both use 0x0A");
+  }
+
+  #[test]
+  fn test_name_matches_debug() {
+    for attribute in Attribute::ALL {
+      assert_eq!(attribute.name(), alloc::format!("{:?}", attribute));
+    }
+  }
+}
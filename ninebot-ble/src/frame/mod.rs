@@ -0,0 +1,8 @@
+mod command;
+mod checksum;
+mod response;
+
+pub use command::{ScooterCommand, ScooterCommandBuilder, Direction, ReadWrite, Attribute, SecurityClass, MAX_PAYLOAD_LENGTH};
+#[cfg(feature = "std")]
+pub(crate) use command::u16_le_payload;
+pub use response::ScooterResponse;
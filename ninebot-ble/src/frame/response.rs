@@ -0,0 +1,137 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use anyhow::{Result, anyhow};
+
+use super::command::{Direction, ReadWrite, Attribute};
+use super::checksum;
+
+/**
+ * Frame received from the scooter. Layout is the same as for commands: length, direction, read/write,
+ * attribute and payload. Length byte counts read/write, attribute and payload bytes, anything after payload
+ * (random bytes from encryption or checksum) is ignored
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScooterResponse {
+  pub direction: Direction,
+  pub read_write: ReadWrite,
+  pub attribute: Attribute,
+  pub payload: Vec<u8>,
+  /**
+   * Bytes after payload: checksum for plain frames, random bytes for decrypted ones
+   */
+  pub trailer: Vec<u8>
+}
+
+impl ScooterResponse {
+  pub fn parse(bytes: &[u8]) -> Result<ScooterResponse> {
+    if bytes.len() < 4 {
+      return Err(anyhow!("Response is too short: {} bytes", bytes.len()))
+    }
+
+    let length = bytes[0] as usize;
+    if length < 2 {
+      return Err(anyhow!("Invalid length byte: {}", length))
+    }
+
+    let frame_length = length + 2;
+    if frame_length > bytes.len() {
+      return Err(anyhow!("Length byte says {} bytes, but received only {}", frame_length, bytes.len()))
+    }
+
+    let direction = Direction::from_value(bytes[1])
+      .ok_or_else(|| anyhow!("Unknown direction: 0x{:02X}", bytes[1]))?;
+    let read_write = ReadWrite::from_value(bytes[2])
+      .ok_or_else(|| anyhow!("Unknown read/write: 0x{:02X}", bytes[2]))?;
+    let attribute = Attribute::from_address(bytes[3]);
+
+    Ok(ScooterResponse {
+      direction,
+      read_write,
+      attribute,
+      payload: bytes[4..frame_length].to_vec(),
+      trailer: bytes[frame_length..].to_vec()
+    })
+  }
+
+  /**
+   * Check that trailer is checksum of the frame, only plain (not encrypted) frames carry it
+   */
+  pub fn verify_checksum(&self) -> Result<()> {
+    let mut bytes : Vec<u8> = vec![
+      self.payload.len() as u8 + 2,
+      self.direction.value(),
+      self.read_write.value(),
+      self.attribute.address()
+    ];
+    bytes.extend_from_slice(&self.payload);
+
+    let expected = checksum::compute(&bytes);
+    if self.trailer.len() < 2 || self.trailer[..2] != expected {
+      return Err(anyhow!("Invalid checksum, expected {:02X?} but received {:02X?}", expected, self.trailer))
+    }
+
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use hex_literal::hex;
+
+  #[test]
+  fn test_parse_battery_info() {
+    // decrypted battery info response, prefixed with size byte from encrypted frame
+    let bytes = hex!("0c250131f91c3f0001005c0e2d2d1178f518");
+    let response = ScooterResponse::parse(&bytes).unwrap();
+
+    assert_eq!(response.direction, Direction::BatteryToMaster);
+    assert_eq!(response.read_write, ReadWrite::Read);
+    assert_eq!(response.attribute, Attribute::BatteryInfo);
+    assert_eq!(response.payload, hex!("f91c3f0001005c0e2d2d").to_vec());
+  }
+
+  #[test]
+  fn test_parse_battery_percent() {
+    let bytes = hex!("0425013240006af89411");
+    let response = ScooterResponse::parse(&bytes).unwrap();
+
+    assert_eq!(response.attribute, Attribute::BatteryPercent);
+    assert_eq!(response.payload, vec![0x40, 0x00]);
+  }
+
+  #[test]
+  fn test_parse_unknown_attribute() {
+    let bytes = hex!("042501F0e803");
+    let response = ScooterResponse::parse(&bytes).unwrap();
+
+    assert_eq!(response.attribute, Attribute::Unknown(0xF0));
+    assert_eq!(response.payload, vec![0xE8, 0x03]);
+  }
+
+  #[test]
+  fn test_parse_length_exceeds_buffer() {
+    assert!(ScooterResponse::parse(&hex!("0c25013240")).is_err());
+    assert!(ScooterResponse::parse(&hex!("012501")).is_err());
+  }
+
+  #[test]
+  fn test_verify_checksum() {
+    let response = ScooterResponse::parse(&hex!("04250132400063FF")).unwrap();
+    assert!(response.verify_checksum().is_ok());
+  }
+
+  #[test]
+  fn test_reject_corrupted_checksum() {
+    let response = ScooterResponse::parse(&hex!("04250132400064FF")).unwrap();
+    assert!(response.verify_checksum().is_err());
+
+    let response = ScooterResponse::parse(&hex!("04250132400063")).unwrap();
+    assert!(response.verify_checksum().is_err());
+  }
+
+  #[test]
+  fn test_parse_unknown_direction() {
+    assert!(ScooterResponse::parse(&hex!("0499013240")).is_err());
+  }
+}
@@ -1,63 +1,93 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+#[cfg(feature = "std")]
 extern crate uuid;
 
 // 宣告模組
+/**
+ * Frame building, checksum and response parsing without any I/O, the only module available without `std`
+ */
+pub mod frame;
+#[cfg(feature = "std")]
 pub mod mi_crypto;
+#[cfg(feature = "std")]
 pub mod protocol;
+#[cfg(feature = "std")]
 pub mod consts;
+#[cfg(feature = "std")]
 pub mod clone_connection;
+#[cfg(feature = "std")]
 pub mod login;
+#[cfg(feature = "std")]
 pub mod scanner;
+#[cfg(feature = "std")]
 pub mod session;
+#[cfg(feature = "std")]
 pub mod android_api;
+#[cfg(feature = "std")]
 pub mod register;
+#[cfg(feature = "std")]
 pub mod connection;
+#[cfg(feature = "std")]
+pub mod uart_service;
+#[cfg(feature = "std")]
+pub mod cli;
 
 // 引用
+#[cfg(feature = "std")]
 pub use clone_connection::ScooterConnection;
-pub use scanner::{ScooterScanner, ScannerEvent};
+#[cfg(feature = "std")]
+pub use scanner::{ScooterScanner, ScannerEvent, DiscoveredScooter, ScooterAdvert, discover_scooters, parse_advertisement};
 
-pub use mi_crypto::AuthToken;
+#[cfg(feature = "std")]
+pub use mi_crypto::{AuthToken, KeySchedule, MiCryptoError};
+#[cfg(feature = "std")]
 pub use register::{RegistrationRequest, RegistrationError};
+#[cfg(feature = "std")]
 pub use login::LoginRequest;
+#[cfg(feature = "std")]
 pub use connection::ConnectionHelper;
+#[cfg(feature = "std")]
+pub use uart_service::{UartService, UartServiceError};
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use jni::JNIEnv;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use jni::objects::{JClass, JObject, JString, JValue, GlobalRef};
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use jni::JavaVM;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use log::{info, error, LevelFilter};
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use android_logger::Config;
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use tokio::runtime::Runtime;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use tokio::sync::mpsc;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter};
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use btleplug::platform::Manager;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use std::time::Duration;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use std::sync::{Mutex, Arc};
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use std::str::FromStr;
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 use once_cell::sync::Lazy;
 
 // --- Globals & Types (Thread-Safe + Arc) ---
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 static JAVA_VM: Lazy<Mutex<Option<Arc<JavaVM>>>> = Lazy::new(|| Mutex::new(None));
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 static BLE_MANAGER_CLASS: Lazy<Mutex<Option<GlobalRef>>> = Lazy::new(|| Mutex::new(None));
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[derive(Debug)]
 pub enum BleEvent {
     Status(String),
@@ -65,11 +95,11 @@ pub enum BleEvent {
     Data { speed: f64, battery: i32, temp: f64 },
 }
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 static EVENT_TX: Lazy<Mutex<Option<mpsc::Sender<BleEvent>>>> = Lazy::new(|| Mutex::new(None));
 
 // --- 1. Init ---
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[no_mangle]
 pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeInit(
     env: JNIEnv,
@@ -155,7 +185,7 @@ pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeInit(
 }
 
 // --- 2. Start Scan ---
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[no_mangle]
 pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeStartScan(
     _env: JNIEnv,
@@ -242,7 +272,7 @@ pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeStartScan(
 }
 
 // --- 3. Connect & Monitor ---
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[no_mangle]
 pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeConnect(
     env: JNIEnv,
@@ -395,25 +425,25 @@ pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeConnect(
 }
 
 // 輔助函數
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 async fn send_status(msg: &str) {
     if let Some(tx) = EVENT_TX.lock().unwrap().clone() {
         let _ = tx.send(BleEvent::Status(msg.to_string())).await;
     }
 }
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 async fn send_data(speed: f64, battery: i32, temp: f64) {
     if let Some(tx) = EVENT_TX.lock().unwrap().clone() {
         let _ = tx.send(BleEvent::Data { speed, battery, temp }).await;
     }
 }
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[no_mangle]
 pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeStopScan(_env: JNIEnv, _this: JObject) {}
 
-#[cfg(target_os = "android")]
+#[cfg(all(target_os = "android", feature = "std"))]
 #[no_mangle]
 pub extern "C" fn Java_com_rokid_m365hud_BleManager_nativeStartMonitoring(env: JNIEnv, this: JObject, j_address: JString) {
     Java_com_rokid_m365hud_BleManager_nativeConnect(env, this, j_address);
@@ -1,6 +1,6 @@
 use crate::mi_crypto::{
-  AuthToken, RandKey, LoginKeychain,
-  gen_rand_key, calc_login_did
+  AuthToken, RandKey, LoginKeychain, KeySchedule,
+  gen_rand_key, calc_login_did, verify_login
 };
 use crate::session::MiSession;
 use crate::consts::{MiCommands, Registers};
@@ -40,6 +40,7 @@ pub struct LoginRequest {
   remote_info: Option<[u8; 32]>,
   keys: Option<LoginKeychain>,
   remote_key: Option<Vec<u8>>,
+  key_schedule: KeySchedule,
 }
 
 impl LoginRequest {
@@ -55,11 +56,19 @@ impl LoginRequest {
         rand_key,
         protocol,
         device: device.clone(),
-        auth_token: token.clone()
+        auth_token: token.clone(),
+        key_schedule: KeySchedule::default()
       }
     )
   }
 
+  /**
+   * Key schedule used to derive session keys, V1 unless changed before `start`
+   */
+  pub fn set_key_schedule(&mut self, key_schedule: KeySchedule) {
+    self.key_schedule = key_schedule;
+  }
+
   pub async fn start(&mut self) -> Result<MiSession> {
     self.send_key().await?;
     self.read_remote_key().await?;
@@ -103,29 +112,33 @@ impl LoginRequest {
   async fn validate_remote_key_and_send_did(&mut self) -> Result<bool, LoginError> {
     tracing::info!("Validating did");
 
-    let rand_key = self.rand_key.as_mut();
-    let remote_key = self.remote_key.as_mut().unwrap();
+    let rand_key = &self.rand_key;
+    let remote_key = self.remote_key.as_deref().unwrap();
     let remote_info = self.remote_info.unwrap();
 
-    let (info, expected_remote_info, keys) = calc_login_did(rand_key, remote_key, &self.auth_token);
-    if remote_info == expected_remote_info {
-      tracing::debug!("Remote info is as expected, sending did");
+    let (info, expected_remote_info, keys) = calc_login_did(self.key_schedule, rand_key, remote_key, &self.auth_token)
+      .map_err(|err| LoginError::Other(err.into()))?;
+    let keys = match verify_login(&remote_info, &expected_remote_info, keys) {
+      Ok(keys) => keys,
+      Err(_) => {
+        tracing::error!("Scooter send invalid remote key:");
+        tracing::error!("   Expected: {:?}", expected_remote_info.hex_dump());
+        tracing::error!("   Received: {:?}", remote_info.hex_dump());
 
-      self.protocol.write(&Registers::AVDTP, MiCommands::CMD_SEND_INFO).await?;
+        return Err(LoginError::InvalidDid)
+      }
+    };
 
-      self.protocol.wait_for_scooter_to_receive_data().await?;
-      self.protocol.write_mi_parcel(&Registers::AVDTP, &info).await?;
-      self.protocol.wait_for_scooter_to_ack_data().await?;
-      self.keys = Some(keys);
+    tracing::debug!("Remote info is as expected, sending did");
 
-      return Ok(true)
-    }
+    self.protocol.write(&Registers::AVDTP, MiCommands::CMD_SEND_INFO).await?;
 
-    tracing::error!("Scooter send invalid remote key:");
-    tracing::error!("   Expected: {:?}", expected_remote_info.hex_dump());
-    tracing::error!("   Received: {:?}", remote_info.hex_dump());
+    self.protocol.wait_for_scooter_to_receive_data().await?;
+    self.protocol.write_mi_parcel(&Registers::AVDTP, &info).await?;
+    self.protocol.wait_for_scooter_to_ack_data().await?;
+    self.keys = Some(keys);
 
-    Err(LoginError::InvalidDid)
+    Ok(true)
   }
 
   async fn confirm(&mut self) -> Result<bool, LoginError> {
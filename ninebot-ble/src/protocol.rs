@@ -1,9 +1,11 @@
 use crate::consts::{MiCommands, Registers};
+use crate::uart_service::UartService;
 use uuid::Uuid;
 use futures::Stream;
 use futures::stream::StreamExt;
 use pretty_hex::*;
 use std::{pin::Pin, boxed::Box};
+use std::sync::Mutex;
 use btleplug::platform::{Peripheral};
 use tokio::time::timeout;
 use std::time::Duration;
@@ -22,23 +24,31 @@ pub struct MiProtocol {
   upnp: Characteristic,
   tx: Characteristic,
   rx: Characteristic,
-  stream: Pin<Box<dyn Stream<Item = ValueNotification> + Send>>,
+  uart: UartService,
+  /**
+   * btleplug's stream is Send but not Sync, behind Mutex `&MiProtocol` can be held across await in Send futures.
+   * Only reached through `&mut self`, so it is never actually locked
+   */
+  stream: Mutex<NotificationStream>,
 }
 
+type NotificationStream = Pin<Box<dyn Stream<Item = ValueNotification> + Send>>;
+
 impl MiProtocol {
   pub async fn new(device: &Peripheral) -> Result<Self> {
-    let (avdtp, upnp, tx, rx) = setup_channels(&device).await?;
-    let stream : Pin<Box<dyn Stream<Item = ValueNotification> + Send>> = device.notifications().await
+    let (avdtp, upnp, uart, tx, rx) = setup_channels(&device).await?;
+    let stream : NotificationStream = device.notifications().await
       .with_context(|| format!("Could not load notifications stream"))?;
     let device = device.clone();
 
     let instance = Self {
       device,
-      stream,
+      stream: Mutex::new(stream),
       avdtp,
       upnp,
       tx,
-      rx
+      rx,
+      uart
     };
 
     Ok(instance)
@@ -52,6 +62,13 @@ impl MiProtocol {
     Ok(true)
   }
 
+  /**
+   * UART service and characteristics this scooter uses
+   */
+  pub fn uart(&self) -> UartService {
+    self.uart
+  }
+
   fn reg_to_channel(&self, reg : &Registers) -> Option<&Characteristic> {
     match reg {
       Registers::RX => Some(&self.rx),
@@ -67,7 +84,12 @@ impl MiProtocol {
    */
   pub async fn next(&mut self) -> Option<ValueNotification> {
     tracing::debug!("Waiting for notifications...");
-    self.stream.next().await
+    self.stream().next().await
+  }
+
+  fn stream(&mut self) -> &mut NotificationStream {
+    // poisoned only if a panic happened while polling, the stream itself is still usable
+    self.stream.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner())
   }
 
   pub async fn wait_for_scooter_to_receive_data(&mut self) -> Result<bool> {
@@ -165,14 +187,14 @@ impl MiProtocol {
     let mut total_frames : u16 = 0;
     let mut received_data : Vec<u8> = Vec::new();
 
-    if let Some(data) = self.stream.next().await {
+    if let Some(data) = self.stream().next().await {
       total_frames = data.value[4] as u16 + 0x100 * data.value[5] as u16;
       tracing::debug!("Expecting {} frames: {:?}", total_frames, data.value.hex_dump());
 
       self.write(reg, MiCommands::RCV_RDY).await?;
     }
 
-    while let Some(data) = self.stream.next().await {
+    while let Some(data) = self.stream().next().await {
       let current_frame : u16 = what_frame(&data.value);
       tracing::debug!("Current frame {}: {:?}", current_frame, data.value.hex_dump());
 
@@ -191,12 +213,20 @@ impl MiProtocol {
     Ok(received_data)
   }
 
-  pub async fn write_nb_parcel(&self, reg: &Registers, data: &[u8]) -> Result<bool> {
+  pub async fn write_nb_parcel(&mut self, reg: &Registers, data: &[u8]) -> Result<bool> {
+    self.write_nb_parcel_as(reg, data, WriteType::WithoutResponse).await
+  }
+
+  /**
+   * Same as write_nb_parcel, but every chunk is written with given GATT write type. WithResponse waits
+   * for scooter to confirm each chunk, which is slower but reports lost writes
+   */
+  pub async fn write_nb_parcel_as(&mut self, reg: &Registers, data: &[u8], write_type: WriteType) -> Result<bool> {
     let channel = self.reg_to_channel(reg).unwrap();
 
     for chunk in data.chunks(NB_CHUNK_SIZE) {
       tracing::debug!("Writing nb chunk to {:?}: {:?}", reg, chunk.hex_dump());
-      self.device.write(&channel, &chunk, WriteType::WithoutResponse).await
+      self.device.write(&channel, &chunk, write_type).await
         .with_context(|| format!("Could not write mi chunk: for channel: {:?}", channel))?;
     }
 
@@ -250,7 +280,7 @@ fn what_frame(bytes: &Vec<u8>) -> u16 {
   bytes[0] as u16 & 0xff + 0x100 * bytes[1] as u16 & 0xff
 }
 
-async fn setup_channels(device : &Peripheral) -> Result<(Characteristic, Characteristic, Characteristic, Characteristic)> {
+async fn setup_channels(device : &Peripheral) -> Result<(Characteristic, Characteristic, UartService, Characteristic, Characteristic)> {
   let mut retries = 5;
   loop {
     // Windows BLE: verify connection is stable before discovering services
@@ -301,8 +331,8 @@ async fn setup_channels(device : &Peripheral) -> Result<(Characteristic, Charact
 
   // UART channels
   tracing::debug!("Setting up UART channels");
-  let tx = find_characteristic(device, Registers::UART.to_uuid(), Registers::TX.to_uuid()).await?;
-  let rx = find_characteristic(device, Registers::UART.to_uuid(), Registers::RX.to_uuid()).await?;
+  let characteristics : Vec<Characteristic> = device.characteristics().into_iter().collect();
+  let (uart, tx, rx) = UartService::resolve(&characteristics)?;
 
   tracing::debug!("Enabling notify for AVDTP");
   device.subscribe(&avdtp).await
@@ -316,5 +346,5 @@ async fn setup_channels(device : &Peripheral) -> Result<(Characteristic, Charact
   device.subscribe(&rx).await
     .with_context(|| format!("Could not subscribe to scooter RX notifications"))?;
 
-  Ok((avdtp, upnp, tx, rx))
+  Ok((avdtp, upnp, uart, tx, rx))
 }
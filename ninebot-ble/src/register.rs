@@ -1,7 +1,7 @@
 use crate::consts::{MiCommands, Registers};
 pub use crate::mi_crypto::AuthToken;
 use crate::protocol::MiProtocol;
-use crate::mi_crypto;
+use crate::mi_crypto::{self, KeySchedule};
 
 use pretty_hex::*;
 use btleplug::platform::Peripheral;
@@ -30,7 +30,8 @@ pub struct RegistrationRequest {
   my_secret_key: EphemeralSecret,
   my_public_key: PublicKey,
   remote_info: Option<Vec<u8>>,
-  token: Option<AuthToken>
+  token: Option<AuthToken>,
+  key_schedule: KeySchedule
 }
 
 impl RegistrationRequest {
@@ -48,12 +49,20 @@ impl RegistrationRequest {
       my_secret_key,
       my_public_key,
       remote_info: None,
-      token: None
+      token: None,
+      key_schedule: KeySchedule::default()
     };
 
     Ok(request)
   }
 
+  /**
+   * Key schedule used to derive token and DID ciphertext, V1 unless changed before `start`
+   */
+  pub fn set_key_schedule(&mut self, key_schedule: KeySchedule) {
+    self.key_schedule = key_schedule;
+  }
+
   /**
    * Starting registration process. In some cases there will be RegistrationError.
    * For this error please disconnect and connect again to scooter and ask user to press power button. Remember to create new instance of
@@ -126,7 +135,7 @@ impl RegistrationRequest {
     let remote_key_bytes = self.protocol.read_mi_parcel(&Registers::AVDTP).await?;
     let remote_info = self.remote_info.as_ref().unwrap();
     let remote_key_bytes = [&[0x04], remote_key_bytes.as_slice()].concat();
-    let (did_ct, token) = mi_crypto::calc_did(&self.my_secret_key, &remote_key_bytes, &remote_info);
+    let (did_ct, token) = mi_crypto::calc_did(self.key_schedule, &self.my_secret_key, &remote_key_bytes, &remote_info)?;
 
     self.token = Some(token);
     self.protocol.write(&Registers::AVDTP, MiCommands::CMD_SEND_DID).await?;
@@ -2,21 +2,37 @@ use std::hash::Hash;
 use anyhow::Result;
 use tokio::sync::mpsc;
 use std::collections::HashSet;
-use futures::stream::StreamExt;
+use futures::stream::{Stream, StreamExt};
 use btleplug::platform::{Adapter, Manager, PeripheralId, Peripheral};
-use btleplug::api::{Central, Manager as _, ScanFilter, BDAddr, Peripheral as _, CentralEvent};
+use btleplug::api::{Central, Manager as _, ScanFilter, BDAddr, Peripheral as _, CentralEvent, PeripheralProperties};
 use thiserror::Error;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+use crate::connection::ConnectionHelper;
+use crate::mi_crypto::LoginKeychain;
+use crate::session::MiSession;
+
 type Devices = Arc<RwLock<HashSet<TrackedDevice>>>;
 
 /**
  * All xiaomi scooters start with name MIScooter and random numbers after tha
  */
 const XIAOMI_SCOOTER_NAME : &str = "MIScooter";
+/**
+ * Names scooters advertise with, older firmwares shorten it to MISc
+ */
+const SCOOTER_NAME_PREFIXES : [&str; 2] = [XIAOMI_SCOOTER_NAME, "MISc"];
 const XIAOMI_SERVICE_UUID : &str = "0000fe95-0000-1000-8000-00805f9b34fb";
+/**
+ * Frame control bit telling that MiBeacon carries MAC address of device
+ */
+const MIBEACON_HAS_MAC : u16 = 0x0010;
+/**
+ * Frame control, product id and frame counter, present in every MiBeacon
+ */
+const MIBEACON_HEADER_LEN : usize = 5;
 
 #[derive(Error, Debug)]
 pub enum ScannerError {
@@ -65,7 +81,7 @@ impl TrackedDevice {
     }
 
     if let Some(name) = &self.name {
-      return name.starts_with(XIAOMI_SCOOTER_NAME);
+      return is_scooter_name(name);
     }
     return false;
   }
@@ -173,6 +189,125 @@ impl ScooterScanner {
   }
 }
 
+/**
+ * Check if advertised name belongs to M365 scooter
+ */
+pub fn is_scooter_name(name: &str) -> bool {
+  SCOOTER_NAME_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+/**
+ * Details scooter advertises in Xiaomi (0xFE95) service data
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScooterAdvert {
+  /**
+   * Product id, tells which scooter model is advertising
+   */
+  pub product_id: u16,
+  /**
+   * MAC address of scooter, only present when firmware puts it into advertisement
+   */
+  pub mac: Option<BDAddr>,
+}
+
+/**
+ * Parse MiBeacon from Xiaomi service data: [frame control][product id][frame counter][mac]...
+ * Multibyte fields are little endian, MAC is reversed. Returns None when data is too short to hold them
+ */
+pub fn parse_advertisement(data: &[u8]) -> Option<ScooterAdvert> {
+  if data.len() < MIBEACON_HEADER_LEN {
+    return None
+  }
+
+  let frame_control = u16::from_le_bytes([data[0], data[1]]);
+  let product_id = u16::from_le_bytes([data[2], data[3]]);
+
+  let mac = if frame_control & MIBEACON_HAS_MAC != 0 {
+    let mut mac : [u8; 6] = data.get(MIBEACON_HEADER_LEN..MIBEACON_HEADER_LEN + 6)?.try_into().ok()?;
+    mac.reverse();
+    Some(BDAddr::from(mac))
+  } else {
+    None
+  };
+
+  Some(ScooterAdvert { product_id, mac })
+}
+
+/**
+ * Scooter seen by `discover_scooters`, with everything needed to show it in a list and connect to it
+ */
+#[derive(Clone, Debug)]
+pub struct DiscoveredScooter {
+  pub addr: BDAddr,
+  pub name: String,
+  pub rssi: Option<i16>,
+  pub advert: Option<ScooterAdvert>,
+  peripheral: Peripheral,
+}
+
+impl DiscoveredScooter {
+  /**
+   * Connect to scooter and open session over its UART service. Keys come from `LoginRequest`,
+   * scooter drops the link shortly after connect if nobody logs in
+   */
+  pub async fn connect(&self, keys: &LoginKeychain) -> Result<MiSession> {
+    ConnectionHelper::new(&self.peripheral).connect().await?;
+
+    Ok(MiSession::new(&self.peripheral, keys).await?)
+  }
+
+  pub fn peripheral(&self) -> &Peripheral {
+    &self.peripheral
+  }
+}
+
+/**
+ * Name and signal strength of advertisement, if it comes from scooter
+ */
+fn scooter_advertisement(props: &PeripheralProperties) -> Option<(String, Option<i16>)> {
+  let name = props.local_name.as_ref()?;
+
+  if is_scooter_name(name) {
+    Some((name.clone(), props.rssi))
+  } else {
+    None
+  }
+}
+
+/**
+ * Scan for scooters on given adapter, every scooter is yielded once when it is first seen.
+ * Scanning continues until the stream is dropped and adapter stops scanning
+ */
+pub async fn discover_scooters(adapter: &Adapter) -> Result<impl Stream<Item = DiscoveredScooter> + Send> {
+  let events = adapter.events().await?;
+  adapter.start_scan(ScanFilter::default()).await?;
+  tracing::debug!("Discovering scooters");
+
+  let adapter = adapter.clone();
+  let scooters = events.filter_map(move |event| {
+    let adapter = adapter.clone();
+
+    async move {
+      let CentralEvent::DeviceDiscovered(peer_id) = event else {
+        return None
+      };
+
+      let peripheral = adapter.peripheral(&peer_id).await.ok()?;
+      let props = peripheral.properties().await.ok()??;
+      let (name, rssi) = scooter_advertisement(&props)?;
+      let advert = props.service_data
+        .get(&Uuid::parse_str(XIAOMI_SERVICE_UUID).unwrap())
+        .and_then(|data| parse_advertisement(data));
+      tracing::debug!("Discovered scooter {} ({}) rssi: {:?} advert: {:?}", name, props.address, rssi, advert);
+
+      Some(DiscoveredScooter { addr: props.address, name, rssi, advert, peripheral })
+    }
+  });
+
+  Ok(scooters)
+}
+
 struct CentralEventsProcessor {
   central: Adapter,
   tx: mpsc::Sender<ScannerEvent>,
@@ -250,3 +385,67 @@ async fn find_central(manager: &Manager) -> Result<Adapter, ScannerError> {
     Err(ScannerError::MissingCentral)
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn advertisement(name: Option<&str>, rssi: Option<i16>) -> PeripheralProperties {
+    PeripheralProperties {
+      local_name: name.map(|name| name.to_owned()),
+      rssi,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn test_scooter_names() {
+    assert!(is_scooter_name("MIScooter7353"));
+    assert!(is_scooter_name("MISc1234"));
+    assert!(!is_scooter_name("Mi Smart Band 6"));
+    assert!(!is_scooter_name("miscooter7353"));
+    assert!(!is_scooter_name(""));
+  }
+
+  #[test]
+  fn test_scooter_advertisement() {
+    assert_eq!(
+      scooter_advertisement(&advertisement(Some("MIScooter7353"), Some(-67))),
+      Some(("MIScooter7353".to_owned(), Some(-67)))
+    );
+    assert_eq!(scooter_advertisement(&advertisement(Some("MISc0042"), None)), Some(("MISc0042".to_owned(), None)));
+  }
+
+  #[test]
+  fn test_other_advertisements_are_ignored() {
+    assert_eq!(scooter_advertisement(&advertisement(Some("JBL Flip 5"), Some(-40))), None);
+    assert_eq!(scooter_advertisement(&advertisement(None, Some(-40))), None);
+  }
+
+  #[test]
+  fn test_parse_advertisement_with_mac() {
+    // frame control 0x3050 (MAC included), product 0x0B2D, counter 0x07, MAC reversed, capability
+    let data = [0x50, 0x30, 0x2d, 0x0b, 0x07, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x08];
+
+    assert_eq!(parse_advertisement(&data), Some(ScooterAdvert {
+      product_id: 0x0B2D,
+      mac: Some(BDAddr::from([0x11, 0x22, 0x33, 0x44, 0x55, 0x66])),
+    }));
+  }
+
+  #[test]
+  fn test_parse_advertisement_without_mac() {
+    // frame control 0x3000, MAC is left out after pairing
+    let data = [0x00, 0x30, 0x2d, 0x0b, 0x12];
+
+    assert_eq!(parse_advertisement(&data), Some(ScooterAdvert { product_id: 0x0B2D, mac: None }));
+  }
+
+  #[test]
+  fn test_parse_truncated_advertisement() {
+    assert_eq!(parse_advertisement(&[]), None);
+    assert_eq!(parse_advertisement(&[0x00, 0x30, 0x2d, 0x0b]), None);
+    // MAC flag set but only 3 bytes of it present
+    assert_eq!(parse_advertisement(&[0x50, 0x30, 0x2d, 0x0b, 0x07, 0x66, 0x55, 0x44]), None);
+  }
+}
@@ -0,0 +1,107 @@
+//! Warnings for M365 scooter
+//!
+//! Unlike faults (see `read_error_code`) warnings don't stop the scooter, they tell rider
+//! that something needs attention soon, e.g. battery is almost empty. HUD shows them as
+//! amber indicators.
+//!
+//! ## Protocol Reference
+//! - Read 2 bytes from address 0xB1, Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01)
+//! - Value: u16 bitmask, several warnings can be active at once
+//!   - bit 0: battery low
+//!   - bit 1: controller temperature close to the limit
+//!   - bit 2: scooter is locked
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+
+use anyhow::Result;
+use bitflags::bitflags;
+
+bitflags! {
+    /// Active warnings, bits firmware sets but we don't know yet are kept as they are
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct AlarmFlags: u16 {
+        const LOW_BATTERY = 0x0001;
+        const HIGH_TEMP = 0x0002;
+        const LOCK_ENGAGED = 0x0004;
+    }
+}
+
+fn parse_alarm_status(payload: Payload) -> Result<AlarmFlags> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(AlarmFlags::from_bits_retain(payload.pop_u16()?))
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read warnings which don't stop the scooter, empty set when there are none
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::AlarmFlags;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// if session.read_alarm_status().await?.contains(AlarmFlags::LOW_BATTERY) {
+    ///     println!("Charge soon");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_alarm_status(&mut self) -> Result<AlarmFlags, SessionError> {
+        tracing::debug!("Reading alarm status");
+
+        self.send(&ScooterCommand::read(Attribute::AlarmStatus.destination(), Attribute::AlarmStatus, 0x02)).await?;
+
+        let payload = self.read_frame().await?;
+        let alarms = parse_alarm_status(payload)?;
+        tracing::debug!("alarms: {:?}", alarms);
+
+        Ok(alarms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alarm_response(raw: u16) -> Payload {
+        let mut bytes = vec![0x23, 0x01, 0xB1];
+        bytes.extend_from_slice(&raw.to_le_bytes());
+        bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+        Payload::from(bytes)
+    }
+
+    #[test]
+    fn test_no_alarms() {
+        assert!(parse_alarm_status(alarm_response(0)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_multiple_alarms() {
+        let alarms = parse_alarm_status(alarm_response(0x0005)).unwrap();
+
+        assert_eq!(alarms, AlarmFlags::LOW_BATTERY | AlarmFlags::LOCK_ENGAGED);
+        assert!(!alarms.contains(AlarmFlags::HIGH_TEMP));
+    }
+
+    #[test]
+    fn test_all_alarms() {
+        assert_eq!(parse_alarm_status(alarm_response(0x0007)).unwrap(), AlarmFlags::all());
+    }
+
+    #[test]
+    fn test_unknown_bits_are_kept() {
+        let alarms = parse_alarm_status(alarm_response(0x8002)).unwrap();
+
+        assert!(alarms.contains(AlarmFlags::HIGH_TEMP));
+        assert_eq!(alarms.bits(), 0x8002);
+    }
+
+    #[test]
+    fn test_alarm_status_read_command() {
+        let cmd = ScooterCommand::read(Attribute::AlarmStatus.destination(), Attribute::AlarmStatus, 0x02);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB1, 0x02]);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{MiSession, Payload};
+use super::{MiSession, Payload, Transport, SessionError};
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
 
 use anyhow::Result;
@@ -6,6 +6,12 @@ use serde::Serialize;
 
 pub type BatteryCellsVoltage = [f32; 10];
 
+/**
+ * Cell voltage block has room for 13 cells, 10S packs report zeros in the last three
+ */
+const MAX_CELLS: usize = 13;
+const MIN_CELLS: usize = 10;
+
 #[derive(Debug, Serialize)]
 pub struct BatteryInfo {
   /**
@@ -46,24 +52,26 @@ impl TryFrom<Payload> for BatteryInfo {
   }
 }
 
-impl MiSession {
+impl<T: Transport> MiSession<T> {
   /**
    * Battery voltage in volts
    */
-  pub async fn battery_voltage(&mut self) -> Result<f32> {
-    tracing::debug!("Reading battery voltage");
+  pub async fn battery_voltage(&mut self) -> Result<f32, SessionError> {
+    self.read_battery_voltage().await
+  }
 
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::BatteryVoltage,
-      payload: vec![0x02]
-    }).await?;
+  /**
+   * Battery voltage in volts, reported by BMS in 10 mV units. Read from BMS register 0x34 (`MasterToBattery`),
+   * not 0xB0: that address is `Attribute::MotorInfo` in the ESC block, and the address check in frame/command.rs
+   * fails the build for two attributes sharing it
+   */
+  pub async fn read_battery_voltage(&mut self) -> Result<f32, SessionError> {
+    tracing::debug!("Reading battery voltage");
 
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
+    self.send(&ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryVoltage, 0x02)).await?;
 
-    let voltage = payload.pop_u16()? as f32 / 100.0;
+    let payload = self.read(2).await?;
+    let voltage = parse_battery_voltage(payload)?;
 
     Ok(voltage)
   }
@@ -71,7 +79,7 @@ impl MiSession {
   /**
    * Return amperage in Ampere
    */
-  pub async fn battery_amperage(&mut self) -> Result<f32> {
+  pub async fn battery_amperage(&mut self) -> Result<f32, SessionError> {
     tracing::debug!("Reading battery amperage");
 
     self.send(&ScooterCommand {
@@ -90,27 +98,28 @@ impl MiSession {
   }
 
   /**
-   * Return amperage in Ampere
+   * Charge left in percents
    */
-  pub async fn battery_percentage(&mut self) -> Result<f32> {
-    tracing::debug!("Reading battery amperage");
+  pub async fn battery_percentage(&mut self) -> Result<f32, SessionError> {
+    Ok(self.read_battery_percent().await? as f32)
+  }
 
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::BatteryPercent,
-      payload: vec![0x02]
-    }).await?;
+  /**
+   * Charge left in percents, between 0 and 100. Read from BMS register 0x32 (`MasterToBattery`), where the BMS
+   * keeps charge next to voltage and current. 0xB4 is in the ESC block, which reports speed and trip values
+   */
+  pub async fn read_battery_percent(&mut self) -> Result<u8, SessionError> {
+    tracing::debug!("Reading battery percent");
 
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
+    self.send(&ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02)).await?;
 
-    let percent = payload.pop_u16()? as f32;
+    let payload = self.read(2).await?;
+    let percent = parse_battery_percent(payload)?;
 
     Ok(percent)
   }
 
-  pub async fn battery_cell_voltages(&mut self) -> Result<BatteryCellsVoltage> {
+  pub async fn battery_cell_voltages(&mut self) -> Result<BatteryCellsVoltage, SessionError> {
     tracing::debug!("Reading battery cell voltages");
 
     self.send(&ScooterCommand {
@@ -139,7 +148,52 @@ impl MiSession {
     Ok(voltages)
   }
 
-  pub async fn battery_info(&mut self) -> Result<BatteryInfo> {
+  /**
+   * Voltage of each cell in volts, 10 or 13 values depending on battery pack
+   */
+  pub async fn read_cell_voltages(&mut self) -> Result<Vec<f32>, SessionError> {
+    tracing::debug!("Reading cell voltages");
+
+    self.send(&ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryCellVoltages, (MAX_CELLS * 2) as u8)).await?;
+
+    let payload = self.read_frame().await?;
+    let voltages = parse_cell_voltages(payload)?;
+    tracing::debug!("cell voltages: {:?}", voltages);
+
+    Ok(voltages)
+  }
+
+  /**
+   * Number of full charge cycles pack went through
+   */
+  pub async fn read_battery_cycles(&mut self) -> Result<u16, SessionError> {
+    tracing::debug!("Reading battery cycles");
+
+    self.send(&ScooterCommand::read(Attribute::BatteryCycles.destination(), Attribute::BatteryCycles, 0x02)).await?;
+
+    let payload = self.read_frame().await?;
+    let cycles = parse_battery_cycles(payload)?;
+    tracing::debug!("battery cycles: {}", cycles);
+
+    Ok(cycles)
+  }
+
+  /**
+   * Capacity of fully charged pack in percents of its factory capacity, between 0 and 100
+   */
+  pub async fn read_battery_health(&mut self) -> Result<u8, SessionError> {
+    tracing::debug!("Reading battery health");
+
+    self.send(&ScooterCommand::read(Attribute::BatteryHealth.destination(), Attribute::BatteryHealth, 0x02)).await?;
+
+    let payload = self.read_frame().await?;
+    let health = parse_battery_health(payload)?;
+    tracing::debug!("battery health: {}%", health);
+
+    Ok(health)
+  }
+
+  pub async fn battery_info(&mut self) -> Result<BatteryInfo, SessionError> {
     self.send(&ScooterCommand {
       direction: Direction::MasterToBattery,
       read_write: ReadWrite::Read,
@@ -154,3 +208,163 @@ impl MiSession {
     )
   }
 }
+
+pub(super) fn parse_battery_voltage(payload: Payload) -> Result<f32> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_u16()? as f32 / 100.0)
+}
+
+pub(super) fn parse_battery_percent(payload: Payload) -> Result<u8> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_u16()?.min(100) as u8)
+}
+
+/**
+ * Every cell is little endian word in millivolts
+ */
+fn parse_cell_voltages(payload: Payload) -> Result<Vec<f32>> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  let mut voltages = Vec::with_capacity(MAX_CELLS);
+  for _ in 0..MAX_CELLS {
+    voltages.push(payload.pop_u16()? as f32 / 1000.0);
+  }
+
+  if voltages[MIN_CELLS..].iter().all(|voltage| *voltage == 0.0) {
+    voltages.truncate(MIN_CELLS);
+  }
+
+  Ok(voltages)
+}
+
+pub(super) fn parse_battery_cycles(payload: Payload) -> Result<u16> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  payload.pop_u16()
+}
+
+/**
+ * Fresh pack can report slightly over 100%, health is capped so it reads as percentage
+ */
+pub(super) fn parse_battery_health(payload: Payload) -> Result<u8> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_u16()?.min(100) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mi_crypto::{encrypt_uart, decrypt_uart, EncryptionKey, LoginKeychain};
+  use crate::session::transport::MockTransport;
+
+  fn cells_response(millivolts: &[u16]) -> Payload {
+    let mut bytes = vec![0x25, 0x01, 0x40];
+    for cell in millivolts {
+      bytes.extend_from_slice(&cell.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[0x3e, 0x91, 0x07, 0xc2]); // random bytes
+    Payload::from(bytes)
+  }
+
+  #[test]
+  fn test_parse_ten_cell_pack() {
+    let block = [4012, 4015, 4009, 4011, 4013, 4010, 4014, 4012, 4008, 4011, 0, 0, 0];
+
+    let voltages = parse_cell_voltages(cells_response(&block)).unwrap();
+
+    assert_eq!(voltages.len(), 10);
+    assert_eq!(voltages[0], 4.012);
+    assert_eq!(voltages[1], 4.015);
+    assert_eq!(voltages[8], 4.008);
+    assert_eq!(voltages[9], 4.011);
+  }
+
+  #[test]
+  fn test_parse_thirteen_cell_pack() {
+    let block = [3701, 3699, 3702, 3700, 3698, 3701, 3703, 3700, 3699, 3702, 3701, 3700, 3697];
+
+    let voltages = parse_cell_voltages(cells_response(&block)).unwrap();
+
+    assert_eq!(voltages.len(), 13);
+    assert_eq!(voltages[0], 3.701);
+    assert_eq!(voltages[12], 3.697);
+  }
+
+  #[test]
+  fn test_parse_truncated_block() {
+    let mut bytes = vec![0x25, 0x01, 0x40];
+    bytes.extend_from_slice(&[0xac, 0x0f, 0xaf, 0x0f]);
+
+    assert!(parse_cell_voltages(Payload::from(bytes)).is_err());
+  }
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  #[tokio::test]
+  async fn test_read_battery_percent_with_mock_transport() {
+    let keys = test_keys();
+    // BMS answers 87%
+    let response = encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![response]), &keys);
+
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+
+    let written = &session.transport().written;
+    assert_eq!(written.len(), 1);
+    let request = decrypt_uart(&keys.app, &written[0]).unwrap();
+    assert_eq!(&request[..4], &[0x22, 0x01, 0x32, 0x02]);
+  }
+
+  fn word_response(attribute: u8, value: u16) -> Payload {
+    let mut bytes = vec![0x25, 0x01, attribute];
+    bytes.extend_from_slice(&value.to_le_bytes());
+    bytes.extend_from_slice(&[0x3e, 0x91, 0x07, 0xc2]); // random bytes
+    Payload::from(bytes)
+  }
+
+  #[test]
+  fn test_parse_battery_cycles() {
+    assert_eq!(parse_battery_cycles(word_response(0x1B, 0x0087)).unwrap(), 135);
+    assert_eq!(parse_battery_cycles(word_response(0x1B, 0)).unwrap(), 0);
+    assert!(parse_battery_cycles(Payload::from(vec![0x25, 0x01, 0x1B])).is_err());
+  }
+
+  #[test]
+  fn test_parse_battery_health() {
+    assert_eq!(parse_battery_health(word_response(0x3B, 0x0055)).unwrap(), 85);
+    assert_eq!(parse_battery_health(word_response(0x3B, 103)).unwrap(), 100);
+  }
+
+  #[tokio::test]
+  async fn test_battery_cycles_and_health_are_read_from_bms() {
+    let keys = test_keys();
+    let responses = vec![
+      encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x1B, 0x87, 0x00], 0, None),
+      encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x3B, 0x55, 0x00], 0, None),
+    ];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    assert_eq!(session.read_battery_cycles().await.unwrap(), 135);
+    assert_eq!(session.read_battery_health().await.unwrap(), 85);
+
+    let written = &session.transport().written;
+    let cycles_request = decrypt_uart(&keys.app, &written[0]).unwrap();
+    let health_request = decrypt_uart(&keys.app, &written[1]).unwrap();
+    assert_eq!(&cycles_request[..4], &[0x22, 0x01, 0x1B, 0x02]);
+    assert_eq!(&health_request[..4], &[0x22, 0x01, 0x3B, 0x02]);
+  }
+}
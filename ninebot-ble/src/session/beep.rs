@@ -0,0 +1,108 @@
+//! Buzzer control for M365 scooter
+//!
+//! Makes the scooter beep, which helps to find it in a crowded rack.
+//!
+//! ## Protocol Reference
+//! - Beep: Write 0x0001 to address 0x7A
+//! - Direction: Master to Motor Controller (0x20)
+//! - Command Type: Write (0x03)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+
+use std::time::Duration;
+use anyhow::Result;
+
+/// Pause between beeps of `beep_pattern`, shorter gap merges beeps into one long tone
+pub const BEEP_PATTERN_GAP: Duration = Duration::from_millis(300);
+
+fn beep_command() -> ScooterCommand {
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute: Attribute::Beep,
+        payload: vec![0x01, 0x00] // 0x0001 little-endian: trigger single beep
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Beep once
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.beep().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn beep(&mut self) -> Result<(), SessionError> {
+        tracing::debug!("Beeping");
+
+        self.send(&beep_command()).await?;
+
+        Ok(())
+    }
+
+    /// Beep `count` times with `BEEP_PATTERN_GAP` between beeps
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.beep_pattern(3).await?; // Which one is mine?
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn beep_pattern(&mut self, count: u8) -> Result<(), SessionError> {
+        for i in 0..count {
+            if i > 0 {
+                tokio::time::sleep(BEEP_PATTERN_GAP).await;
+            }
+
+            self.beep().await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::commands::SecurityClass;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{EncryptionKey, LoginKeychain};
+
+    #[test]
+    fn test_beep_command_bytes() {
+        let cmd = beep_command();
+
+        // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7A) + Payload(0x01, 0x00)
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7A, 0x01, 0x00]);
+        assert_eq!(cmd.checksum().unwrap(), [0x5D, 0xFF]);
+        assert_eq!(&cmd.as_frame().unwrap()[8..], &[0x5D, 0xFF]);
+    }
+
+    #[test]
+    fn test_beep_is_public() {
+        assert!(Attribute::Beep.writable_by_user());
+        assert!(SecurityClass::Public.allows(&beep_command()));
+    }
+
+    #[tokio::test]
+    async fn test_beep_pattern_sends_count_beeps() {
+        let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        let keys = LoginKeychain { dev: key.clone(), app: key };
+        let mut transport = MockTransport::default();
+        transport.auto_reply_with_simulated_responses(&keys);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        session.beep_pattern(3).await.unwrap();
+        assert_eq!(session.transport().written.len(), 3);
+        assert_eq!(session.mutation_log().len(), 3);
+
+        session.beep_pattern(0).await.unwrap();
+        assert_eq!(session.transport().written.len(), 3);
+    }
+}
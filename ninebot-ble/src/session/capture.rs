@@ -0,0 +1,330 @@
+use super::{Payload, ScooterResponse};
+use super::commands::{Attribute, ReadWrite};
+use super::{battery, current, temperature, travel};
+use crate::mi_crypto::{decrypt_uart, LoginKeychain};
+
+use std::io::Write;
+use std::time::Instant;
+use anyhow::{Result, anyhow};
+
+/**
+ * Which way frame went, written as "->" (to scooter) and "<-" (from scooter)
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameDirection {
+  Outbound,
+  Inbound
+}
+
+impl FrameDirection {
+  pub fn arrow(&self) -> &'static str {
+    match self {
+      FrameDirection::Outbound => "->",
+      FrameDirection::Inbound => "<-"
+    }
+  }
+}
+
+/**
+ * Logs every encrypted frame session exchanges with scooter, one line per frame:
+ * `     0.125 -> 55 AB 03 00 00 ...` with seconds since tap was attached. Frames are logged as they go
+ * over the air, so log can be decrypted later with the same keychain or replayed with ReplayTransport
+ */
+pub struct FrameTap {
+  sink: Box<dyn Write + Send>,
+  started: Instant,
+  /**
+   * Set for decoding taps, frames are decrypted with it instead of logged as hex
+   */
+  keys: Option<LoginKeychain>
+}
+
+impl FrameTap {
+  pub fn new<W: Write + Send + 'static>(sink: W) -> Self {
+    Self {
+      sink: Box::new(sink),
+      started: Instant::now(),
+      keys: None
+    }
+  }
+
+  /**
+   * Log frames as readable trace, `     0.180 <- READ BatteryPercent(0x32) -> 87%`, for bug reports. Needs the
+   * session keychain to decrypt frames. Frames which can't be decrypted are logged as hex like with `new`,
+   * but decoded log can't be replayed with ReplayTransport
+   */
+  pub fn decoding<W: Write + Send + 'static>(sink: W, keys: &LoginKeychain) -> Self {
+    Self {
+      keys: Some(keys.clone()),
+      ..Self::new(sink)
+    }
+  }
+
+  /**
+   * Failing sink must not break the ride, so write errors are only logged
+   */
+  pub fn record(&mut self, direction: FrameDirection, frame: &[u8]) {
+    let seconds = self.started.elapsed().as_secs_f64();
+    let line = match &self.keys {
+      Some(keys) => format_decoded_line(seconds, direction, frame, keys),
+      None => format_frame_line(seconds, direction, frame)
+    };
+
+    if let Err(err) = writeln!(self.sink, "{}", line).and_then(|_| self.sink.flush()) {
+      tracing::warn!("Could not write captured frame: {}", err);
+    }
+  }
+}
+
+pub(super) fn format_frame_line(seconds: f64, direction: FrameDirection, frame: &[u8]) -> String {
+  format!("{:>10.3} {} {}", seconds, direction.arrow(), hex(frame))
+}
+
+/**
+ * Commands are encrypted with app key and responses with device key. Falls back to hex for frames
+ * which aren't UART frames of this session
+ */
+pub(super) fn format_decoded_line(seconds: f64, direction: FrameDirection, frame: &[u8], keys: &LoginKeychain) -> String {
+  let key = match direction {
+    FrameDirection::Outbound => &keys.app,
+    FrameDirection::Inbound => &keys.dev
+  };
+
+  // length byte is sent in clear, decrypted part starts with direction
+  let response = decrypt_uart(key, frame).ok().and_then(|decrypted| {
+    let mut plain = vec![frame[2]];
+    plain.extend_from_slice(&decrypted);
+    ScooterResponse::parse(&plain).ok().map(|response| (response, decrypted))
+  });
+
+  let (response, decrypted) = match response {
+    Some(response) => response,
+    None => return format_frame_line(seconds, direction, frame)
+  };
+
+  let operation = match response.read_write {
+    ReadWrite::Read => "READ",
+    ReadWrite::Write => "WRITE"
+  };
+  let mut line = format!("{:>10.3} {} {} {}", seconds, direction.arrow(), operation, response.attribute);
+
+  match (direction, response.read_write) {
+    // read request only carries number of bytes asked for
+    (FrameDirection::Outbound, ReadWrite::Read) => {},
+    (FrameDirection::Outbound, ReadWrite::Write) => line.push_str(&format!(" {}", hex(&response.payload))),
+    (FrameDirection::Inbound, ReadWrite::Write) => {
+      let status = if response.payload.first() == Some(&0x01) { "ACK" } else { "NAK" };
+      line.push_str(&format!(" -> {}", status));
+    },
+    (FrameDirection::Inbound, ReadWrite::Read) => {
+      let value = describe_value(&response.attribute, decrypted).unwrap_or_else(|| hex(&response.payload));
+      line.push_str(&format!(" -> {}", value));
+    }
+  }
+
+  line
+}
+
+/**
+ * Value of read response in units session reports it in, None for attributes without decoder
+ */
+fn describe_value(attribute: &Attribute, decrypted: Vec<u8>) -> Option<String> {
+  let payload = Payload::from(decrypted);
+
+  let value = match attribute {
+    Attribute::BatteryPercent => format!("{}%", battery::parse_battery_percent(payload).ok()?),
+    Attribute::BatteryVoltage => format!("{:.2} V", battery::parse_battery_voltage(payload).ok()?),
+    Attribute::BatteryCycles => format!("{} cycles", battery::parse_battery_cycles(payload).ok()?),
+    Attribute::BatteryHealth => format!("{}%", battery::parse_battery_health(payload).ok()?),
+    Attribute::BatteryCurrent => format!("{:.2} A", current::parse_current(payload).ok()?),
+    Attribute::FrameTemperature => format!("{} °C", temperature::parse_controller_temp(payload).ok()?),
+    Attribute::Speed => format!("{:.1} km/h", travel::parse_speed(payload).ok()?),
+    Attribute::TripDistance => format!("{:.2} km", travel::parse_trip_distance(payload).ok()?),
+    Attribute::TotalMileage => format!("{:.1} km", travel::parse_odometer(payload).ok()?),
+    _ => return None
+  };
+
+  Some(value)
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+}
+
+/**
+ * Inverse of `format_frame_line`, timestamp is dropped
+ */
+pub(super) fn parse_frame_line(line: &str) -> Result<(FrameDirection, Vec<u8>)> {
+  let mut parts = line.split_whitespace();
+  let seconds = parts.next().ok_or_else(|| anyhow!("Empty line"))?;
+  seconds.parse::<f64>()
+    .map_err(|_| anyhow!("Invalid timestamp: {}", seconds))?;
+
+  let direction = match parts.next() {
+    Some("->") => FrameDirection::Outbound,
+    Some("<-") => FrameDirection::Inbound,
+    other => return Err(anyhow!("Invalid direction: {:?}", other))
+  };
+
+  let frame = parts
+    .map(|byte| u8::from_str_radix(byte, 16).map_err(|_| anyhow!("Invalid byte: {}", byte)))
+    .collect::<Result<Vec<u8>>>()?;
+
+  Ok((direction, frame))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::MiSession;
+  use super::super::transport::MockTransport;
+  use super::super::commands::{Attribute, Direction, ScooterCommand};
+  use crate::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
+
+  use std::sync::{Arc, Mutex};
+
+  #[derive(Clone, Default)]
+  struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+      String::from_utf8(self.0.lock().unwrap().clone()).unwrap().lines().map(|line| line.to_owned()).collect()
+    }
+  }
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  #[test]
+  fn test_line_format() {
+    assert_eq!(format_frame_line(0.0, FrameDirection::Outbound, &[0x55, 0xAB, 0x03]), "     0.000 -> 55 AB 03");
+    assert_eq!(format_frame_line(12.3456, FrameDirection::Inbound, &[0x0F]), "    12.346 <- 0F");
+  }
+
+  #[test]
+  fn test_parse_line() {
+    let (direction, frame) = parse_frame_line("     0.125 <- 55 AB 0F").unwrap();
+    assert_eq!(direction, FrameDirection::Inbound);
+    assert_eq!(frame, vec![0x55, 0xAB, 0x0F]);
+
+    let line = format_frame_line(3.5, FrameDirection::Outbound, &[0x01, 0xFE]);
+    assert_eq!(parse_frame_line(&line).unwrap(), (FrameDirection::Outbound, vec![0x01, 0xFE]));
+  }
+
+  #[test]
+  fn test_parse_invalid_lines() {
+    assert!(parse_frame_line("").is_err());
+    assert!(parse_frame_line("0.1 => 55").is_err());
+    assert!(parse_frame_line("0.1 -> 5G").is_err());
+    assert!(parse_frame_line("now -> 55").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_capture_exchanges() {
+    let keys = test_keys();
+    let responses = vec![
+      encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11])),
+      encrypt_uart(&keys.dev, &[0x04, 0x23, 0x01, 0x3E, 0x18, 0x01], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+    ];
+    let buffer = SharedBuffer::default();
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses.clone()), &keys);
+    session.attach_tap(FrameTap::new(buffer.clone()));
+    session.read_battery_percent().await.unwrap();
+    session.read_controller_temp().await.unwrap();
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 4);
+
+    let written = &session.transport().written;
+    let expected = [
+      ("->", &written[0]), ("<-", &responses[0]),
+      ("->", &written[1]), ("<-", &responses[1])
+    ];
+
+    for (line, (arrow, frame)) in lines.iter().zip(expected) {
+      let (seconds, rest) = line.trim_start().split_once(' ').unwrap();
+      assert!(seconds.parse::<f64>().is_ok(), "{}", line);
+      assert_eq!(rest, format!("{} {}", arrow, frame.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")));
+    }
+  }
+
+  #[test]
+  fn test_decoded_lines() {
+    let keys = test_keys();
+    let request = ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02);
+    let request = encrypt_uart(&keys.app, &request.try_as_bytes().unwrap(), 0, Some([0x01, 0x02, 0x03, 0x04]));
+    let percent = encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+    let temperature = encrypt_uart(&keys.dev, &[0x04, 0x23, 0x01, 0x3E, 0x18, 0x01], 1, Some([0x6a, 0xf8, 0x94, 0x11]));
+    let ack = MockTransport::write_reply(&keys, Attribute::Beep, 0x01);
+
+    assert_eq!(format_decoded_line(0.1, FrameDirection::Outbound, &request, &keys), "     0.100 -> READ BatteryPercent(0x32)");
+    assert_eq!(format_decoded_line(0.2, FrameDirection::Inbound, &percent, &keys), "     0.200 <- READ BatteryPercent(0x32) -> 87%");
+    assert_eq!(format_decoded_line(0.3, FrameDirection::Inbound, &temperature, &keys), "     0.300 <- READ FrameTemperature(0x3E) -> 28 °C");
+    assert_eq!(
+      format_decoded_line(0.4, FrameDirection::Inbound, &ack, &keys),
+      format!("     0.400 <- WRITE {} -> ACK", Attribute::Beep)
+    );
+  }
+
+  #[test]
+  fn test_undecryptable_frame_is_logged_as_hex() {
+    let keys = test_keys();
+    // response encrypted with app key can't be decrypted with device key
+    let frame = encrypt_uart(&keys.app, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, None);
+
+    assert_eq!(
+      format_decoded_line(0.0, FrameDirection::Inbound, &frame, &keys),
+      format_frame_line(0.0, FrameDirection::Inbound, &frame)
+    );
+  }
+
+  #[tokio::test]
+  async fn test_decoding_tap() {
+    let keys = test_keys();
+    let responses = vec![encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11]))];
+    let buffer = SharedBuffer::default();
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+    session.attach_tap(FrameTap::decoding(buffer.clone(), &keys));
+    session.read_battery_percent().await.unwrap();
+
+    let lines = buffer.lines();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("-> READ BatteryPercent(0x32)"), "{}", lines[0]);
+    assert!(lines[1].ends_with("<- READ BatteryPercent(0x32) -> 87%"), "{}", lines[1]);
+  }
+
+  #[tokio::test]
+  async fn test_detached_tap_stops_logging() {
+    let keys = test_keys();
+    let buffer = SharedBuffer::default();
+
+    let mut transport = MockTransport::default();
+    transport.auto_reply_with_simulated_responses(&keys);
+
+    let mut session = MiSession::with_transport(transport, &keys);
+    session.attach_tap(FrameTap::new(buffer.clone()));
+    session.beep().await.unwrap();
+    assert!(session.detach_tap().is_some());
+    session.beep().await.unwrap();
+
+    // beep and its ack
+    assert_eq!(buffer.lines().len(), 2);
+  }
+}
@@ -1,107 +1,880 @@
 use core::fmt::Debug;
+use std::cmp::Ordering;
+use std::time::{Instant, SystemTime};
+use chrono::{DateTime, Utc};
+use anyhow::Result;
 use pretty_hex::*;
 
-#[derive(Clone)]
-pub enum Direction {
-  MasterToMotor,
-  MasterToBattery,
-  MotorToMaster,
-  BatteryToMaster,
-}
-
-impl Direction {
-  fn value(&self) -> u8 {
-    match self {
-      Direction::MasterToMotor      => 0x20,
-      Direction::MasterToBattery    => 0x22,
-      Direction::MotorToMaster      => 0x23,
-      Direction::BatteryToMaster    => 0x25,
+pub use crate::frame::{ScooterCommand, ScooterCommandBuilder, Direction, ReadWrite, Attribute, SecurityClass};
+pub(crate) use crate::frame::u16_le_payload;
+
+impl Attribute {
+  /**
+   * Find attribute by its variant name ignoring case, e.g. "taillight" or "TAILLIGHT" returns TailLight
+   */
+  pub fn from_name(name: &str) -> Option<Attribute> {
+    Attribute::ALL.iter()
+      .find(|attribute| attribute.name().eq_ignore_ascii_case(name))
+      .cloned()
+  }
+}
+
+/**
+ * Hex dump of serialized command. Oversized command can't be serialized, its fields are printed instead,
+ * so logging it never panics
+ */
+impl Debug for ScooterCommand {
+  fn fmt(&self, form: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self.try_as_bytes() {
+      Ok(bytes) => write!(form, "{:?}", bytes.hex_dump()),
+      Err(_) => write!(form, "{:?} {:?} {:?} (oversized: {} bytes of payload)", self.direction, self.read_write, self.attribute, self.payload.len())
     }
   }
 }
 
-#[derive(Clone)]
-pub enum ReadWrite {
-  Read,
-  Write
+impl ScooterCommand {
+  /**
+   * Wrap framed command in PCAPNG Enhanced Packet Block, timestamp is in microseconds
+   */
+  pub fn to_pcapng_record(&self, timestamp_us: u64) -> Result<Vec<u8>> {
+    let frame = self.as_frame()?;
+
+    let mut body : Vec<u8> = Vec::new();
+    body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+    body.extend_from_slice(&((timestamp_us >> 32) as u32).to_le_bytes());
+    body.extend_from_slice(&(timestamp_us as u32).to_le_bytes());
+    body.extend_from_slice(&(frame.len() as u32).to_le_bytes()); // captured length
+    body.extend_from_slice(&(frame.len() as u32).to_le_bytes()); // original length
+    body.extend_from_slice(&frame);
+
+    Ok(pcapng_block(PCAPNG_ENHANCED_PACKET_BLOCK, &body))
+  }
+
+  /**
+   * Render command inside unicode box, useful for debugging tools and developer consoles
+   */
+  pub fn display_box(&self) -> String {
+    let lines = [
+      format!("Dir: {:?}  CMD: {:?}", self.direction, self.read_write),
+      format!("Attr: {:?} (0x{:02X})", self.attribute, self.attribute.address()),
+      format!("Payload: {}", hex_string(&self.payload)),
+      match self.checksum() {
+        Ok(checksum) => format!("Checksum: {}", hex_string(&checksum)),
+        Err(_) => format!("Checksum: oversized ({} bytes of payload)", self.payload.len())
+      },
+    ];
+
+    draw_box(&lines)
+  }
 }
 
-impl ReadWrite {
-  fn value(&self) -> u8 {
-    match self {
-      ReadWrite::Read     => 0x01,
-      ReadWrite::Write    => 0x03
-    }
+/**
+ * Priority used when commands are waiting in queue, user actions like lock should not wait behind telemetry
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+  Low,
+  Normal,
+  High,
+  Immediate
+}
+
+/**
+ * Command waiting in priority queue. Ordered by priority and then by age, so in `BinaryHeap` the oldest
+ * command with the highest priority is popped first
+ */
+#[derive(Clone, Debug)]
+pub struct PrioritizedCommand {
+  pub priority: Priority,
+  pub created_at: Instant,
+  pub command: ScooterCommand
+}
+
+impl PrioritizedCommand {
+  pub fn new(priority: Priority, command: ScooterCommand) -> Self {
+    Self { priority, created_at: Instant::now(), command }
   }
 }
 
-#[derive(Clone)]
-pub enum Attribute {
-  GeneralInfo,
-  MotorInfo,
-  DistanceLeft,
-  Speed,
-  TripDistance,
-  BatteryVoltage,
-  BatteryCurrent,
-  BatteryPercent,
-  BatteryCellVoltages,
-  Supplementary,
-  Cruise,
-  TailLight,
-  BatteryInfo,
-  Lock,
-  Unlock
+impl PartialEq for PrioritizedCommand {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
 }
 
-impl Attribute {
-  fn value(&self) -> u8 {
-    match self {
-      Attribute::GeneralInfo          => 0x10,
-      Attribute::DistanceLeft         => 0x25,
-      Attribute::Speed                => 0xB5,
-      Attribute::TripDistance         => 0xB9,
-      Attribute::BatteryVoltage       => 0x34,
-      Attribute::BatteryCurrent       => 0x33,
-      Attribute::BatteryPercent       => 0x32,
-      Attribute::MotorInfo            => 0xB0,
-      Attribute::BatteryCellVoltages  => 0x40,
-      Attribute::Supplementary        => 0x7B,
-      Attribute::Cruise               => 0x7C,
-      Attribute::TailLight            => 0x7D,
-      Attribute::BatteryInfo          => 0x31,
-      Attribute::Lock                 => 0x70,
-      Attribute::Unlock               => 0x71
+impl Eq for PrioritizedCommand {}
+
+impl PartialOrd for PrioritizedCommand {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for PrioritizedCommand {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self.priority.cmp(&other.priority)
+      .then_with(|| other.created_at.cmp(&self.created_at))
+  }
+}
+
+const PCAPNG_SECTION_HEADER_BLOCK : u32 = 0x0A0D0D0A;
+const PCAPNG_INTERFACE_DESCRIPTION_BLOCK : u32 = 0x00000001;
+const PCAPNG_ENHANCED_PACKET_BLOCK : u32 = 0x00000006;
+const PCAPNG_BYTE_ORDER_MAGIC : u32 = 0x1A2B3C4D;
+/**
+ * LINKTYPE_USER0, frames are raw ninebot UART frames, so in Wireshark they have to be decoded with custom dissector
+ */
+const PCAPNG_LINK_TYPE : u16 = 147;
+
+/**
+ * Block layout is: type, total length, body padded to 32 bits, total length repeated
+ */
+fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+  let padding = (4 - body.len() % 4) % 4;
+  let total_length = (12 + body.len() + padding) as u32;
+
+  let mut block : Vec<u8> = Vec::new();
+  block.extend_from_slice(&block_type.to_le_bytes());
+  block.extend_from_slice(&total_length.to_le_bytes());
+  block.extend_from_slice(body);
+  block.extend(std::iter::repeat(0u8).take(padding));
+  block.extend_from_slice(&total_length.to_le_bytes());
+  block
+}
+
+/**
+ * Create complete PCAPNG file (section header, interface description and one record per command) for offline
+ * analysis in Wireshark. Each command comes with timestamp in microseconds
+ */
+pub fn pcapng_export(commands: &[(u64, ScooterCommand)]) -> Result<Vec<u8>> {
+  let mut section : Vec<u8> = Vec::new();
+  section.extend_from_slice(&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes());
+  section.extend_from_slice(&1u16.to_le_bytes()); // major version
+  section.extend_from_slice(&0u16.to_le_bytes()); // minor version
+  section.extend_from_slice(&(-1i64).to_le_bytes()); // section length is not specified
+
+  let mut interface : Vec<u8> = Vec::new();
+  interface.extend_from_slice(&PCAPNG_LINK_TYPE.to_le_bytes());
+  interface.extend_from_slice(&0u16.to_le_bytes()); // reserved
+  interface.extend_from_slice(&0u32.to_le_bytes()); // no snap length limit
+
+  let mut file = pcapng_block(PCAPNG_SECTION_HEADER_BLOCK, &section);
+  file.extend_from_slice(&pcapng_block(PCAPNG_INTERFACE_DESCRIPTION_BLOCK, &interface));
+  for (timestamp_us, command) in commands {
+    file.extend_from_slice(&command.to_pcapng_record(*timestamp_us)?);
+  }
+
+  Ok(file)
+}
+
+/**
+ * Audit trail of write commands scooter accepted during session. Session records a write once its ACK arrives,
+ * NAK'd writes and reads are not recorded since they don't change anything on the scooter
+ */
+#[derive(Clone, Debug, Default)]
+pub struct MutationLog {
+  entries: Vec<(SystemTime, ScooterCommand)>
+}
+
+impl MutationLog {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Record command if it is a write, returns whether it was recorded
+   */
+  pub fn record(&mut self, cmd: &ScooterCommand) -> bool {
+    self.record_at(SystemTime::now(), cmd)
+  }
+
+  fn record_at(&mut self, time: SystemTime, cmd: &ScooterCommand) -> bool {
+    if cmd.read_write != ReadWrite::Write {
+      return false;
     }
+
+    self.entries.push((time, cmd.clone()));
+    true
+  }
+
+  pub fn entries(&self) -> &[(SystemTime, ScooterCommand)] {
+    &self.entries
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+
+  /**
+   * One line per write, e.g. "2024-01-01T12:00:00Z WRITE TailLight [02 00]"
+   */
+  pub fn to_audit_string(&self) -> String {
+    self.entries.iter()
+      .map(|(time, cmd)| {
+        let payload = if cmd.payload.is_empty() { String::new() } else { hex_string(&cmd.payload) };
+        format!("{} WRITE {:?} [{}]", DateTime::<Utc>::from(*time).format("%Y-%m-%dT%H:%M:%SZ"), cmd.attribute, payload)
+      })
+      .collect::<Vec<String>>()
+      .join("\n")
   }
 }
 
-#[derive(Clone)]
-pub struct ScooterCommand {
-  pub direction: Direction,
-  pub read_write: ReadWrite,
-  pub attribute: Attribute,
-  pub payload: Vec<u8>
+fn hex_string(bytes: &[u8]) -> String {
+  if bytes.is_empty() {
+    return String::from("-");
+  }
+
+  bytes.iter()
+    .map(|byte| format!("{:02X}", byte))
+    .collect::<Vec<String>>()
+    .join(" ")
 }
 
-impl Debug for ScooterCommand {
-  fn fmt(&self, form: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-    let message = format!("{:?}", self.as_bytes().hex_dump());
-    form.write_str(&message).unwrap();
-    Ok(())
+/**
+ * Width is counted in chars, not bytes, so box stays aligned. Control characters would break
+ * the frame, so they are replaced
+ */
+fn draw_box(lines: &[String]) -> String {
+  let lines : Vec<String> = lines.iter()
+    .map(|line| line.chars().map(|c| if c.is_control() { '?' } else { c }).collect())
+    .collect();
+  let width = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+  let mut output = format!("┌{}┐\n", "─".repeat(width + 2));
+  for line in &lines {
+    let padding = width - line.chars().count();
+    output.push_str(&format!("│ {}{} │\n", line, " ".repeat(padding)));
   }
+  output.push_str(&format!("└{}┘", "─".repeat(width + 2)));
+
+  output
 }
 
-impl ScooterCommand {
-  pub fn as_bytes(&self) -> Vec<u8> {
-    let mut bytes : Vec<u8> = Vec::new();
-    bytes.push(self.payload.len() as u8 + 2u8);
-    bytes.push(self.direction.value());
-    bytes.push(self.read_write.value());
-    bytes.push(self.attribute.value());
-    for byte in &self.payload {
-      bytes.push(*byte);
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::frame::MAX_PAYLOAD_LENGTH;
+  use proptest::prelude::*;
+
+  fn tail_light_on() -> ScooterCommand {
+    ScooterCommand {
+      direction: Direction::MasterToMotor,
+      read_write: ReadWrite::Write,
+      attribute: Attribute::TailLight,
+      payload: vec![0x02, 0x00]
+    }
+  }
+
+  #[test]
+  fn test_checksum() {
+    // 0x04 + 0x20 + 0x03 + 0x7D + 0x02 + 0x00 = 0xA6, inverted = 0xFF59
+    assert_eq!(tail_light_on().checksum().unwrap(), [0x59, 0xFF]);
+  }
+
+  #[test]
+  fn test_u16_le_payload() {
+    let expected = [
+      (0x0000, vec![0x00, 0x00]),
+      (0x0001, vec![0x01, 0x00]),
+      (0x0002, vec![0x02, 0x00]),
+      (0x00FF, vec![0xFF, 0x00]),
+      (0x0100, vec![0x00, 0x01]),
+      (0x1234, vec![0x34, 0x12]),
+      (0xFFFF, vec![0xFF, 0xFF]),
+    ];
+
+    for (value, bytes) in expected {
+      assert_eq!(u16_le_payload(value), bytes, "0x{:04X}", value);
+    }
+  }
+
+  #[test]
+  fn test_builder_write_u16() {
+    let cmd = ScooterCommand::builder()
+      .attribute(Attribute::TailLight)
+      .write_u16(0x0002)
+      .build()
+      .unwrap();
+
+    assert_eq!(cmd.payload, vec![0x02, 0x00]);
+    assert_eq!(cmd, tail_light_on());
+  }
+
+  #[test]
+  fn test_builder_defaults_to_read_for_owner() {
+    let cmd = ScooterCommand::builder()
+      .attribute(Attribute::BatteryPercent)
+      .payload(&[0x02])
+      .build()
+      .unwrap();
+
+    assert_eq!(cmd, ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02));
+  }
+
+  #[test]
+  fn test_builder_explicit_fields() {
+    let cmd = ScooterCommand::builder()
+      .direction(Direction::MasterToBattery)
+      .read_write(ReadWrite::Write)
+      .attribute(Attribute::Beep)
+      .payload(&[0x01, 0x00])
+      .build()
+      .unwrap();
+
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x22, 0x03, 0x7A, 0x01, 0x00]);
+  }
+
+  #[test]
+  fn test_builder_requires_attribute() {
+    assert!(ScooterCommand::builder().write_u16(1).build().is_err());
+  }
+
+  #[test]
+  fn test_display_box_content() {
+    let output = tail_light_on().display_box();
+    let lines : Vec<&str> = output.lines().collect();
+
+    assert_eq!(lines.len(), 6);
+    assert!(lines[1].contains("Dir: MasterToMotor  CMD: Write"));
+    assert!(lines[2].contains("Attr: TailLight (0x7D)"));
+    assert!(lines[3].contains("Payload: 02 00"));
+    assert!(lines[4].contains("Checksum: 59 FF"));
+  }
+
+  #[test]
+  fn test_display_box_lines_have_same_width() {
+    let output = tail_light_on().display_box();
+    let width = output.lines().next().unwrap().chars().count();
+
+    for line in output.lines() {
+      assert_eq!(line.chars().count(), width, "Misaligned line: {}", line);
+    }
+  }
+
+  #[test]
+  fn test_display_box_handles_special_characters() {
+    let output = draw_box(&[String::from("Attr: Tail\tLight"), String::from("Żółw ✓")]);
+    let width = output.lines().next().unwrap().chars().count();
+
+    assert!(!output.contains('\t'));
+    assert!(output.contains("Attr: Tail?Light"));
+    for line in output.lines() {
+      assert_eq!(line.chars().count(), width, "Misaligned line: {}", line);
+    }
+  }
+
+  #[test]
+  fn test_commands_are_compared_field_wise() {
+    let mut other = tail_light_on();
+    assert!(tail_light_on() == other.clone());
+
+    other.payload = vec![0x00, 0x00];
+    assert!(tail_light_on() != other);
+  }
+
+  #[test]
+  fn test_priority_queue_pops_immediate_first() {
+    let read = ScooterCommand {
+      direction: Direction::MasterToMotor,
+      read_write: ReadWrite::Read,
+      attribute: Attribute::MotorInfo,
+      payload: vec![0x20]
+    };
+    let lock = ScooterCommand {
+      direction: Direction::MasterToMotor,
+      read_write: ReadWrite::Write,
+      attribute: Attribute::Lock,
+      payload: vec![0x01, 0x00]
+    };
+
+    let mut queue = std::collections::BinaryHeap::new();
+    queue.push(PrioritizedCommand::new(Priority::Normal, read.clone()));
+    queue.push(PrioritizedCommand::new(Priority::Immediate, lock.clone()));
+
+    assert_eq!(queue.pop().unwrap().command, lock);
+    assert_eq!(queue.pop().unwrap().command, read);
+  }
+
+  #[test]
+  fn test_priority_queue_keeps_order_for_same_priority() {
+    let first = PrioritizedCommand::new(Priority::Normal, tail_light_on());
+    let mut second = PrioritizedCommand::new(Priority::Normal, tail_light_on());
+    second.created_at = first.created_at + std::time::Duration::from_millis(1);
+
+    assert!(first > second);
+  }
+
+  fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+  }
+
+  #[test]
+  fn test_oversized_payload_is_rejected() {
+    let mut cmd = tail_light_on();
+    cmd.payload = vec![0xAB; 300];
+
+    let error = cmd.try_as_bytes().unwrap_err();
+    assert!(error.to_string().contains("300 bytes"), "{}", error);
+  }
+
+  #[test]
+  fn test_oversized_command_fails_without_panic() {
+    let mut cmd = tail_light_on();
+    cmd.payload = vec![0xAB; 300];
+
+    assert!(cmd.checksum().is_err());
+    assert!(cmd.as_frame().is_err());
+    assert!(cmd.to_pcapng_record(0).is_err());
+    assert!(format!("{:?}", cmd).contains("oversized: 300 bytes"));
+    assert!(cmd.display_box().contains("oversized"));
+  }
+
+  #[test]
+  fn test_largest_payload_fits() {
+    let mut cmd = tail_light_on();
+    cmd.payload = vec![0xAB; MAX_PAYLOAD_LENGTH];
+
+    let bytes = cmd.try_as_bytes().unwrap();
+    assert_eq!(bytes[0], 0xFF);
+    assert_eq!(bytes.len(), 4 + MAX_PAYLOAD_LENGTH);
+
+    cmd.payload.push(0xAB);
+    assert!(cmd.try_as_bytes().is_err());
+  }
+
+  #[test]
+  fn test_as_frame() {
+    assert_eq!(tail_light_on().as_frame().unwrap(), vec![0x55, 0xAA, 0x04, 0x20, 0x03, 0x7D, 0x02, 0x00, 0x59, 0xFF]);
+  }
+
+  #[test]
+  fn test_pcapng_record() {
+    let record = tail_light_on().to_pcapng_record(0x0000_0001_0000_0002).unwrap();
+    let total_length = read_u32(&record, 4) as usize;
+
+    assert_eq!(read_u32(&record, 0), 0x00000006);
+    assert_eq!(total_length, record.len());
+    assert_eq!(total_length % 4, 0);
+    assert_eq!(read_u32(&record, total_length - 4) as usize, total_length);
+    assert_eq!(read_u32(&record, 12), 1); // timestamp high
+    assert_eq!(read_u32(&record, 16), 2); // timestamp low
+    assert_eq!(read_u32(&record, 20), 10); // captured length
+    assert_eq!(&record[28..38], tail_light_on().as_frame().unwrap().as_slice());
+  }
+
+  #[test]
+  fn test_pcapng_export() {
+    let file = pcapng_export(&[(0, tail_light_on()), (1000, tail_light_on())]).unwrap();
+
+    assert_eq!(read_u32(&file, 0), 0x0A0D0D0A);
+    assert_eq!(read_u32(&file, 8), 0x1A2B3C4D);
+
+    let mut offset = 0;
+    let mut block_types = Vec::new();
+    while offset < file.len() {
+      block_types.push(read_u32(&file, offset));
+      offset += read_u32(&file, offset + 4) as usize;
+    }
+
+    assert_eq!(offset, file.len());
+    assert_eq!(block_types, vec![0x0A0D0D0A, 0x00000001, 0x00000006, 0x00000006]);
+  }
+
+  #[test]
+  fn test_simulate_read_response() {
+    let read = ScooterCommand {
+      direction: Direction::MasterToBattery,
+      read_write: ReadWrite::Read,
+      attribute: Attribute::BatteryInfo,
+      payload: vec![0x0A]
+    };
+
+    let response = read.simulate_response();
+
+    assert_eq!(response.direction, Direction::BatteryToMaster);
+    assert_eq!(response.read_write, ReadWrite::Read);
+    assert_eq!(response.attribute, Attribute::BatteryInfo);
+    assert_eq!(response.payload, vec![0u8; 10]);
+  }
+
+  #[test]
+  fn test_simulate_write_response() {
+    let response = tail_light_on().simulate_response();
+
+    assert_eq!(response.direction, Direction::MotorToMaster);
+    assert_eq!(response.read_write, ReadWrite::Write);
+    assert_eq!(response.attribute, Attribute::TailLight);
+    assert_eq!(response.payload, vec![0x01]);
+  }
+
+  #[test]
+  fn test_attribute_from_name() {
+    assert_eq!(Attribute::from_name("taillight"), Some(Attribute::TailLight));
+    assert_eq!(Attribute::from_name("TailLight"), Some(Attribute::TailLight));
+    assert_eq!(Attribute::from_name("TAILLIGHT"), Some(Attribute::TailLight));
+    assert_eq!(Attribute::from_name("batterycellvoltages"), Some(Attribute::BatteryCellVoltages));
+  }
+
+  #[test]
+  fn test_attribute_address() {
+    let expected = [
+      (Attribute::GeneralInfo, 0x10),
+      (Attribute::DistanceLeft, 0x25),
+      (Attribute::Speed, 0xB5),
+      (Attribute::TripDistance, 0xB9),
+      (Attribute::BatteryVoltage, 0x34),
+      (Attribute::BatteryCurrent, 0x33),
+      (Attribute::BatteryPercent, 0x32),
+      (Attribute::MotorInfo, 0xB0),
+      (Attribute::BatteryCellVoltages, 0x40),
+      (Attribute::Supplementary, 0x7B),
+      (Attribute::Cruise, 0x7C),
+      (Attribute::TailLight, 0x7D),
+      (Attribute::Headlight, 0x7E),
+      (Attribute::BatteryInfo, 0x31),
+      (Attribute::Lock, 0x70),
+      (Attribute::Unlock, 0x71),
+      (Attribute::RideMode, 0x75),
+      (Attribute::BleVersion, 0x9B),
+      (Attribute::EscVersion, 0x1A),
+      (Attribute::BmsVersion, 0x17),
+      (Attribute::TotalMileage, 0x29),
+      (Attribute::Beep, 0x7A),
+      (Attribute::PowerOff, 0x79),
+      (Attribute::Reboot, 0x78),
+      (Attribute::FrameTemperature, 0x3E),
+      (Attribute::BatteryCycles, 0x1B),
+      (Attribute::BatteryHealth, 0x3B),
+      (Attribute::AlarmStatus, 0xB1),
+      (Attribute::LockStatus, 0xB2),
+      (Attribute::TripStatistics, 0x3A),
+      (Attribute::RidingTime, 0x3C),
+      (Attribute::Uptime, 0x3D),
+      (Attribute::Unknown(0x42), 0x42),
+    ];
+
+    assert_eq!(expected.len(), Attribute::ALL.len() + 1);
+    for (attribute, address) in expected {
+      assert_eq!(attribute.address(), address, "{:?}", attribute);
+    }
+  }
+
+  #[test]
+  fn test_attribute_address_is_used_in_frame() {
+    for attribute in Attribute::ALL {
+      let cmd = ScooterCommand::read(attribute.destination(), attribute.clone(), 0x02);
+      assert_eq!(cmd.try_as_bytes().unwrap()[3], attribute.address());
+    }
+  }
+
+  #[test]
+  fn test_attribute_try_from_known_address() {
+    assert_eq!(Attribute::try_from(0x7D).unwrap(), Attribute::TailLight);
+    assert_eq!(Attribute::try_from(0x32).unwrap(), Attribute::BatteryPercent);
+  }
+
+  #[test]
+  fn test_attribute_try_from_unknown_address() {
+    assert!(Attribute::try_from(0x42).is_err());
+    assert_eq!(Attribute::from_address(0x42), Attribute::Unknown(0x42));
+    assert_eq!(Attribute::from_address(0x7D), Attribute::TailLight);
+  }
+
+  #[test]
+  fn test_attribute_try_from_boundaries() {
+    assert!(Attribute::try_from(0x00).is_err());
+    assert!(Attribute::try_from(0xFF).is_err());
+    assert_eq!(Attribute::from_address(0x00), Attribute::Unknown(0x00));
+    assert_eq!(Attribute::from_address(0xFF), Attribute::Unknown(0xFF));
+  }
+
+  #[test]
+  fn test_attribute_try_from_is_inverse_of_address() {
+    for attribute in Attribute::ALL {
+      assert_eq!(Attribute::try_from(attribute.address()).unwrap(), attribute);
     }
-    bytes
+  }
+
+  #[test]
+  fn test_attribute_display() {
+    assert_eq!(Attribute::TailLight.to_string(), "TailLight(0x7D)");
+    assert_eq!(Attribute::BmsVersion.to_string(), "BmsVersion(0x17)");
+    assert_eq!(Attribute::Unknown(0x0A).to_string(), "Unknown(0x0A)");
+  }
+
+  #[test]
+  fn test_attribute_from_unknown_name() {
+    assert_eq!(Attribute::from_name("horn"), None);
+    assert_eq!(Attribute::from_name(""), None);
+    assert_eq!(Attribute::from_name("headlight"), Some(Attribute::Headlight));
+    // Unknown(_) carries an address, its name alone doesn't identify one
+    assert_eq!(Attribute::from_name("unknown"), None);
+  }
+
+  #[test]
+  fn test_attribute_from_name_covers_all() {
+    for attribute in Attribute::ALL {
+      assert_eq!(Attribute::from_name(attribute.name()), Some(attribute.clone()));
+      assert_eq!(Attribute::from_name(&attribute.name().to_uppercase()), Some(attribute));
+    }
+  }
+
+  fn lock() -> ScooterCommand {
+    ScooterCommand {
+      direction: Direction::MasterToMotor,
+      read_write: ReadWrite::Write,
+      attribute: Attribute::Lock,
+      payload: vec![0x01, 0x00]
+    }
+  }
+
+  #[test]
+  fn test_security_class() {
+    let read_lock = ScooterCommand { read_write: ReadWrite::Read, payload: vec![0x02], ..lock() };
+    let write_speed = ScooterCommand { attribute: Attribute::Speed, ..lock() };
+
+    assert_eq!(read_lock.security_class(), SecurityClass::Public);
+    assert_eq!(tail_light_on().security_class(), SecurityClass::Public);
+    assert_eq!(lock().security_class(), SecurityClass::Restricted);
+    assert_eq!(write_speed.security_class(), SecurityClass::Admin);
+  }
+
+  #[test]
+  fn test_restricted_command_requires_elevated_auth() {
+    assert!(SecurityClass::Public.allows(&tail_light_on()));
+    assert!(!SecurityClass::Public.allows(&lock()));
+    assert!(SecurityClass::Restricted.allows(&lock()));
+    assert!(SecurityClass::Admin.allows(&lock()));
+  }
+
+  #[test]
+  fn test_mutation_log_records_only_writes() {
+    let read = ScooterCommand { read_write: ReadWrite::Read, payload: vec![0x02], ..tail_light_on() };
+    let mut log = MutationLog::new();
+
+    assert!(!log.record(&read));
+    assert!(log.is_empty());
+
+    assert!(log.record(&tail_light_on()));
+    assert!(log.record(&lock()));
+    assert!(!log.record(&read));
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.entries()[1].1, lock());
+
+    log.clear();
+    assert!(log.is_empty());
+  }
+
+  #[test]
+  fn test_mutation_log_audit_string() {
+    let noon = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_704_110_400); // 2024-01-01 12:00:00 UTC
+    let mut log = MutationLog::new();
+    log.record_at(noon, &tail_light_on());
+    log.record_at(noon + std::time::Duration::from_secs(5), &lock());
+
+    assert_eq!(log.to_audit_string(), "2024-01-01T12:00:00Z WRITE TailLight [02 00]\n2024-01-01T12:00:05Z WRITE Lock [01 00]");
+  }
+
+  #[test]
+  fn test_writable_by_user() {
+    assert!(Attribute::TailLight.writable_by_user());
+    assert!(Attribute::Lock.writable_by_user());
+    assert!(!Attribute::TripDistance.writable_by_user());
+    assert!(!Attribute::MotorInfo.writable_by_user());
+    assert!(!Attribute::TotalMileage.writable_by_user());
+    assert!(!Attribute::BatteryInfo.writable_by_user());
+  }
+
+  #[test]
+  fn test_try_from_bytes_with_checksum() {
+    let command = ScooterCommand::try_from_bytes(&[0x04, 0x20, 0x03, 0x7D, 0x02, 0x00, 0x59, 0xFF]).unwrap();
+
+    assert_eq!(command.try_as_bytes().unwrap(), tail_light_on().try_as_bytes().unwrap());
+  }
+
+  #[test]
+  fn test_try_from_bytes_rejects_invalid_checksum() {
+    assert!(ScooterCommand::try_from_bytes(&[0x04, 0x20, 0x03, 0x7D, 0x02, 0x00, 0x58, 0xFF]).is_err());
+  }
+
+  #[test]
+  fn test_try_from_bytes_rejects_invalid_length() {
+    assert!(ScooterCommand::try_from_bytes(&[0x06, 0x20, 0x03, 0x7D, 0x02, 0x00]).is_err());
+  }
+
+  fn directions() -> Vec<Direction> {
+    vec![Direction::MasterToMotor, Direction::MasterToBattery, Direction::MotorToMaster, Direction::BatteryToMaster]
+  }
+
+  /**
+   * Every known attribute, and Unknown for addresses no variant claims (known address always decodes to its variant)
+   */
+  fn attributes() -> impl Strategy<Value = Attribute> {
+    prop_oneof![
+      prop::sample::select(Attribute::ALL.to_vec()),
+      any::<u8>()
+        .prop_filter("address of known attribute", |address| Attribute::try_from(*address).is_err())
+        .prop_map(Attribute::Unknown)
+    ]
+  }
+
+  proptest! {
+    #[test]
+    fn test_roundtrip(
+      direction in prop::sample::select(directions()),
+      read_write in prop::sample::select(vec![ReadWrite::Read, ReadWrite::Write]),
+      attribute in attributes(),
+      payload in prop::collection::vec(any::<u8>(), 0..=16),
+      with_checksum in any::<bool>()
+    ) {
+      let command = ScooterCommand { direction, read_write, attribute, payload };
+
+      let mut bytes = command.try_as_bytes().unwrap();
+      if with_checksum {
+        bytes.extend_from_slice(&command.checksum().unwrap());
+      }
+
+      let decoded = ScooterCommand::try_from_bytes(&bytes).unwrap();
+
+      prop_assert_eq!(decoded.direction, command.direction);
+      prop_assert_eq!(decoded.read_write, command.read_write);
+      prop_assert_eq!(decoded.attribute, command.attribute);
+      prop_assert_eq!(decoded.payload, command.payload);
+    }
+  }
+}
+
+/**
+ * Frames taken from M365 protocol documentation, every test is one known (attribute, read/write, bytes) triple
+ */
+#[cfg(test)]
+mod conformance_tests {
+  use super::*;
+
+  fn command(direction: Direction, read_write: ReadWrite, attribute: Attribute, payload: &[u8]) -> ScooterCommand {
+    ScooterCommand { direction, read_write, attribute, payload: payload.to_vec() }
+  }
+
+  #[test]
+  fn test_tail_light_on() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::TailLight, &[0x02, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7D, 0x02, 0x00]);
+  }
+
+  #[test]
+  fn test_tail_light_on_brake() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::TailLight, &[0x01, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7D, 0x01, 0x00]);
+  }
+
+  #[test]
+  fn test_tail_light_off() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::TailLight, &[0x00, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7D, 0x00, 0x00]);
+  }
+
+  #[test]
+  fn test_lock() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::Lock, &[0x01, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x70, 0x01, 0x00]);
+  }
+
+  #[test]
+  fn test_unlock() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::Unlock, &[0x01, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x71, 0x01, 0x00]);
+  }
+
+  #[test]
+  fn test_cruise_on() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::Cruise, &[0x01, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7C, 0x01, 0x00]);
+  }
+
+  #[test]
+  fn test_cruise_off() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Write, Attribute::Cruise, &[0x00, 0x00]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7C, 0x00, 0x00]);
+  }
+
+  #[test]
+  fn test_read_serial_number() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Read, Attribute::GeneralInfo, &[0x0E]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0x10, 0x0E]);
+  }
+
+  #[test]
+  fn test_read_motor_info() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Read, Attribute::MotorInfo, &[0x20]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB0, 0x20]);
+  }
+
+  #[test]
+  fn test_read_distance_left() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Read, Attribute::DistanceLeft, &[0x02]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0x25, 0x02]);
+  }
+
+  #[test]
+  fn test_read_speed() {
+    let cmd = command(Direction::MasterToMotor, ReadWrite::Read, Attribute::Speed, &[0x02]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB5, 0x02]);
+  }
+
+  #[test]
+  fn test_read_battery_info() {
+    let cmd = command(Direction::MasterToBattery, ReadWrite::Read, Attribute::BatteryInfo, &[0x0A]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x22, 0x01, 0x31, 0x0A]);
+  }
+
+  #[test]
+  fn test_read_builder() {
+    let cmd = ScooterCommand::read(Direction::MasterToMotor, Attribute::MotorInfo, 0x20);
+    assert_eq!(cmd, command(Direction::MasterToMotor, ReadWrite::Read, Attribute::MotorInfo, &[0x20]));
+  }
+
+  #[test]
+  fn test_read_battery_percent() {
+    let cmd = ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x22, 0x01, 0x32, 0x02]);
+  }
+
+  #[test]
+  fn test_read_battery_voltage() {
+    let cmd = ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryVoltage, 0x02);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x22, 0x01, 0x34, 0x02]);
+  }
+
+  #[test]
+  fn test_bms_read_goes_to_battery() {
+    let cmd = ScooterCommand::read(Attribute::BatteryCellVoltages.destination(), Attribute::BatteryCellVoltages, 0x1B);
+    assert_eq!(cmd.try_as_bytes().unwrap()[1], 0x22);
+  }
+
+  #[test]
+  fn test_destination() {
+    for attribute in Attribute::ALL {
+      let expected = match attribute {
+        Attribute::BatteryVoltage | Attribute::BatteryCurrent | Attribute::BatteryPercent |
+        Attribute::BatteryCellVoltages | Attribute::BatteryInfo | Attribute::BmsVersion |
+        Attribute::BatteryCycles | Attribute::BatteryHealth => 0x22,
+        _ => 0x20
+      };
+      assert_eq!(attribute.destination().value(), expected, "{:?}", attribute);
+    }
+  }
+
+  #[test]
+  fn test_read_battery_cell_voltages() {
+    let cmd = command(Direction::MasterToBattery, ReadWrite::Read, Attribute::BatteryCellVoltages, &[0x1B]);
+    assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x22, 0x01, 0x40, 0x1B]);
   }
 }
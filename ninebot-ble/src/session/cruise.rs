@@ -0,0 +1,81 @@
+//! Cruise control for M365 scooter
+//!
+//! When enabled, the scooter keeps its current speed after holding the
+//! throttle steady for a few seconds.
+//!
+//! ## Protocol Reference
+//! - Cruise On: Write 0x0001 to address 0x7C
+//! - Cruise Off: Write 0x0000 to address 0x7C
+//! - Direction: Master to Motor Controller (0x20)
+//! - Command Type: Write (0x03)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+
+use anyhow::Result;
+
+/// Build cruise control write command
+fn cruise_control_command(enabled: bool) -> ScooterCommand {
+    // Payload: 0x0001 to enable, 0x0000 to disable (little-endian: LSB first)
+    let payload = if enabled { vec![0x01, 0x00] } else { vec![0x00, 0x00] };
+
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute: Attribute::Cruise,
+        payload
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Enable or disable cruise control
+    ///
+    /// # Arguments
+    /// * `enabled` - `true` to enable, `false` to disable
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_cruise_control(true).await?;  // Enable
+    /// session.set_cruise_control(false).await?; // Disable
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_cruise_control(&mut self, enabled: bool) -> Result<(), SessionError> {
+        tracing::debug!("Setting cruise control enabled: {}", enabled);
+
+        self.send(&cruise_control_command(enabled)).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cruise_on_command_bytes() {
+        let bytes = cruise_control_command(true).try_as_bytes().unwrap();
+        // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7C) + Payload(0x01, 0x00)
+        assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
+        assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
+        assert_eq!(bytes[2], 0x03); // read_write: Write
+        assert_eq!(bytes[3], 0x7C); // attribute: Cruise
+        assert_eq!(bytes[4], 0x01); // payload[0] - LSB
+        assert_eq!(bytes[5], 0x00); // payload[1] - MSB
+    }
+
+    #[test]
+    fn test_cruise_off_command_bytes() {
+        let bytes = cruise_control_command(false).try_as_bytes().unwrap();
+        // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7C) + Payload(0x00, 0x00)
+        assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
+        assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
+        assert_eq!(bytes[2], 0x03); // read_write: Write
+        assert_eq!(bytes[3], 0x7C); // attribute: Cruise
+        assert_eq!(bytes[4], 0x00); // payload[0]
+        assert_eq!(bytes[5], 0x00); // payload[1]
+    }
+}
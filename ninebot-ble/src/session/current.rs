@@ -0,0 +1,134 @@
+//! Motor current and power draw for M365 scooter
+//!
+//! Current the motor controller draws from the battery, and power computed from it and
+//! battery voltage. While braking with KERS the motor works as generator, current and
+//! power are negative then.
+//!
+//! Motor controller has no known register with its own current, 0xBA in its status
+//! block is uptime. BMS measures the same current on the battery side.
+//!
+//! ## Protocol Reference
+//! - Read 2 bytes from address 0x33, Master to Battery (0x22)
+//! - Command Type: Read (0x01)
+//! - Value: signed 16-bit, hundredths of ampere (0x04E2 = 12.5 A, 0xFEA2 = -3.5 A)
+//! - Power: battery voltage (0x34) × current, two round-trips
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+
+use anyhow::Result;
+
+pub(super) fn parse_current(payload: Payload) -> Result<f32> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(payload.pop_i16()? as f32 / 100.0)
+}
+
+/// Power in watts, negative while regenerating
+fn power_watts(voltage: f32, current: f32) -> f32 {
+    voltage * current
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read current drawn by the motor in amperes, negative during regenerative braking
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// println!("Motor current: {:.1} A", session.read_current().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_current(&mut self) -> Result<f32, SessionError> {
+        tracing::debug!("Reading current");
+
+        self.send(&ScooterCommand::read(Attribute::BatteryCurrent.destination(), Attribute::BatteryCurrent, 0x02)).await?;
+
+        let payload = self.read_frame().await?;
+        let current = parse_current(payload)?;
+        tracing::debug!("current: {}A", current);
+
+        Ok(current)
+    }
+
+    /// Read power drawn by the motor in watts, negative during regenerative braking
+    ///
+    /// Reads battery voltage and current one after another, so the two
+    /// values are a few milliseconds apart.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// println!("Power: {:.0} W", session.read_power().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_power(&mut self) -> Result<f32, SessionError> {
+        let voltage = self.read_battery_voltage().await?;
+        let current = self.read_current().await?;
+
+        let power = power_watts(voltage, current);
+        tracing::debug!("power: {}W", power);
+
+        Ok(power)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
+
+    fn current_response(raw: i16) -> Payload {
+        let mut bytes = vec![0x25, 0x01, 0x33];
+        bytes.extend_from_slice(&raw.to_le_bytes());
+        bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+        Payload::from(bytes)
+    }
+
+    fn encrypted(keys: &LoginKeychain, direction: u8, attribute: u8, raw: i16) -> Vec<u8> {
+        let [low, high] = raw.to_le_bytes();
+        encrypt_uart(&keys.dev, &[0x04, direction, 0x01, attribute, low, high], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+    }
+
+    #[test]
+    fn test_current() {
+        assert_eq!(parse_current(current_response(1250)).unwrap(), 12.5);
+        assert_eq!(parse_current(current_response(0)).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_regen_current_is_negative() {
+        // -3.5 A as 0xFEA2, reading it unsigned would be 651 A
+        assert_eq!(parse_current(current_response(-350)).unwrap(), -3.5);
+    }
+
+    #[test]
+    fn test_power_watts() {
+        assert_eq!(power_watts(40.0, 12.5), 500.0);
+        assert_eq!(power_watts(40.0, -3.5), -140.0);
+    }
+
+    #[test]
+    fn test_current_read_command() {
+        let cmd = ScooterCommand::read(Attribute::BatteryCurrent.destination(), Attribute::BatteryCurrent, 0x02);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x22, 0x01, 0x33, 0x02]);
+    }
+
+    #[tokio::test]
+    async fn test_read_power() {
+        let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        let keys = LoginKeychain { dev: key.clone(), app: key };
+        let transport = MockTransport::with_responses(vec![
+            encrypted(&keys, 0x25, 0x34, 4000), // 40.00 V
+            encrypted(&keys, 0x25, 0x33, -350), // -3.5 A
+        ]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        assert_eq!(session.read_power().await.unwrap(), -140.0);
+    }
+}
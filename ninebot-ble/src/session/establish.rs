@@ -0,0 +1,163 @@
+use super::MiSession;
+use crate::login::{LoginRequest, LoginError};
+use crate::register::RegistrationRequest;
+use crate::mi_crypto::AuthToken;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use btleplug::platform::Peripheral;
+
+/**
+ * Both ways of getting session from connected scooter, so choosing between them can be tested without scooter.
+ * Private and only awaited from `establish`, so futures don't have to be Send
+ */
+#[async_trait(?Send)]
+trait Handshake {
+  type Session;
+
+  async fn login(&self, token: &AuthToken) -> Result<Self::Session>;
+
+  async fn register(&self) -> Result<AuthToken>;
+}
+
+#[async_trait(?Send)]
+impl Handshake for Peripheral {
+  type Session = MiSession;
+
+  async fn login(&self, token: &AuthToken) -> Result<MiSession> {
+    LoginRequest::new(self, token).await?.start().await
+  }
+
+  async fn register(&self) -> Result<AuthToken> {
+    Ok(RegistrationRequest::new(self).await?.start().await?)
+  }
+}
+
+/**
+ * Scooter rejected the token, as opposed to link or protocol errors which registering won't fix
+ */
+fn is_auth_failure(err: &anyhow::Error) -> bool {
+  matches!(err.downcast_ref::<LoginError>(), Some(LoginError::LoginFailed | LoginError::InvalidDid))
+}
+
+/**
+ * Log in with stored token, register when there is none or scooter no longer accepts it
+ */
+async fn establish_with<H: Handshake>(handshake: &H, token: Option<&AuthToken>) -> Result<(H::Session, AuthToken)> {
+  if let Some(token) = token {
+    match handshake.login(token).await {
+      Ok(session) => return Ok((session, *token)),
+      Err(err) if is_auth_failure(&err) => tracing::warn!("Stored token was rejected, registering again: {}", err),
+      Err(err) => return Err(err)
+    }
+  }
+
+  let token = handshake.register().await?;
+  let session = handshake.login(&token).await?;
+  Ok((session, token))
+}
+
+impl MiSession {
+  /**
+   * Get ready session without caring whether scooter was registered before. Logs in with stored token,
+   * when there is none or scooter rejects it, registers and logs in with the new one. Registration
+   * needs rider to press power button, so tell them before calling this without token.
+   * Returned token is the one session was opened with, store it when it differs from the passed one.
+   * Takes the peripheral rather than a transport because login and registration run over MiProtocol
+   * before the encrypted transport exists, and the token is passed in and returned because the crate
+   * doesn't persist tokens itself
+   */
+  pub async fn establish(device: &Peripheral, token: Option<&AuthToken>) -> Result<(MiSession, AuthToken)> {
+    establish_with(device, token).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transport::MockTransport;
+  use crate::mi_crypto::{EncryptionKey, LoginKeychain};
+
+  use anyhow::anyhow;
+  use std::sync::Mutex;
+
+  const STORED: AuthToken = [0x01; 12];
+  const ISSUED: AuthToken = [0x02; 12];
+
+  /**
+   * Scooter which accepts one token and hands out `ISSUED` on registration, sessions run over mock transport
+   */
+  struct MockHandshake {
+    accepted: Mutex<Option<AuthToken>>,
+    calls: Mutex<Vec<&'static str>>,
+  }
+
+  impl MockHandshake {
+    fn accepting(token: Option<AuthToken>) -> Self {
+      Self { accepted: Mutex::new(token), calls: Mutex::new(Vec::new()) }
+    }
+
+    fn calls(&self) -> Vec<&'static str> {
+      self.calls.lock().unwrap().clone()
+    }
+  }
+
+  #[async_trait(?Send)]
+  impl Handshake for MockHandshake {
+    type Session = MiSession<MockTransport>;
+
+    async fn login(&self, token: &AuthToken) -> Result<Self::Session> {
+      self.calls.lock().unwrap().push("login");
+      if *self.accepted.lock().unwrap() != Some(*token) {
+        return Err(LoginError::LoginFailed.into())
+      }
+
+      let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+      let keys = LoginKeychain { dev: key.clone(), app: key };
+      Ok(MiSession::with_transport(MockTransport::with_responses(vec![]), &keys))
+    }
+
+    async fn register(&self) -> Result<AuthToken> {
+      self.calls.lock().unwrap().push("register");
+      *self.accepted.lock().unwrap() = Some(ISSUED);
+      Ok(ISSUED)
+    }
+  }
+
+  #[tokio::test]
+  async fn test_stored_token_logs_in() {
+    let scooter = MockHandshake::accepting(Some(STORED));
+
+    let (_, token) = establish_with(&scooter, Some(&STORED)).await.unwrap();
+
+    assert_eq!(token, STORED);
+    assert_eq!(scooter.calls(), vec!["login"]);
+  }
+
+  #[tokio::test]
+  async fn test_no_token_registers() {
+    let scooter = MockHandshake::accepting(None);
+
+    let (_, token) = establish_with(&scooter, None).await.unwrap();
+
+    assert_eq!(token, ISSUED);
+    assert_eq!(scooter.calls(), vec!["register", "login"]);
+  }
+
+  #[tokio::test]
+  async fn test_rejected_token_registers_again() {
+    let scooter = MockHandshake::accepting(Some(ISSUED));
+
+    let (_, token) = establish_with(&scooter, Some(&STORED)).await.unwrap();
+
+    assert_eq!(token, ISSUED);
+    assert_eq!(scooter.calls(), vec!["login", "register", "login"]);
+  }
+
+  #[test]
+  fn test_only_rejection_is_auth_failure() {
+    assert!(is_auth_failure(&LoginError::LoginFailed.into()));
+    assert!(is_auth_failure(&LoginError::InvalidDid.into()));
+    assert!(!is_auth_failure(&anyhow!("Scooter disconnected")));
+  }
+}
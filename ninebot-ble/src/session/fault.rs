@@ -0,0 +1,188 @@
+//! Fault (error) code for M365 scooter
+//!
+//! When scooter refuses to ride it blinks the error code on dashboard. Controller keeps the
+//! same code in a register, so HUD can explain the problem instead of making rider count blinks.
+//!
+//! ## Protocol Reference
+//! - Read 2 bytes from address 0xB0, Master to Motor Controller (0x20). It is the first word of
+//!   the status block, so it is addressed as `Attribute::MotorInfo`
+//! - Command Type: Read (0x01)
+//! - Value: u16 fault code, 0 when there is no fault. It is a single code, not a bitmask,
+//!   scooter reports only the first fault it detected
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Hard fault which stops the scooter from riding
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ScooterFault {
+    None,
+    /// 10: Dashboard and motor controller can't talk to each other
+    DashboardCommFault,
+    /// 11-13: Current on one of motor phases is out of range
+    MotorFault,
+    /// 14: Throttle is not in rest position at power on
+    ThrottleFault,
+    /// 15: Brake lever is not in rest position or its sensor is broken
+    BrakeFault,
+    /// 18: Motor hall sensor doesn't report rotation
+    HallSensorFault,
+    /// 21: Motor controller can't talk to battery
+    BmsCommFault,
+    /// 24: Battery voltage is out of range
+    VoltageFault,
+    /// 39: Battery temperature sensor is broken
+    BatteryTempSensorFault,
+    Unknown(u16),
+}
+
+impl ScooterFault {
+    /// Code as dashboard blinks it
+    pub fn code(&self) -> u16 {
+        match self {
+            ScooterFault::None => 0,
+            ScooterFault::DashboardCommFault => 10,
+            ScooterFault::MotorFault => 11,
+            ScooterFault::ThrottleFault => 14,
+            ScooterFault::BrakeFault => 15,
+            ScooterFault::HallSensorFault => 18,
+            ScooterFault::BmsCommFault => 21,
+            ScooterFault::VoltageFault => 24,
+            ScooterFault::BatteryTempSensorFault => 39,
+            ScooterFault::Unknown(code) => *code,
+        }
+    }
+
+    /// Explanation for the rider, what is wrong and what to check
+    pub fn description(&self) -> &'static str {
+        match self {
+            ScooterFault::None => "No fault",
+            ScooterFault::DashboardCommFault => "Dashboard can't reach motor controller, check cable in the handlebar",
+            ScooterFault::MotorFault => "Motor phase current is abnormal, check motor cable and controller",
+            ScooterFault::ThrottleFault => "Throttle is not at rest, release it and power on again",
+            ScooterFault::BrakeFault => "Brake lever is not at rest or its sensor is broken",
+            ScooterFault::HallSensorFault => "Motor hall sensor fault, check motor cable",
+            ScooterFault::BmsCommFault => "Motor controller can't reach battery, check battery cable",
+            ScooterFault::VoltageFault => "Battery voltage is out of range",
+            ScooterFault::BatteryTempSensorFault => "Battery temperature sensor is broken",
+            ScooterFault::Unknown(_) => "Unknown fault",
+        }
+    }
+
+    pub fn is_fault(&self) -> bool {
+        *self != ScooterFault::None
+    }
+}
+
+impl From<u16> for ScooterFault {
+    fn from(code: u16) -> Self {
+        match code {
+            0 => ScooterFault::None,
+            10 => ScooterFault::DashboardCommFault,
+            11..=13 => ScooterFault::MotorFault,
+            14 => ScooterFault::ThrottleFault,
+            15 => ScooterFault::BrakeFault,
+            18 => ScooterFault::HallSensorFault,
+            21 => ScooterFault::BmsCommFault,
+            24 => ScooterFault::VoltageFault,
+            39 => ScooterFault::BatteryTempSensorFault,
+            code => ScooterFault::Unknown(code),
+        }
+    }
+}
+
+pub(super) fn parse_error_code(payload: Payload) -> Result<ScooterFault> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(ScooterFault::from(payload.pop_u16()?))
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read fault which keeps scooter from riding, `ScooterFault::None` when everything is fine
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let fault = session.read_error_code().await?;
+    /// if fault.is_fault() {
+    ///     println!("Error {}: {}", fault.code(), fault.description());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_error_code(&mut self) -> Result<ScooterFault, SessionError> {
+        tracing::debug!("Reading error code");
+
+        self.send(&ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x02)).await?;
+
+        let payload = self.read_frame().await?;
+        let fault = parse_error_code(payload)?;
+        tracing::debug!("error code: {:?}", fault);
+
+        Ok(fault)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error_code_response(code: u16) -> Payload {
+        let mut bytes = vec![0x23, 0x01, 0xB0];
+        bytes.extend_from_slice(&code.to_le_bytes());
+        bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+        Payload::from(bytes)
+    }
+
+    #[test]
+    fn test_no_fault() {
+        let fault = parse_error_code(error_code_response(0)).unwrap();
+
+        assert_eq!(fault, ScooterFault::None);
+        assert!(!fault.is_fault());
+    }
+
+    #[test]
+    fn test_documented_faults() {
+        let expected = [
+            (10, ScooterFault::DashboardCommFault),
+            (12, ScooterFault::MotorFault),
+            (14, ScooterFault::ThrottleFault),
+            (15, ScooterFault::BrakeFault),
+            (18, ScooterFault::HallSensorFault),
+            (21, ScooterFault::BmsCommFault),
+            (39, ScooterFault::BatteryTempSensorFault),
+        ];
+
+        for (code, fault) in expected {
+            assert_eq!(parse_error_code(error_code_response(code)).unwrap(), fault, "code {}", code);
+            assert!(fault.is_fault());
+        }
+    }
+
+    #[test]
+    fn test_unknown_fault() {
+        let fault = parse_error_code(error_code_response(0x0101)).unwrap();
+
+        assert_eq!(fault, ScooterFault::Unknown(0x0101));
+        assert_eq!(fault.code(), 0x0101);
+        assert_eq!(fault.description(), "Unknown fault");
+    }
+
+    #[test]
+    fn test_descriptions() {
+        assert!(ScooterFault::BrakeFault.description().contains("Brake"));
+        assert!(ScooterFault::BmsCommFault.description().contains("battery"));
+    }
+
+    #[test]
+    fn test_error_code_read_command() {
+        let cmd = ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x02);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB0, 0x02]);
+    }
+}
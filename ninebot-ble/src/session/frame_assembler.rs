@@ -0,0 +1,212 @@
+use pretty_hex::*;
+
+/**
+ * Largest chunk scooter accepts in single BLE write, longer frames are split and come back split too
+ */
+pub const BLE_MTU : usize = 20;
+
+const HEADER_ENCRYPTED : [u8; 2] = [0x55, 0xAB];
+const HEADER_PLAIN : [u8; 2] = [0x55, 0xAA];
+
+/**
+ * Outcome of looking for the next frame in received bytes
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Assembled {
+  /**
+   * Whole frame from header up to and including checksum
+   */
+  Frame(Vec<u8>),
+  /**
+   * Notification cut the frame short, which is normal on slow links and not an error. `missing` is how
+   * many more bytes are needed, until size byte arrives it only counts header and size
+   */
+  Incomplete { missing: usize }
+}
+
+/**
+ * Collects BLE notifications and yields complete frames. Frame length is known from size byte right after
+ * header: encrypted frames add header, size, counter, random bytes, CCM tag and crc (size + 16 bytes),
+ * plain frames add header, size and checksum (size + 6 bytes)
+ */
+#[derive(Default)]
+pub struct FrameAssembler {
+  buffer: Vec<u8>
+}
+
+impl FrameAssembler {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Add received chunk, returns all frames completed by it
+   */
+  pub fn push(&mut self, chunk: &[u8]) -> Vec<Vec<u8>> {
+    self.buffer.extend_from_slice(chunk);
+
+    let mut frames : Vec<Vec<u8>> = Vec::new();
+    while let Assembled::Frame(frame) = self.next_frame() {
+      frames.push(frame);
+    }
+
+    frames
+  }
+
+  /**
+   * Number of bytes waiting for rest of the frame
+   */
+  pub fn pending(&self) -> usize {
+    self.buffer.len()
+  }
+
+  pub fn clear(&mut self) {
+    self.buffer.clear();
+  }
+
+  /**
+   * Take next complete frame out of buffered bytes. Nothing is consumed while frame is incomplete, so
+   * partial frame is never handed over for parsing
+   */
+  pub fn next_frame(&mut self) -> Assembled {
+    self.skip_to_header();
+
+    if self.buffer.len() < 3 {
+      return Assembled::Incomplete { missing: 3 - self.buffer.len() };
+    }
+
+    let size = self.buffer[2] as usize;
+    let frame_length = if self.buffer[..2] == HEADER_ENCRYPTED { size + 16 } else { size + 6 };
+    if self.buffer.len() < frame_length {
+      return Assembled::Incomplete { missing: frame_length - self.buffer.len() };
+    }
+
+    let frame : Vec<u8> = self.buffer.drain(..frame_length).collect();
+    tracing::debug!("Assembled frame: {:?}", frame.hex_dump());
+    Assembled::Frame(frame)
+  }
+
+  /**
+   * Drop bytes which can't be start of the frame, e.g. rest of frame which was cut off
+   */
+  fn skip_to_header(&mut self) {
+    let start = (0..self.buffer.len())
+      .find(|&i| match &self.buffer[i..] {
+        [0x55] => true,
+        [first, second, ..] => [*first, *second] == HEADER_ENCRYPTED || [*first, *second] == HEADER_PLAIN,
+        _ => false
+      })
+      .unwrap_or(self.buffer.len());
+
+    if start > 0 {
+      tracing::debug!("Skipping {} bytes before frame header", start);
+      self.buffer.drain(..start);
+    }
+  }
+}
+
+/**
+ * Split frame into chunks which fit single BLE write
+ */
+pub fn split_frame(frame: &[u8], mtu: usize) -> Vec<Vec<u8>> {
+  frame.chunks(mtu)
+    .map(|chunk| chunk.to_vec())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encrypted_frame(size: u8) -> Vec<u8> {
+    let mut frame = vec![0x55, 0xAB, size];
+    frame.extend((0..size as usize + 13).map(|i| i as u8));
+    frame
+  }
+
+  #[test]
+  fn test_reassemble_frame_from_three_chunks() {
+    let frame = encrypted_frame(29);
+    assert_eq!(frame.len(), 45);
+
+    let mut assembler = FrameAssembler::new();
+    let chunks = split_frame(&frame, BLE_MTU);
+    assert_eq!(chunks.len(), 3);
+
+    assert!(assembler.push(&chunks[0]).is_empty());
+    assert!(assembler.push(&chunks[1]).is_empty());
+    assert_eq!(assembler.push(&chunks[2]), vec![frame]);
+    assert_eq!(assembler.pending(), 0);
+  }
+
+  #[test]
+  fn test_two_frames_in_one_chunk() {
+    let first = encrypted_frame(3);
+    let second = encrypted_frame(4);
+    let joined = [first.clone(), second.clone()].concat();
+
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(assembler.push(&joined), vec![first, second]);
+  }
+
+  #[test]
+  fn test_plain_frame() {
+    let frame = vec![0x55, 0xAA, 0x04, 0x20, 0x03, 0x7D, 0x02, 0x00, 0x59, 0xFF];
+
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(assembler.push(&frame), vec![frame]);
+  }
+
+  #[test]
+  fn test_skip_garbage_before_header() {
+    let frame = encrypted_frame(3);
+    let mut assembler = FrameAssembler::new();
+
+    assert!(assembler.push(&[0x01, 0x02, 0x55]).is_empty());
+    assert_eq!(assembler.pending(), 1);
+    assert_eq!(assembler.push(&frame[1..]), vec![frame]);
+  }
+
+  #[test]
+  fn test_frame_delivered_byte_by_byte() {
+    let frame = vec![0x55, 0xAA, 0x04, 0x25, 0x01, 0x32, 0x40, 0x00, 0x63, 0xFF];
+    let mut assembler = FrameAssembler::new();
+
+    let mut frames : Vec<Vec<u8>> = Vec::new();
+    for (i, byte) in frame.iter().enumerate() {
+      let completed = assembler.push(&[*byte]);
+      if i + 1 < frame.len() {
+        assert!(completed.is_empty(), "frame yielded after {} bytes", i + 1);
+      }
+      frames.extend(completed);
+    }
+
+    assert_eq!(frames, vec![frame]);
+    assert_eq!(assembler.pending(), 0);
+  }
+
+  #[test]
+  fn test_short_buffer_is_incomplete() {
+    let mut assembler = FrameAssembler::new();
+    assert_eq!(assembler.next_frame(), Assembled::Incomplete { missing: 3 });
+
+    assembler.push(&[0x55, 0xAA]);
+    assert_eq!(assembler.next_frame(), Assembled::Incomplete { missing: 1 });
+
+    // size 4 declares 10 bytes in total
+    assembler.push(&[0x04, 0x25, 0x01]);
+    assert_eq!(assembler.next_frame(), Assembled::Incomplete { missing: 5 });
+    assert_eq!(assembler.pending(), 5);
+
+    assembler.push(&[0x32, 0x40, 0x00, 0x63]);
+    assert_eq!(assembler.next_frame(), Assembled::Incomplete { missing: 1 });
+    assert_eq!(assembler.push(&[0xFF]).len(), 1);
+    assert_eq!(assembler.next_frame(), Assembled::Incomplete { missing: 3 });
+  }
+
+  #[test]
+  fn test_split_frame() {
+    let chunks = split_frame(&[0u8; 45], BLE_MTU);
+    assert_eq!(chunks.iter().map(|c| c.len()).collect::<Vec<usize>>(), vec![20, 20, 5]);
+  }
+}
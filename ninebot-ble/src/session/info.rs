@@ -1,4 +1,4 @@
-use super::{MiSession, Payload};
+use super::{MiSession, Payload, Transport, SessionError};
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
 
 use std::time::Duration;
@@ -12,6 +12,22 @@ pub struct GeneralInfo {
   version: String
 }
 
+/**
+ * Details which don't change while connected, shown on "About this scooter" screen
+ */
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScooterInfo {
+  pub serial: String,
+  pub ble_version: String,
+  pub esc_version: String,
+  pub bms_version: String,
+  /**
+   * Total distance in kilometers
+   */
+  pub odometer_km: f32,
+  pub battery_cycles: u16
+}
+
 #[derive(Debug, Serialize)]
 pub struct MotorInfo {
   /**
@@ -71,8 +87,8 @@ impl TryFrom<Payload> for MotorInfo {
   }
 }
 
-impl MiSession {
-  pub async fn general_info(&mut self) -> Result<GeneralInfo> {
+impl<T: Transport> MiSession<T> {
+  pub async fn general_info(&mut self) -> Result<GeneralInfo, SessionError> {
     tracing::debug!("Reading general information");
 
     let cmd = ScooterCommand {
@@ -99,7 +115,7 @@ impl MiSession {
   /**
    * Read scooter serial number
    */
-  pub async fn serial_number(&mut self) -> Result<String> {
+  pub async fn serial_number(&mut self) -> Result<String, SessionError> {
     tracing::debug!("Reading serial number");
     let cmd = ScooterCommand {
       direction: Direction::MasterToMotor,
@@ -117,7 +133,47 @@ impl MiSession {
     Ok(serial)
   }
 
-  pub async fn motor_info(&mut self) -> Result<MotorInfo> {
+  /**
+   * Read scooter serial number, e.g. "16133/00095292". Response is longer than single BLE notification,
+   * transport puts the pieces together
+   */
+  pub async fn read_serial(&mut self) -> Result<String, SessionError> {
+    tracing::debug!("Reading serial");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::GeneralInfo, SERIAL_LENGTH as u8)).await?;
+
+    let payload = self.read_frame().await?;
+    let serial = parse_serial(payload)?;
+    tracing::debug!("serial: {}", serial);
+
+    Ok(serial)
+  }
+
+  /**
+   * Serial, firmware versions, odometer and battery cycles read one after another. Result is kept
+   * on session, so only the first call talks to scooter
+   */
+  pub async fn read_device_info(&mut self) -> Result<ScooterInfo, SessionError> {
+    if let Some(info) = self.device_info() {
+      return Ok(info.clone())
+    }
+
+    tracing::debug!("Reading device info");
+
+    let info = ScooterInfo {
+      serial: self.read_serial().await?,
+      ble_version: self.read_ble_version().await?,
+      esc_version: self.read_esc_version().await?,
+      bms_version: self.read_bms_version().await?,
+      odometer_km: self.read_odometer().await?,
+      battery_cycles: self.read_battery_cycles().await?
+    };
+    self.set_device_info(info.clone());
+
+    Ok(info)
+  }
+
+  pub async fn motor_info(&mut self) -> Result<MotorInfo, SessionError> {
     tracing::debug!("Reading motor info");
 
     self.send(&ScooterCommand {
@@ -129,6 +185,165 @@ impl MiSession {
 
     let payload = self.read(3).await?;
 
-    MotorInfo::try_from(payload)
+    Ok(MotorInfo::try_from(payload)?)
+  }
+}
+
+const SERIAL_LENGTH: usize = 14;
+
+/**
+ * Shorter serials are padded with zeros, bytes which are not UTF-8 become U+FFFD instead of failing whole read
+ */
+fn parse_serial(payload: Payload) -> Result<String> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  let serial = payload.pop_string_utf8(SERIAL_LENGTH)?;
+  Ok(serial.trim_end_matches('\0').trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mi_crypto::{encrypt_uart, decrypt_uart, EncryptionKey, LoginKeychain};
+  use crate::session::transport::MockTransport;
+  use crate::session::SecurityClass;
+  use crate::session::frame_assembler::{FrameAssembler, split_frame, BLE_MTU};
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  fn serial_response(serial: &[u8]) -> Payload {
+    let mut bytes = vec![0x23, 0x01, 0x10];
+    bytes.extend_from_slice(serial);
+    bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+    Payload::from(bytes)
+  }
+
+  #[test]
+  fn test_parse_serial() {
+    assert_eq!(parse_serial(serial_response(b"16133/00095292")).unwrap(), "16133/00095292");
+  }
+
+  #[test]
+  fn test_parse_serial_with_null_padding() {
+    assert_eq!(parse_serial(serial_response(b"26495/0012\0\0\0\0")).unwrap(), "26495/0012");
+  }
+
+  #[test]
+  fn test_parse_serial_with_invalid_bytes() {
+    assert_eq!(parse_serial(serial_response(b"16133/0009\xFF\xFE92")).unwrap(), "16133/0009\u{FFFD}\u{FFFD}92");
+  }
+
+  #[test]
+  fn test_parse_truncated_serial() {
+    assert!(parse_serial(Payload::from(vec![0x23, 0x01, 0x10, 0x31, 0x36])).is_err());
+  }
+
+  #[tokio::test]
+  async fn test_read_serial_from_two_chunks() {
+    let keys = test_keys();
+    let mut plain = vec![SERIAL_LENGTH as u8 + 2, 0x23, 0x01, 0x10];
+    plain.extend_from_slice(b"16133/00095292");
+    let frame = encrypt_uart(&keys.dev, &plain, 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    // scooter sends it as two notifications
+    let chunks = split_frame(&frame, BLE_MTU);
+    assert_eq!(chunks.len(), 2);
+
+    let mut assembler = FrameAssembler::new();
+    assert!(assembler.push(&chunks[0]).is_empty());
+    let frames = assembler.push(&chunks[1]);
+    assert_eq!(frames, vec![frame]);
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(frames), &keys);
+    assert_eq!(session.read_serial().await.unwrap(), "16133/00095292");
+
+    let request = decrypt_uart(&keys.app, &session.transport().written[0]).unwrap();
+    assert_eq!(&request[..4], &[0x20, 0x01, 0x10, 0x0E]);
+  }
+
+  #[tokio::test]
+  async fn test_read_device_info_is_cached() {
+    let keys = test_keys();
+    let response = |plain: &[u8]| encrypt_uart(&keys.dev, plain, 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    let mut serial = vec![SERIAL_LENGTH as u8 + 2, 0x23, 0x01, 0x10];
+    serial.extend_from_slice(b"16133/00095292");
+    let responses = vec![
+      response(&serial),
+      response(&[0x04, 0x23, 0x01, 0x9B, 0x40, 0x01]),
+      response(&[0x04, 0x23, 0x01, 0x1A, 0x46, 0x01]),
+      response(&[0x04, 0x25, 0x01, 0x17, 0x15, 0x01]),
+      response(&[0x06, 0x23, 0x01, 0x29, 0x40, 0xE2, 0x01, 0x00]),
+      response(&[0x04, 0x25, 0x01, 0x1B, 0x2A, 0x00]),
+    ];
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+    assert!(session.device_info().is_none());
+
+    let expected = ScooterInfo {
+      serial: "16133/00095292".to_string(),
+      ble_version: "1.4.0".to_string(),
+      esc_version: "1.4.6".to_string(),
+      bms_version: "1.1.5".to_string(),
+      odometer_km: 123.456,
+      battery_cycles: 42
+    };
+    assert_eq!(session.read_device_info().await.unwrap(), expected);
+    assert_eq!(session.transport().written.len(), 6);
+
+    // second call is answered from session
+    assert_eq!(session.read_device_info().await.unwrap(), expected);
+    assert_eq!(session.transport().written.len(), 6);
+    assert_eq!(session.device_info(), Some(&expected));
+  }
+
+  #[tokio::test]
+  async fn test_second_read_serial_uses_cache() {
+    let keys = test_keys();
+    let mut plain = vec![SERIAL_LENGTH as u8 + 2, 0x23, 0x01, 0x10];
+    plain.extend_from_slice(b"16133/00095292");
+    let frame = encrypt_uart(&keys.dev, &plain, 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![frame.clone(), frame]), &keys);
+    assert_eq!(session.read_serial().await.unwrap(), "16133/00095292");
+    assert_eq!(session.read_serial().await.unwrap(), "16133/00095292");
+    assert_eq!(session.transport().written.len(), 1);
+
+    session.clear_cache();
+    assert_eq!(session.read_serial().await.unwrap(), "16133/00095292");
+    assert_eq!(session.transport().written.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_unread_cached_response_is_not_taken_for_ack() {
+    let keys = test_keys();
+    let mut plain = vec![SERIAL_LENGTH as u8 + 2, 0x23, 0x01, 0x10];
+    plain.extend_from_slice(b"16133/00095292");
+    let serial = encrypt_uart(&keys.dev, &plain, 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+    let ack = MockTransport::write_reply(&keys, Attribute::Lock, 0x01);
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![serial, ack]), &keys);
+    session.set_authorization_level(SecurityClass::Restricted);
+    session.read_serial().await.unwrap();
+
+    // answered from cache and never read, e.g. broadcast read
+    session.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::GeneralInfo, SERIAL_LENGTH as u8)).await.unwrap();
+
+    session.lock().await.unwrap();
+    assert!(session.transport().responses.is_empty());
+  }
+
+  #[test]
+  fn test_only_static_attributes_are_cached() {
+    assert!(Attribute::GeneralInfo.is_static());
+    assert!(Attribute::EscVersion.is_static());
+    assert!(!Attribute::Speed.is_static());
+    assert!(!Attribute::BatteryPercent.is_static());
   }
 }
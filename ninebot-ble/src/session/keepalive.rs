@@ -0,0 +1,144 @@
+//! Keep-alive for idle sessions
+//!
+//! Some M365 firmware drops the BLE link when it sees no traffic for a while, e.g. while HUD only shows
+//! values read long ago. Reading something harmless now and then keeps the link up.
+//!
+//! ## Protocol Reference
+//! - There is no dedicated ping, keep-alive is plain battery percent read (0x32, 2 bytes)
+//! - Read is answered by BMS and changes nothing on the scooter
+//! - Idle timeout is not documented and differs between firmware versions. `DEFAULT_KEEPALIVE_INTERVAL`
+//!   of 5 s is the recommended interval: frequent enough for short timeouts, one small round-trip each time.
+//!   Go longer only after checking the link survives it on your firmware
+
+use super::{MiSession, SessionHandle, Transport, SessionError};
+
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// Recommended pause between keep-alive reads
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+impl<T: Transport> MiSession<T> {
+    /// Send harmless read, so firmware which drops idle links sees traffic
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.keepalive().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn keepalive(&mut self) -> Result<(), SessionError> {
+        tracing::debug!("Sending keep-alive");
+
+        self.read_battery_percent().await?;
+
+        Ok(())
+    }
+}
+
+impl<T: Transport + 'static> SessionHandle<T> {
+    /// Run `keepalive` every `interval` in background, first one after `interval` has passed. Failed keep-alive
+    /// is only logged, task stops by itself once session is closed. Abort returned handle to stop it earlier
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::{SessionHandle, DEFAULT_KEEPALIVE_INTERVAL};
+    /// # async fn example(handle: SessionHandle) -> Result<()> {
+    /// let keepalive = handle.spawn_keepalive(DEFAULT_KEEPALIVE_INTERVAL);
+    /// // ... ride with the HUD on ...
+    /// keepalive.abort();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_keepalive(&self, interval: Duration) -> JoinHandle<()> {
+        let handle = self.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            // Link busy with other reads is alive anyway, no need to catch up on missed ticks
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                ticker.tick().await;
+
+                let mut session = handle.session().await;
+                if session.is_closed() {
+                    tracing::debug!("Session closed, stopping keep-alive");
+                    return;
+                }
+
+                if let Err(err) = session.keepalive().await {
+                    tracing::warn!("Keep-alive failed: {}", err);
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{encrypt_uart, decrypt_uart, EncryptionKey, LoginKeychain};
+
+    fn test_keys() -> LoginKeychain {
+        LoginKeychain {
+            dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+            app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] },
+        }
+    }
+
+    fn battery_percent_response(keys: &LoginKeychain) -> Vec<u8> {
+        encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_reads_battery_percent() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![battery_percent_response(&keys)]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        session.keepalive().await.unwrap();
+
+        let written = &session.transport().written;
+        assert_eq!(written.len(), 1);
+        let request = decrypt_uart(&keys.app, &written[0]).unwrap();
+        assert_eq!(&request[..4], &[0x22, 0x01, 0x32, 0x02]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_task_reads_on_schedule() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![battery_percent_response(&keys); 2]);
+        let handle = SessionHandle::new(MiSession::with_transport(transport, &keys));
+
+        let keepalive = handle.spawn_keepalive(Duration::from_secs(5));
+
+        tokio::time::sleep(Duration::from_secs(4)).await;
+        assert_eq!(handle.session().await.transport().written.len(), 0);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(handle.session().await.transport().written.len(), 1);
+
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        assert_eq!(handle.session().await.transport().written.len(), 2);
+
+        keepalive.abort();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_task_stops_when_session_closes() {
+        let handle = SessionHandle::new(MiSession::with_transport(MockTransport::default(), &test_keys()));
+        handle.session().await.close();
+
+        let keepalive = handle.spawn_keepalive(Duration::from_secs(5));
+        tokio::time::sleep(Duration::from_secs(6)).await;
+
+        assert!(tokio::time::timeout(Duration::from_secs(1), keepalive).await.is_ok());
+        assert!(handle.session().await.transport().written.is_empty());
+    }
+}
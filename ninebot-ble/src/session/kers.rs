@@ -0,0 +1,131 @@
+//! KERS (regenerative braking) strength for M365 scooter
+//!
+//! Controls how strongly the motor brakes and recovers energy when the
+//! throttle is released.
+//!
+//! ## Protocol Reference
+//! - Weak: 0x0000, Medium: 0x0001, Strong: 0x0002 at address 0x7B
+//! - Direction: Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01) / Write (0x03)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KersLevel {
+    Weak,
+    Medium,
+    Strong,
+}
+
+impl KersLevel {
+    fn value(&self) -> u16 {
+        match self {
+            KersLevel::Weak => 0x0000,
+            KersLevel::Medium => 0x0001,
+            KersLevel::Strong => 0x0002,
+        }
+    }
+}
+
+impl TryFrom<u16> for KersLevel {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0000 => Ok(KersLevel::Weak),
+            0x0001 => Ok(KersLevel::Medium),
+            0x0002 => Ok(KersLevel::Strong),
+            _ => Err(anyhow!("Unknown KERS level: 0x{:04X}", value)),
+        }
+    }
+}
+
+/// Build KERS write command, attribute 0x7B is mapped as `Attribute::Supplementary`
+fn set_kers_command(level: KersLevel) -> ScooterCommand {
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute: Attribute::Supplementary,
+        payload: level.value().to_le_bytes().to_vec()
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Set KERS strength
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::KersLevel;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_kers(KersLevel::Strong).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_kers(&mut self, level: KersLevel) -> Result<(), SessionError> {
+        tracing::debug!("Setting KERS level: {:?}", level);
+
+        self.send(&set_kers_command(level)).await?;
+
+        Ok(())
+    }
+
+    /// Read current KERS strength, unknown values are reported as error
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let level = session.read_kers().await?;
+    /// println!("KERS: {:?}", level);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_kers(&mut self) -> Result<KersLevel, SessionError> {
+        tracing::debug!("Reading KERS level");
+
+        self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::Supplementary, 0x02)).await?;
+
+        let mut payload = self.read(2).await?;
+        payload.pop_head()?;
+
+        Ok(KersLevel::try_from(payload.pop_u16()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kers_command_bytes() {
+        let expected = [
+            (KersLevel::Weak, 0x00),
+            (KersLevel::Medium, 0x01),
+            (KersLevel::Strong, 0x02),
+        ];
+
+        for (level, byte) in expected {
+            let bytes = set_kers_command(level).try_as_bytes().unwrap();
+            // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7B) + Payload(level, 0x00)
+            assert_eq!(bytes, vec![0x04, 0x20, 0x03, 0x7B, byte, 0x00]);
+        }
+    }
+
+    #[test]
+    fn test_kers_roundtrip() {
+        for level in [KersLevel::Weak, KersLevel::Medium, KersLevel::Strong] {
+            assert_eq!(KersLevel::try_from(level.value()).unwrap(), level);
+        }
+    }
+
+    #[test]
+    fn test_unknown_kers_level() {
+        assert!(KersLevel::try_from(0x0003).is_err());
+    }
+}
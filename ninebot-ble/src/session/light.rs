@@ -1,23 +1,74 @@
-//! Tail light control for M365 scooter
+//! Light control for M365 scooter
 //!
-//! Controls the rear/tail light of the scooter with direct on/off commands.
+//! Controls the rear/tail light mode and, on models which have a switchable one,
+//! the front headlight with direct on/off commands.
 //!
 //! ## Protocol Reference
-//! - Light On: Write 0x0002 to address 0x7D
-//! - Light Off: Write 0x0000 to address 0x7D
+//! - Tail Light Off: Write 0x0000 to address 0x7D
+//! - Tail Light On Brake: Write 0x0001 to address 0x7D
+//! - Tail Light Always: Write 0x0002 to address 0x7D
+//! - Tail Light Mode: Read 2 bytes from address 0x7D
+//! - Headlight On: Write 0x0001 to address 0x7E
+//! - Headlight Off: Write 0x0000 to address 0x7E
 //! - Direction: Master to Motor Controller (0x20)
 //! - Command Type: Write (0x03)
-//!
-//! ## Note
-//! This module provides simple on/off control. For tail light mode settings
-//! (Off, OnBrake, Always), see the `set_tail_light` method in `settings.rs`.
+//! - Writes are answered with 0x01 (ACK), anything else is NAK
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, Attribute};
+
+use anyhow::{Result, anyhow};
+
+/// When the tail light is lit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TailLightMode {
+    /// Never lit
+    Off,
+    /// Lit only while braking
+    OnBrake,
+    /// Always lit, brighter while braking
+    Always,
+}
+
+impl TailLightMode {
+    /// Value written to the tail light register
+    pub fn value(&self) -> u16 {
+        match self {
+            TailLightMode::Off => 0x0000,
+            TailLightMode::OnBrake => 0x0001,
+            TailLightMode::Always => 0x0002,
+        }
+    }
+}
+
+impl TryFrom<u16> for TailLightMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u16) -> Result<Self> {
+        match value {
+            0x0000 => Ok(TailLightMode::Off),
+            0x0001 => Ok(TailLightMode::OnBrake),
+            0x0002 => Ok(TailLightMode::Always),
+            _ => Err(anyhow!("Unknown tail light mode: 0x{:04X}", value))
+        }
+    }
+}
 
-use super::MiSession;
-use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+fn tail_light_command(mode: TailLightMode) -> Result<ScooterCommand> {
+    ScooterCommand::builder()
+        .attribute(Attribute::TailLight)
+        .write_u16(mode.value())
+        .build()
+}
 
-use anyhow::Result;
+fn headlight_command(on: bool) -> Result<ScooterCommand> {
+    ScooterCommand::builder()
+        .attribute(Attribute::Headlight)
+        .write_u16(on as u16)
+        .build()
+}
 
-impl MiSession {
+impl<T: Transport> MiSession<T> {
     /// Turn on the tail light
     ///
     /// Sets the tail light to always-on mode.
@@ -31,20 +82,8 @@ impl MiSession {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn light_on(&mut self) -> Result<()> {
-        tracing::debug!("Turning tail light on");
-
-        // Payload: [0x02, 0x00] - Write value 0x0002 (little-endian: LSB first) for "Always" mode
-        let payload = vec![0x02, 0x00];
-
-        self.send(&ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::TailLight,
-            payload
-        }).await?;
-
-        Ok(())
+    pub async fn light_on(&mut self) -> Result<(), SessionError> {
+        self.set_tail_light(TailLightMode::Always).await
     }
 
     /// Turn off the tail light
@@ -60,20 +99,8 @@ impl MiSession {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn light_off(&mut self) -> Result<()> {
-        tracing::debug!("Turning tail light off");
-
-        // Payload: [0x00, 0x00] - Write value 0x0000 (little-endian) for "Off" mode
-        let payload = vec![0x00, 0x00];
-
-        self.send(&ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::TailLight,
-            payload
-        }).await?;
-
-        Ok(())
+    pub async fn light_off(&mut self) -> Result<(), SessionError> {
+        self.set_tail_light(TailLightMode::Off).await
     }
 
     /// Set tail light state
@@ -92,30 +119,110 @@ impl MiSession {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn set_light(&mut self, on: bool) -> Result<()> {
+    pub async fn set_light(&mut self, on: bool) -> Result<(), SessionError> {
         if on {
             self.light_on().await
         } else {
             self.light_off().await
         }
     }
+
+    /// Set when the tail light is lit
+    ///
+    /// # Arguments
+    /// * `mode` - `Off`, `OnBrake` or `Always`
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::TailLightMode;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_tail_light(TailLightMode::OnBrake).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_tail_light(&mut self, mode: TailLightMode) -> Result<(), SessionError> {
+        tracing::debug!("Setting tail light: {:?}", mode);
+
+        self.send(&tail_light_command(mode)?).await?;
+
+        Ok(())
+    }
+
+    /// Read current tail light mode
+    ///
+    /// Fails when the scooter reports a value which is not one of the three modes.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// println!("Tail light: {:?}", session.read_tail_light_mode().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_tail_light_mode(&mut self) -> Result<TailLightMode, SessionError> {
+        tracing::debug!("Reading tail light mode");
+
+        self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::TailLight, 0x02)).await?;
+
+        let mut payload = self.read_frame().await?;
+        payload.pop_head()?;
+
+        Ok(TailLightMode::try_from(payload.pop_u16()?)?)
+    }
+
+    /// Turn the front headlight on or off
+    ///
+    /// Not all models have a controllable headlight. Stock M365 keeps it tied to
+    /// the tail light and rejects the write, which is reported as
+    /// `SessionError::CommandRejected` instead of silently doing nothing.
+    ///
+    /// # Arguments
+    /// * `on` - `true` to turn on, `false` to turn off
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// if session.set_headlight(true).await.is_err() {
+    ///     println!("Headlight can't be switched on this model");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_headlight(&mut self, on: bool) -> Result<(), SessionError> {
+        tracing::debug!("Setting headlight: {}", on);
+
+        self.send(&headlight_command(on)?).await?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{encrypt_uart, decrypt_uart, EncryptionKey, LoginKeychain};
+
+    fn test_keys() -> LoginKeychain {
+        let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        LoginKeychain { dev: key.clone(), app: key }
+    }
+
+    fn tail_light_reply(keys: &LoginKeychain, mode: u16) -> Vec<u8> {
+        let [low, high] = mode.to_le_bytes();
+        // MotorToMaster (0x23), Read (0x01), TailLight (0x7D), mode
+        encrypt_uart(&keys.dev, &[0x04, 0x23, 0x01, 0x7D, low, high], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+    }
 
     #[test]
     fn test_light_on_command_bytes() {
-        // Verify the ScooterCommand produces correct bytes for light on
-        let cmd = ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::TailLight,
-            payload: vec![0x02, 0x00]  // 0x0002 little-endian: LSB first
-        };
-
-        let bytes = cmd.as_bytes();
+        // Verify the command produces correct bytes for light on
+        let cmd = tail_light_command(TailLightMode::Always).unwrap();
+
+        let bytes = cmd.try_as_bytes().unwrap();
         // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7D) + Payload(0x02, 0x00)
         assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
         assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
@@ -123,19 +230,18 @@ mod tests {
         assert_eq!(bytes[3], 0x7D); // attribute: TailLight
         assert_eq!(bytes[4], 0x02); // payload[0] - LSB
         assert_eq!(bytes[5], 0x00); // payload[1] - MSB
+
+        // Checksum trailer is not part of as_bytes, encryption adds its own crc, plain frame carries it
+        assert_eq!(cmd.checksum().unwrap(), [0x59, 0xFF]);
+        assert_eq!(&cmd.as_frame().unwrap()[8..], &[0x59, 0xFF]);
     }
 
     #[test]
     fn test_light_off_command_bytes() {
-        // Verify the ScooterCommand produces correct bytes for light off
-        let cmd = ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::TailLight,
-            payload: vec![0x00, 0x00]
-        };
-
-        let bytes = cmd.as_bytes();
+        // Verify the command produces correct bytes for light off
+        let cmd = tail_light_command(TailLightMode::Off).unwrap();
+
+        let bytes = cmd.try_as_bytes().unwrap();
         // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x7D) + Payload(0x00, 0x00)
         assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
         assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
@@ -143,5 +249,87 @@ mod tests {
         assert_eq!(bytes[3], 0x7D); // attribute: TailLight
         assert_eq!(bytes[4], 0x00); // payload[0]
         assert_eq!(bytes[5], 0x00); // payload[1]
+
+        // Checksum trailer is not part of as_bytes, encryption adds its own crc, plain frame carries it
+        assert_eq!(cmd.checksum().unwrap(), [0x5B, 0xFF]);
+        assert_eq!(&cmd.as_frame().unwrap()[8..], &[0x5B, 0xFF]);
+    }
+
+    #[test]
+    fn test_tail_light_mode_payload() {
+        let modes = [
+            (TailLightMode::Off, 0x00),
+            (TailLightMode::OnBrake, 0x01),
+            (TailLightMode::Always, 0x02),
+        ];
+
+        for (mode, byte) in modes {
+            let bytes = tail_light_command(mode).unwrap().try_as_bytes().unwrap();
+            assert_eq!(bytes, vec![0x04, 0x20, 0x03, 0x7D, byte, 0x00], "{:?}", mode);
+            assert_eq!(TailLightMode::try_from(mode.value()).unwrap(), mode);
+        }
+
+        assert!(TailLightMode::try_from(0x0003).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_tail_light_mode() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![tail_light_reply(&keys, 0x0001), tail_light_reply(&keys, 0x0007)]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        assert_eq!(session.read_tail_light_mode().await.unwrap(), TailLightMode::OnBrake);
+        assert!(session.read_tail_light_mode().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_light_on_off_use_tail_light_mode() {
+        let keys = test_keys();
+        let mut transport = MockTransport::default();
+        transport.auto_reply_with_simulated_responses(&keys);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        session.light_on().await.unwrap();
+        session.set_light(false).await.unwrap();
+
+        let written = &session.transport().written;
+        assert_eq!(written.len(), 2);
+        assert_eq!(decrypt_uart(&keys.app, &written[0]).unwrap()[3..5], [0x02, 0x00]);
+        assert_eq!(decrypt_uart(&keys.app, &written[1]).unwrap()[3..5], [0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_headlight_command_bytes() {
+        let on = headlight_command(true).unwrap();
+        assert_eq!(on.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7E, 0x01, 0x00]);
+
+        let off = headlight_command(false).unwrap();
+        assert_eq!(off.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x7E, 0x00, 0x00]);
+    }
+
+    #[tokio::test]
+    async fn test_headlight_ack() {
+        let keys = test_keys();
+        let mut transport = MockTransport::default();
+        transport.auto_reply_with_simulated_responses(&keys);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        session.set_headlight(true).await.unwrap();
+        assert_eq!(session.transport().written.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_headlight_nak() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![MockTransport::write_reply(&keys, Attribute::Headlight, 0x00)]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        match session.set_headlight(true).await {
+            Err(SessionError::CommandRejected { attribute, status }) => {
+                assert_eq!(attribute, Attribute::Headlight);
+                assert_eq!(status, 0x00);
+            },
+            other => panic!("Expected CommandRejected, got {:?}", other)
+        }
     }
 }
@@ -6,41 +6,56 @@
 //! ## Protocol Reference
 //! - Lock: Write 0x0001 to address 0x70
 //! - Unlock: Write 0x0001 to address 0x71
+//! - Lock State: Read 2 bytes from address 0xB2, bit 0 set = locked. Other bits of the same flags
+//!   word carry unrelated state (parked scooter reports 0x0800) and are ignored
 //! - Direction: Master to Motor Controller (0x20)
-//! - Command Type: Write (0x03)
+//! - Command Type: Write (0x03), Read (0x01) for lock state
 
-use super::MiSession;
-use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute, u16_le_payload};
 
 use anyhow::Result;
 
-impl MiSession {
+/// Bit of status flags word (0xB2) which is set while motor is locked
+const LOCKED_BIT: u16 = 0x0001;
+
+/// Both lock and unlock are triggered by writing 0x0001 to their register
+fn lock_command(attribute: Attribute) -> ScooterCommand {
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute,
+        payload: u16_le_payload(0x0001)
+    }
+}
+
+/// Lock bit of status flags, the other flags don't matter for lock state
+pub(super) fn lock_state_from_raw(value: u16) -> bool {
+    value & LOCKED_BIT != 0
+}
+
+impl<T: Transport> MiSession<T> {
     /// Lock the scooter motor
     ///
     /// When locked, the scooter cannot be ridden. The motor controller
-    /// will ignore throttle input until unlocked.
+    /// will ignore throttle input until unlocked. Requires session
+    /// authorized for `SecurityClass::Restricted`.
     ///
     /// # Example
     /// ```rust,no_run
     /// # use anyhow::Result;
+    /// # use ninebot_ble::session::SecurityClass;
     /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_authorization_level(SecurityClass::Restricted);
     /// session.lock().await?;
     /// println!("Scooter locked!");
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn lock(&mut self) -> Result<()> {
+    pub async fn lock(&mut self) -> Result<(), SessionError> {
         tracing::debug!("Locking scooter motor");
 
-        // Payload: [0x01, 0x00] - Write value 0x0001 (little-endian: LSB first)
-        let payload = vec![0x01, 0x00];
-
-        self.send(&ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::Lock,
-            payload
-        }).await?;
+        self.send(&lock_command(Attribute::Lock)).await?;
 
         Ok(())
     }
@@ -48,33 +63,54 @@ impl MiSession {
     /// Unlock the scooter motor
     ///
     /// Re-enables the motor after being locked. The scooter can be
-    /// ridden normally after unlocking.
+    /// ridden normally after unlocking. Requires session authorized
+    /// for `SecurityClass::Restricted`.
     ///
     /// # Example
     /// ```rust,no_run
     /// # use anyhow::Result;
+    /// # use ninebot_ble::session::SecurityClass;
     /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_authorization_level(SecurityClass::Restricted);
     /// session.unlock().await?;
     /// println!("Scooter unlocked!");
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn unlock(&mut self) -> Result<()> {
+    pub async fn unlock(&mut self) -> Result<(), SessionError> {
         tracing::debug!("Unlocking scooter motor");
 
-        // Payload: [0x01, 0x00] - Write value 0x0001 (little-endian: LSB first)
-        let payload = vec![0x01, 0x00];
-
-        self.send(&ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::Unlock,
-            payload
-        }).await?;
+        self.send(&lock_command(Attribute::Unlock)).await?;
 
         Ok(())
     }
 
+    /// Read whether the scooter motor is currently locked
+    ///
+    /// Lock and unlock don't report state, so use this to sync UI after
+    /// reconnect. Reading is public, no elevated authorization is needed.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// if session.read_lock_state().await? {
+    ///     println!("Scooter is locked");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_lock_state(&mut self) -> Result<bool, SessionError> {
+        tracing::debug!("Reading lock state");
+
+        self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::LockStatus, 0x02)).await?;
+
+        let mut payload = self.read_frame().await?;
+        payload.pop_head()?;
+
+        Ok(lock_state_from_raw(payload.pop_u16()?))
+    }
+
     /// Set scooter lock state
     ///
     /// Convenience method to lock or unlock the scooter based on a boolean.
@@ -85,13 +121,15 @@ impl MiSession {
     /// # Example
     /// ```rust,no_run
     /// # use anyhow::Result;
+    /// # use ninebot_ble::session::SecurityClass;
     /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_authorization_level(SecurityClass::Restricted);
     /// session.set_lock(true).await?;  // Lock the scooter
     /// session.set_lock(false).await?; // Unlock the scooter
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn set_lock(&mut self, locked: bool) -> Result<()> {
+    pub async fn set_lock(&mut self, locked: bool) -> Result<(), SessionError> {
         if locked {
             self.lock().await
         } else {
@@ -103,18 +141,15 @@ impl MiSession {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
 
     #[test]
     fn test_lock_command_bytes() {
-        // Verify the ScooterCommand produces correct bytes for lock
-        let cmd = ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::Lock,
-            payload: vec![0x01, 0x00]  // 0x0001 little-endian: LSB first
-        };
+        // Verify the command produces correct bytes for lock
+        let cmd = lock_command(Attribute::Lock);
 
-        let bytes = cmd.as_bytes();
+        let bytes = cmd.try_as_bytes().unwrap();
         // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x70) + Payload(0x01, 0x00)
         assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
         assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
@@ -122,19 +157,18 @@ mod tests {
         assert_eq!(bytes[3], 0x70); // attribute: Lock
         assert_eq!(bytes[4], 0x01); // payload[0] - LSB
         assert_eq!(bytes[5], 0x00); // payload[1] - MSB
+
+        // Checksum trailer is not part of as_bytes, encryption adds its own crc, plain frame carries it
+        assert_eq!(cmd.checksum().unwrap(), [0x67, 0xFF]);
+        assert_eq!(&cmd.as_frame().unwrap()[8..], &[0x67, 0xFF]);
     }
 
     #[test]
     fn test_unlock_command_bytes() {
-        // Verify the ScooterCommand produces correct bytes for unlock
-        let cmd = ScooterCommand {
-            direction: Direction::MasterToMotor,
-            read_write: ReadWrite::Write,
-            attribute: Attribute::Unlock,
-            payload: vec![0x01, 0x00]  // 0x0001 little-endian: LSB first
-        };
+        // Verify the command produces correct bytes for unlock
+        let cmd = lock_command(Attribute::Unlock);
 
-        let bytes = cmd.as_bytes();
+        let bytes = cmd.try_as_bytes().unwrap();
         // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x71) + Payload(0x01, 0x00)
         assert_eq!(bytes[0], 0x04); // length = payload.len() + 2 = 2 + 2 = 4
         assert_eq!(bytes[1], 0x20); // direction: MasterToMotor
@@ -142,5 +176,36 @@ mod tests {
         assert_eq!(bytes[3], 0x71); // attribute: Unlock
         assert_eq!(bytes[4], 0x01); // payload[0] - LSB
         assert_eq!(bytes[5], 0x00); // payload[1] - MSB
+
+        // Checksum trailer is not part of as_bytes, encryption adds its own crc, plain frame carries it
+        assert_eq!(cmd.checksum().unwrap(), [0x66, 0xFF]);
+        assert_eq!(&cmd.as_frame().unwrap()[8..], &[0x66, 0xFF]);
+    }
+
+    #[test]
+    fn test_lock_state_from_raw() {
+        assert!(!lock_state_from_raw(0x0000));
+        assert!(lock_state_from_raw(0x0001));
+        assert!(!lock_state_from_raw(0x0002));
+        assert!(lock_state_from_raw(0xFFFF));
+    }
+
+    #[tokio::test]
+    async fn test_read_lock_state() {
+        let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        let keys = LoginKeychain { dev: key.clone(), app: key };
+        let reply = |flags: u16| {
+            let [low, high] = flags.to_le_bytes();
+            // MotorToMaster (0x23), Read (0x01), LockStatus (0xB2), flags
+            encrypt_uart(&keys.dev, &[0x04, 0x23, 0x01, 0xB2, low, high], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+        };
+        let transport = MockTransport::with_responses(vec![reply(0x0001), reply(0x0000), reply(0x0800), reply(0x0801)]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        assert!(session.read_lock_state().await.unwrap());
+        assert!(!session.read_lock_state().await.unwrap());
+        // flags of parked scooter from captured status block
+        assert!(!session.read_lock_state().await.unwrap());
+        assert!(session.read_lock_state().await.unwrap());
     }
 }
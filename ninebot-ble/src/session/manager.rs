@@ -0,0 +1,184 @@
+use super::{MiSession, SessionError};
+use super::commands::ScooterCommand;
+use super::transport::{Transport, BleTransport};
+use crate::scanner::ScooterScanner;
+use crate::connection::ConnectionHelper;
+use crate::login::LoginRequest;
+use crate::mi_crypto::AuthToken;
+
+use std::collections::HashMap;
+use btleplug::api::BDAddr;
+use futures::future::join_all;
+
+/**
+ * Sessions with several scooters at once, keyed by MAC address. Meant for fleets, e.g. locking every
+ * scooter in the yard with one `broadcast`
+ */
+pub struct SessionManager<T: Transport = BleTransport> {
+  sessions: HashMap<BDAddr, MiSession<T>>,
+  scanner: Option<ScooterScanner>,
+}
+
+impl<T: Transport> SessionManager<T> {
+  pub fn new() -> Self {
+    Self {
+      sessions: HashMap::new(),
+      scanner: None,
+    }
+  }
+
+  /**
+   * Manage already established session, session previously kept for the address is returned
+   */
+  pub fn insert(&mut self, addr: BDAddr, session: MiSession<T>) -> Option<MiSession<T>> {
+    self.sessions.insert(addr, session)
+  }
+
+  pub fn get(&mut self, addr: &BDAddr) -> Option<&mut MiSession<T>> {
+    self.sessions.get_mut(addr)
+  }
+
+  /**
+   * Stop managing scooter, dropping returned session closes its link
+   */
+  pub fn remove(&mut self, addr: &BDAddr) -> Option<MiSession<T>> {
+    self.sessions.remove(addr)
+  }
+
+  pub fn addresses(&self) -> Vec<BDAddr> {
+    self.sessions.keys().cloned().collect()
+  }
+
+  pub fn len(&self) -> usize {
+    self.sessions.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.sessions.is_empty()
+  }
+
+  /**
+   * Send the same command to every scooter concurrently. One scooter failing doesn't stop the others,
+   * every address gets its own result. Writes wait for ACK like `send`, so this is meant for writes,
+   * response to a read is left on each session for `read_frame`
+   */
+  pub async fn broadcast(&mut self, cmd: &ScooterCommand) -> HashMap<BDAddr, Result<bool, SessionError>> {
+    let sends = self.sessions.iter_mut().map(|(addr, session)| async move {
+      (*addr, session.send(cmd).await)
+    });
+
+    join_all(sends).await.into_iter().collect()
+  }
+}
+
+impl<T: Transport> Default for SessionManager<T> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SessionManager<BleTransport> {
+  /**
+   * Find scooter by address, connect and log in with its token. Scanner is started on first connect
+   * and shared by the following ones
+   */
+  pub async fn connect(&mut self, addr: BDAddr, token: &AuthToken) -> anyhow::Result<&mut MiSession> {
+    if self.scanner.is_none() {
+      self.scanner = Some(ScooterScanner::new().await?);
+    }
+    let scanner = self.scanner.as_mut().unwrap();
+
+    let scooter = scanner.wait_for(&addr).await?;
+    let device = scanner.peripheral(&scooter).await?;
+    ConnectionHelper::new(&device).connect().await?;
+
+    let session = LoginRequest::new(&device, token).await?.start().await?;
+    tracing::info!("Managing session with {}", addr);
+    self.sessions.insert(addr, session);
+
+    Ok(self.sessions.get_mut(&addr).unwrap())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transport::MockTransport;
+  use super::super::commands::{Attribute, SecurityClass};
+  use crate::mi_crypto::{EncryptionKey, LoginKeychain};
+
+  fn test_keys() -> LoginKeychain {
+    let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+    LoginKeychain { dev: key.clone(), app: key }
+  }
+
+  fn session(responses: Vec<Vec<u8>>) -> MiSession<MockTransport> {
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &test_keys());
+    session.set_authorization_level(SecurityClass::Restricted);
+    session
+  }
+
+  /**
+   * Scooter which acknowledges every command
+   */
+  fn accepting_session() -> MiSession<MockTransport> {
+    let mut transport = MockTransport::default();
+    transport.auto_reply_with_simulated_responses(&test_keys());
+
+    let mut session = MiSession::with_transport(transport, &test_keys());
+    session.set_authorization_level(SecurityClass::Restricted);
+    session
+  }
+
+  fn lock_command() -> ScooterCommand {
+    ScooterCommand::builder().attribute(Attribute::Lock).write_u16(0x0001).build().unwrap()
+  }
+
+  #[tokio::test]
+  async fn test_broadcast_hits_every_scooter() {
+    let first = BDAddr::from([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    let second = BDAddr::from([0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]);
+
+    let mut manager = SessionManager::new();
+    manager.insert(first, accepting_session());
+    manager.insert(second, accepting_session());
+
+    let results = manager.broadcast(&lock_command()).await;
+
+    assert_eq!(results.len(), 2);
+    assert!(results.values().all(|result| result.is_ok()));
+    assert_eq!(manager.get(&first).unwrap().transport().written.len(), 1);
+    assert_eq!(manager.get(&second).unwrap().transport().written.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_broadcast_reports_partial_failure() {
+    let keys = test_keys();
+    let accepting = BDAddr::from([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    let rejecting = BDAddr::from([0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F]);
+
+    let mut manager = SessionManager::new();
+    manager.insert(accepting, accepting_session());
+    manager.insert(rejecting, session(vec![MockTransport::write_reply(&keys, Attribute::Lock, 0x00)]));
+
+    let results = manager.broadcast(&lock_command()).await;
+
+    assert!(results[&accepting].is_ok());
+    assert!(matches!(results[&rejecting], Err(SessionError::CommandRejected { status: 0x00, .. })));
+  }
+
+  #[test]
+  fn test_insert_get_remove() {
+    let addr = BDAddr::from([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+    let mut manager = SessionManager::new();
+    assert!(manager.is_empty());
+
+    assert!(manager.insert(addr, session(vec![])).is_none());
+    assert_eq!(manager.addresses(), vec![addr]);
+    assert!(manager.get(&addr).is_some());
+
+    assert!(manager.remove(&addr).is_some());
+    assert!(manager.get(&addr).is_none());
+    assert_eq!(manager.len(), 0);
+  }
+}
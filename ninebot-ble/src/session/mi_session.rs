@@ -1,41 +1,1087 @@
 pub use super::payload::Payload;
-use super::commands::ScooterCommand;
-use crate::protocol::MiProtocol;
-use crate::mi_crypto::{encrypt_uart, decrypt_uart, LoginKeychain};
-use crate::consts::Registers;
+use super::commands::{self, ScooterCommand, Direction, ReadWrite, Attribute, SecurityClass, MutationLog};
+use super::transport::{Transport, BleTransport, WriteMode};
+use super::range::DEFAULT_CONSUMPTION_WH_PER_KM;
+use super::reconnect::ReconnectPolicy;
+use super::info::ScooterInfo;
+use super::model::Model;
+use crate::frame::ScooterResponse;
+#[cfg(feature = "capture")]
+use super::capture::{FrameTap, FrameDirection};
+use crate::uart_service::UartService;
+use crate::mi_crypto::{encrypt_uart, decrypt_uart, uart_frame_counter, crc16, LoginKeychain, MiCryptoError};
 
+use std::collections::HashMap;
+use std::time::Duration;
 use anyhow::Result;
 use btleplug::platform::Peripheral;
+use thiserror::Error;
 
-pub struct MiSession {
-  protocol: MiProtocol,
+/**
+ * Every session method fails with one of these, so callers can e.g. retry on Timeout and reconnect on Disconnected.
+ * It converts into anyhow::Error with `?`, so code using anyhow doesn't need to change
+ */
+#[derive(Error, Debug)]
+pub enum SessionError {
+  #[error("Command requires {required:?} authorization, session has {granted:?}")]
+  Unauthorized { required: SecurityClass, granted: SecurityClass },
+  #[error("Scooter did not respond in time")]
+  Timeout,
+  #[error("Response frame is corrupted, checksum does not match")]
+  ChecksumMismatch,
+  #[error("Expected response for {expected}, but received {received}")]
+  UnexpectedAttribute { expected: Attribute, received: Attribute },
+  #[error("Scooter rejected write to {attribute} (status 0x{status:02X})")]
+  CommandRejected { attribute: Attribute, status: u8 },
+  #[error("Scooter is disconnected")]
+  Disconnected,
+  #[error("Transport failed: {0}")]
+  Transport(std::io::Error),
+  #[error("Session error: {0}")]
+  Other(anyhow::Error)
+}
+
+impl From<anyhow::Error> for SessionError {
+  fn from(other: anyhow::Error) -> Self {
+    SessionError::Other(other)
+  }
+}
+
+/**
+ * How long read waits for response when nothing else was set with `set_timeout`
+ */
+pub const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const WRITE_ACK: u8 = 0x01;
+
+/**
+ * Every decrypted response ends with random bytes scooter mixes into frame
+ */
+const RESPONSE_RANDOM_LEN: usize = 4;
+
+pub struct MiSession<T: Transport = BleTransport> {
+  transport: T,
   keys: LoginKeychain,
+  authorization: SecurityClass,
+  mutations: MutationLog,
+  closed: bool,
+  /**
+   * Last read request, response for any other attribute is rejected. It is sent again when link drops before response.
+   * Cleared only once its response is read, so request whose reader was dropped is still known when next one is sent
+   */
+  pending_read: Option<ScooterCommand>,
+  /**
+   * Requests whose reader gave up (future dropped or timed out), their responses may still arrive and are discarded
+   */
+  orphaned: Vec<Attribute>,
+  timeout: Duration,
+  consumption_wh_per_km: f32,
+  reconnect_policy: ReconnectPolicy,
+  auto_reconnect: bool,
+  /**
+   * Counter expected in next frame from scooter, None until first frame arrives
+   */
+  rx_counter: Option<u16>,
+  counter_resyncs: u32,
+  device_info: Option<ScooterInfo>,
+  /**
+   * Responses to reads of static attributes, see `Attribute::is_static`
+   */
+  cache: HashMap<Attribute, CachedRead>,
+  /**
+   * Cached response `read_frame` returns instead of waiting for scooter
+   */
+  cached_response: Option<Vec<u8>>,
+  notification_handlers: Vec<Box<dyn FnMut(&ScooterResponse) + Send>>,
+  write_mode: WriteMode,
+  model: Model,
+  #[cfg(feature = "capture")]
+  tap: Option<FrameTap>,
 }
 
 impl MiSession {
-  pub async fn new(device: &Peripheral, keys: &LoginKeychain) -> Result<Self> {
-    let protocol = MiProtocol::new(device).await?;
-    let keys = keys.clone();
+  pub async fn new(device: &Peripheral, keys: &LoginKeychain) -> Result<Self, SessionError> {
+    let transport = BleTransport::new(device).await?;
+
+    Ok(Self::with_transport(transport, keys))
+  }
+
+  /**
+   * UART service and characteristics scooter answered on, useful when debugging new firmware revision
+   */
+  pub fn uart_service(&self) -> UartService {
+    self.transport.uart()
+  }
+
+  /**
+   * Export commands with their timestamps (in microseconds) as PCAPNG file which can be opened in Wireshark
+   */
+  pub fn pcapng_export(commands: &[(u64, ScooterCommand)]) -> Result<Vec<u8>> {
+    commands::pcapng_export(commands)
+  }
+}
+
+impl<T: Transport> MiSession<T> {
+  /**
+   * Create session which talks to scooter over given transport, keys have to come from successful login
+   */
+  pub fn with_transport(transport: T, keys: &LoginKeychain) -> Self {
+    Self {
+      transport,
+      keys: keys.clone(),
+      authorization: SecurityClass::Public,
+      mutations: MutationLog::new(),
+      closed: false,
+      pending_read: None,
+      orphaned: Vec::new(),
+      timeout: DEFAULT_RESPONSE_TIMEOUT,
+      consumption_wh_per_km: DEFAULT_CONSUMPTION_WH_PER_KM,
+      reconnect_policy: ReconnectPolicy::default(),
+      auto_reconnect: false,
+      rx_counter: None,
+      counter_resyncs: 0,
+      device_info: None,
+      cache: HashMap::new(),
+      cached_response: None,
+      notification_handlers: Vec::new(),
+      write_mode: WriteMode::default(),
+      model: Model::default(),
+      #[cfg(feature = "capture")]
+      tap: None
+    }
+  }
+
+  pub fn transport(&self) -> &T {
+    &self.transport
+  }
+
+  #[cfg(test)]
+  pub(crate) fn transport_mut(&mut self) -> &mut T {
+    &mut self.transport
+  }
+
+  /**
+   * Allow sending commands up to given security class. New sessions can send only public commands,
+   * so e.g. locking scooter has to be allowed explicitly with SecurityClass::Restricted
+   */
+  pub fn set_authorization_level(&mut self, level: SecurityClass) {
+    self.authorization = level;
+  }
+
+  /**
+   * Give up on response which doesn't arrive within given time, e.g. because command was sent
+   * to controller which doesn't own the register. Defaults to DEFAULT_RESPONSE_TIMEOUT
+   */
+  pub fn set_timeout(&mut self, timeout: Duration) {
+    self.timeout = timeout;
+  }
+
+  pub fn timeout(&self) -> Duration {
+    self.timeout
+  }
+
+  /**
+   * GATT write used for commands, defaults to WriteMode::WithoutResponse. Confirmed writes are slower,
+   * so keep the default for frequent HUD polling and switch when lost writes have to be noticed right away
+   */
+  pub fn set_write_mode(&mut self, mode: WriteMode) {
+    self.write_mode = mode;
+  }
+
+  pub fn write_mode(&self) -> WriteMode {
+    self.write_mode
+  }
+
+  /**
+   * Energy used per kilometer, remaining range is computed from it when firmware has no estimate.
+   * Heavier rider or hilly route needs more than DEFAULT_CONSUMPTION_WH_PER_KM
+   */
+  pub fn set_consumption(&mut self, wh_per_km: f32) {
+    self.consumption_wh_per_km = wh_per_km;
+  }
+
+  pub fn consumption(&self) -> f32 {
+    self.consumption_wh_per_km
+  }
+
+  /**
+   * Model session talks to, values computed on our side (e.g. range from pack energy) depend on it.
+   * Scooter doesn't report it, so it has to be set by the app. Defaults to Model::M365
+   */
+  pub fn set_model(&mut self, model: Model) {
+    self.model = model;
+  }
+
+  pub fn model(&self) -> Model {
+    self.model
+  }
+
+  /**
+   * Result of `read_device_info`, None until it succeeds
+   */
+  pub fn device_info(&self) -> Option<&ScooterInfo> {
+    self.device_info.as_ref()
+  }
+
+  pub(super) fn set_device_info(&mut self, info: ScooterInfo) {
+    self.device_info = Some(info);
+  }
+
+  /**
+   * Forget cached static reads and device info, next reads go to scooter again
+   */
+  pub fn clear_cache(&mut self) {
+    self.cache.clear();
+    self.cached_response = None;
+    self.device_info = None;
+  }
+
+  pub fn set_reconnect_policy(&mut self, policy: ReconnectPolicy) {
+    self.reconnect_policy = policy;
+  }
+
+  pub fn reconnect_policy(&self) -> &ReconnectPolicy {
+    &self.reconnect_policy
+  }
+
+  /**
+   * Reconnect on its own when link drops, command which was in flight is sent again. Off by default
+   */
+  pub fn set_auto_reconnect(&mut self, enabled: bool) {
+    self.auto_reconnect = enabled;
+  }
+
+  /**
+   * Bring dropped link back, waiting between attempts as reconnect policy says. Keys from login stay valid
+   * for new link, so there is no new handshake. Session closed by power off or reboot is not reopened
+   */
+  pub async fn reconnect(&mut self) -> Result<(), SessionError> {
+    if self.closed {
+      return Err(SessionError::Disconnected)
+    }
+
+    for (attempt, delay) in self.reconnect_policy.schedule().into_iter().enumerate() {
+      match self.transport.reconnect().await {
+        Ok(()) => {
+          // answers in flight were lost with the old link
+          self.orphaned.clear();
+          tracing::debug!("Reconnected after {} attempts", attempt + 1);
+          return Ok(())
+        },
+        Err(err) => {
+          tracing::debug!("Reconnect attempt {} failed: {}, next in {:?}", attempt + 1, err, delay);
+          tokio::time::sleep(delay).await;
+        }
+      }
+    }
+
+    Err(SessionError::Disconnected)
+  }
+
+  /**
+   * Log every frame sent and received from now on, replaces tap attached before
+   */
+  #[cfg(feature = "capture")]
+  pub fn attach_tap(&mut self, tap: FrameTap) {
+    self.tap = Some(tap);
+  }
+
+  #[cfg(feature = "capture")]
+  pub fn detach_tap(&mut self) -> Option<FrameTap> {
+    self.tap.take()
+  }
 
-    Ok(Self { protocol, keys })
+  /**
+   * Hand frames to attached tap, both do nothing without `capture` feature
+   */
+  #[allow(unused_variables)]
+  fn capture_outbound(&mut self, frame: &[u8]) {
+    #[cfg(feature = "capture")]
+    if let Some(tap) = &mut self.tap {
+      tap.record(FrameDirection::Outbound, frame);
+    }
+  }
+
+  #[allow(unused_variables)]
+  fn capture_inbound(&mut self, frame: &[u8]) {
+    #[cfg(feature = "capture")]
+    if let Some(tap) = &mut self.tap {
+      tap.record(FrameDirection::Inbound, frame);
+    }
   }
 
   /**
-   * Serialize, encrypt and send command to scooter
+   * Serialize, encrypt and send command to scooter. Writes also wait for scooter's ACK and fail with
+   * CommandRejected when it answers with NAK, reads leave response for `read_frame`
    */
-  pub async fn send(&mut self, cmd: &ScooterCommand) -> Result<bool> {
-    let bytes = encrypt_uart(&self.keys.app, &cmd.as_bytes(), 0, None); // encrypt bytes
-    self.protocol.write_nb_parcel(&Registers::TX, &bytes).await?;
+  pub async fn send(&mut self, cmd: &ScooterCommand) -> Result<bool, SessionError> {
+    if let Some(response) = self.cached(cmd) {
+      tracing::debug!("Answering {} from cache", cmd.attribute);
+      self.cached_response = Some(response);
+      return Ok(true)
+    }
+
+    self.write_command(cmd).await?;
+
+    if awaits_ack(cmd) {
+      self.read_ack(cmd).await?;
+    }
+    // write scooter answered with NAK changed nothing, so it never reaches the audit trail
+    self.mutations.record(cmd);
+
     Ok(true)
   }
 
   /**
-   * Wait for response from scooter. You can specify number of frames that you expect to receive
+   * Send command to any address and return response data without header and random trailer, for registers
+   * which have no typed method yet. Same authorization check applies as for typed commands, so writes to addresses
+   * without variant (`Attribute::Unknown`) and other registers rider can't change need SecurityClass::Admin.
+   * Writes return the ACK status byte, NAK is returned as well instead of failing
+   */
+  pub async fn send_raw(&mut self, direction: Direction, read_write: ReadWrite, address: u8, payload: &[u8]) -> Result<Vec<u8>, SessionError> {
+    let cmd = ScooterCommand {
+      direction,
+      read_write,
+      attribute: Attribute::from_address(address),
+      payload: payload.to_vec()
+    };
+
+    if cmd.read_write == ReadWrite::Write && !awaits_ack(&cmd) {
+      self.send(&cmd).await?;
+      return Ok(Vec::new())
+    }
+
+    if let Some(response) = self.cached(&cmd) {
+      self.cached_response = Some(response);
+    } else {
+      self.write_command(&cmd).await?;
+    }
+
+    let mut response = self.read_frame().await?;
+    response.pop_head()?;
+
+    let mut data = response.into_bytes();
+    data.truncate(data.len().saturating_sub(RESPONSE_RANDOM_LEN));
+    if data.first() == Some(&WRITE_ACK) {
+      self.mutations.record(&cmd);
+    }
+    Ok(data)
+  }
+
+  async fn write_command(&mut self, cmd: &ScooterCommand) -> Result<(), SessionError> {
+    if self.closed {
+      return Err(SessionError::Disconnected)
+    }
+
+    if !self.authorization.allows(cmd) {
+      // writes to registers rider can't change are Admin class, so this is also what keeps them from Restricted sessions
+      return Err(SessionError::Unauthorized { required: cmd.security_class(), granted: self.authorization })
+    }
+
+    let bytes = encrypt_uart(&self.keys.app, &cmd.try_as_bytes()?, 0, None); // encrypt bytes
+    match self.transport.write_frame_as(&bytes, self.write_mode).await {
+      Err(SessionError::Disconnected) if self.auto_reconnect => {
+        self.reconnect().await?;
+        self.transport.write_frame_as(&bytes, self.write_mode).await?;
+      },
+      result => result?
+    }
+    self.capture_outbound(&bytes);
+
+    if self.cached_response.take().is_some() {
+      tracing::debug!("Cached response was never read, discarding it");
+    }
+
+    if let Some(previous) = self.pending_read.take() {
+      tracing::debug!("Response to {} was never read, it will be discarded when it arrives", previous.attribute);
+      self.orphaned.push(previous.attribute);
+    }
+
+    self.pending_read = match cmd.read_write {
+      ReadWrite::Read => Some(cmd.clone()),
+      ReadWrite::Write if awaits_ack(cmd) => Some(cmd.clone()),
+      ReadWrite::Write => None
+    };
+
+    Ok(())
+  }
+
+  /**
+   * Response cached for the same read, request payload (length) has to match too
+   */
+  fn cached(&self, cmd: &ScooterCommand) -> Option<Vec<u8>> {
+    if self.closed || cmd.read_write != ReadWrite::Read {
+      return None
+    }
+
+    self.cache.get(&cmd.attribute)
+      .filter(|cached| cached.request == cmd.payload)
+      .map(|cached| cached.response.clone())
+  }
+
+  /**
+   * Write is answered with the same attribute and one status byte, 0x01 is ACK and anything else NAK
+   */
+  async fn read_ack(&mut self, cmd: &ScooterCommand) -> Result<(), SessionError> {
+    let mut payload = self.read_frame().await?;
+    payload.pop_head()?;
+
+    match payload.pad_byte()? {
+      WRITE_ACK => Ok(()),
+      status => {
+        tracing::warn!("Scooter rejected write to {}: 0x{:02X}", cmd.attribute, status);
+        Err(SessionError::CommandRejected { attribute: cmd.attribute.clone(), status })
+      }
+    }
+  }
+
+  /**
+   * Forget counter expected from scooter, next frame sets new baseline without counting as resync
+   */
+  pub fn resync_counter(&mut self) {
+    tracing::debug!("Counter resync requested, was expecting {:?}", self.rx_counter);
+    self.rx_counter = None;
+  }
+
+  /**
+   * Counter expected in next frame from scooter
+   */
+  pub fn rx_counter(&self) -> Option<u16> {
+    self.rx_counter
+  }
+
+  /**
+   * How many times scooter's counter jumped and session followed it
+   */
+  pub fn counter_resyncs(&self) -> u32 {
+    self.counter_resyncs
+  }
+
+  /**
+   * Frames are decrypted with the counter from their own header, so gap (e.g. frame lost when link dropped)
+   * doesn't break anything. Session follows the scooter instead of waiting for counter which won't come
    */
-  pub async fn read(&mut self, frames: u8) -> Result<Payload> {
-    let data = self.protocol.read_nb_parcel(frames).await?;
-    let response = decrypt_uart(&self.keys.dev, &data)?;
-    let payload = Payload::from(response);
-    Ok(payload)
+  fn track_counter(&mut self, frame: &[u8]) {
+    let Some(received) = uart_frame_counter(frame) else {
+      return
+    };
+
+    if let Some(expected) = self.rx_counter {
+      if received != expected {
+        tracing::info!("Counter drift, expected {} but scooter sent {}, resyncing", expected, received);
+        self.counter_resyncs += 1;
+      }
+    }
+
+    self.rx_counter = Some(received.wrapping_add(1));
+  }
+
+  /**
+   * Session is closed after scooter was told to power off or reboot. BLE link drops with it, so every
+   * following command fails and new session has to be created after reconnect
+   */
+  pub fn is_closed(&self) -> bool {
+    self.closed
+  }
+
+  pub(super) fn close(&mut self) {
+    self.closed = true;
+  }
+
+  /**
+   * Writes sent during this session
+   */
+  pub fn mutation_log(&self) -> &MutationLog {
+    &self.mutations
+  }
+
+  pub fn mutation_log_mut(&mut self) -> &mut MutationLog {
+    &mut self.mutations
+  }
+
+  /**
+   * Wait for response from scooter. Frame count is kept for compatibility, transport always returns
+   * one complete frame no matter into how many notifications it was split
+   */
+  pub async fn read(&mut self, _frames: u8) -> Result<Payload, SessionError> {
+    self.read_frame().await
+  }
+
+  /**
+   * Wait for next complete response frame, no matter into how many notifications scooter splits it
+   */
+  pub async fn read_frame(&mut self) -> Result<Payload, SessionError> {
+    if let Some(response) = self.cached_response.take() {
+      return Ok(Payload::from(response))
+    }
+
+    // stays pending until response is read, this future may be dropped while waiting
+    let request = self.pending_read.clone();
+    let response = loop {
+      let frame = match self.read_transport_frame().await {
+        Err(SessionError::Disconnected) if self.auto_reconnect => {
+          // response was lost with the link, ask again
+          self.reconnect().await?;
+          if let Some(request) = &request {
+            self.pending_read = None;
+            self.write_command(request).await?;
+          }
+          self.read_transport_frame().await?
+        },
+        result => result?
+      };
+      verify_frame_checksum(&frame)?;
+
+      let response = decrypt_uart(&self.keys.dev, &frame).map_err(|err| match err {
+        MiCryptoError::AuthTagMismatch => SessionError::ChecksumMismatch,
+        other => SessionError::Other(other.into())
+      })?;
+      self.track_counter(&frame);
+
+      // decrypted response starts with direction, read/write and attribute
+      if let (Some(expected), Some(received)) = (request.as_ref().map(|request| &request.attribute), response.get(2)) {
+        if expected.address() != *received {
+          if let Some(index) = self.orphaned.iter().position(|orphan| orphan.address() == *received) {
+            tracing::debug!("Discarding late response to {}", self.orphaned.remove(index));
+            continue;
+          }
+
+          if !self.notification_handlers.is_empty() {
+            self.notify(&frame, &response)?;
+            continue;
+          }
+
+          let received = Attribute::from_address(*received);
+          return Err(SessionError::UnexpectedAttribute { expected: expected.clone(), received })
+        }
+      }
+
+      break response;
+    };
+
+    // scooter answers in order, so responses to other requests sent before this one can't come anymore. Answer to
+    // abandoned read of the same attribute may have been taken for this one, then the real answer is still coming
+    self.pending_read = None;
+    let answered = request.as_ref().map(|request| request.attribute.address());
+    self.orphaned.retain(|orphan| Some(orphan.address()) == answered);
+
+    if let Some(request) = request {
+      if request.read_write == ReadWrite::Read && request.attribute.is_static() {
+        self.cache.insert(request.attribute, CachedRead { request: request.payload, response: response.clone() });
+      }
+    }
+
+    Ok(Payload::from(response))
+  }
+
+  /**
+   * Call `callback` with every frame scooter sends on its own, e.g. periodic battery updates. Such frame
+   * is told apart from the awaited response by its attribute, callbacks get it and session keeps waiting
+   * for the response. When no callback is registered, frame for other attribute fails the read with
+   * UnexpectedAttribute, since it most likely is late answer to earlier request
+   */
+  pub fn on_notification<F: FnMut(&ScooterResponse) + Send + 'static>(&mut self, callback: F) {
+    self.notification_handlers.push(Box::new(callback));
+  }
+
+  /**
+   * Decrypted frame lacks length byte, it is taken from encrypted frame header
+   */
+  fn notify(&mut self, frame: &[u8], decrypted: &[u8]) -> Result<(), SessionError> {
+    let mut bytes = vec![frame[2]];
+    bytes.extend_from_slice(decrypted);
+    let notification = ScooterResponse::parse(&bytes)?;
+    tracing::debug!("Unsolicited frame for {}", notification.attribute);
+
+    for handler in &mut self.notification_handlers {
+      handler(&notification);
+    }
+
+    Ok(())
+  }
+
+  async fn read_transport_frame(&mut self) -> Result<Vec<u8>, SessionError> {
+    let frame = tokio::time::timeout(self.timeout, self.transport.read_frame()).await
+      .map_err(|_| SessionError::Timeout)??;
+    self.capture_inbound(&frame);
+
+    Ok(frame)
+  }
+}
+
+struct CachedRead {
+  request: Vec<u8>,
+  response: Vec<u8>
+}
+
+/**
+ * Power off and reboot drop the link right away, so there is nobody left to answer them
+ */
+fn awaits_ack(cmd: &ScooterCommand) -> bool {
+  cmd.read_write == ReadWrite::Write && !matches!(cmd.attribute, Attribute::PowerOff | Attribute::Reboot)
+}
+
+/**
+ * Encrypted frame ends with checksum of everything between header and checksum itself
+ */
+fn verify_frame_checksum(frame: &[u8]) -> Result<(), SessionError> {
+  if frame.len() < 4 {
+    return Err(SessionError::ChecksumMismatch)
+  }
+
+  let (body, trailer) = frame[2..].split_at(frame.len() - 4);
+  if crc16(body) != trailer {
+    return Err(SessionError::ChecksumMismatch)
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::transport::MockTransport;
+  use crate::mi_crypto::EncryptionKey;
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  /**
+   * BMS answering 87% to battery percent read
+   */
+  fn battery_percent_response(keys: &LoginKeychain) -> Vec<u8> {
+    encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+  }
+
+  #[tokio::test]
+  async fn test_timeout_when_scooter_is_silent() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    session.set_timeout(Duration::from_millis(50));
+
+    let started = std::time::Instant::now();
+    assert!(matches!(session.read_battery_percent().await, Err(SessionError::Timeout)));
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(session.transport().written.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_response_within_timeout() {
+    let keys = test_keys();
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![battery_percent_response(&keys)]), &keys);
+    session.set_timeout(Duration::from_millis(50));
+
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+  }
+
+  #[test]
+  fn test_default_timeout() {
+    let session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    assert_eq!(session.timeout(), DEFAULT_RESPONSE_TIMEOUT);
+  }
+
+  #[tokio::test]
+  async fn test_corrupted_checksum() {
+    let keys = test_keys();
+    let mut response = battery_percent_response(&keys);
+    let last = response.len() - 1;
+    response[last] ^= 0xFF;
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![response]), &keys);
+
+    assert!(matches!(session.read_battery_percent().await, Err(SessionError::ChecksumMismatch)));
+  }
+
+  #[tokio::test]
+  async fn test_tampered_ciphertext() {
+    let keys = test_keys();
+    let mut response = battery_percent_response(&keys);
+    response[6] ^= 0x01;
+    // keep checksum valid, so only CCM tag catches it
+    let len = response.len();
+    let crc = crc16(&response[2..len - 2]);
+    response[len - 2..].copy_from_slice(&crc);
+
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![response]), &keys);
+
+    assert!(matches!(session.read_battery_percent().await, Err(SessionError::ChecksumMismatch)));
+  }
+
+  #[tokio::test]
+  async fn test_response_for_other_attribute() {
+    let keys = test_keys();
+    let mut session = MiSession::with_transport(MockTransport::with_responses(vec![battery_percent_response(&keys)]), &keys);
+
+    let result = session.read_battery_voltage().await;
+
+    match result {
+      Err(SessionError::UnexpectedAttribute { expected, received }) => {
+        assert_eq!(expected, Attribute::BatteryVoltage);
+        assert_eq!(received, Attribute::BatteryPercent);
+      },
+      other => panic!("Expected UnexpectedAttribute, got {:?}", other)
+    }
+  }
+
+  #[tokio::test]
+  async fn test_write_mode_is_passed_to_transport() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    let read = ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02);
+    assert_eq!(session.write_mode(), WriteMode::WithoutResponse);
+
+    session.send(&read).await.unwrap();
+    session.set_write_mode(WriteMode::WithResponse);
+    session.send(&read).await.unwrap();
+    session.set_write_mode(WriteMode::WithoutResponse);
+    session.send(&read).await.unwrap();
+
+    assert_eq!(session.transport().write_modes, vec![
+      WriteMode::WithoutResponse,
+      WriteMode::WithResponse,
+      WriteMode::WithoutResponse
+    ]);
+  }
+
+  #[tokio::test]
+  async fn test_unsolicited_frame_goes_to_notification() {
+    let keys = test_keys();
+    let voltage = encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x34, 0xA0, 0x0F], 1, Some([0x6a, 0xf8, 0x94, 0x11]));
+    let transport = MockTransport::with_responses(vec![battery_percent_response(&keys), voltage]);
+    let mut session = MiSession::with_transport(transport, &keys);
+
+    let received = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = received.clone();
+    session.on_notification(move |response| sink.lock().unwrap().push(response.clone()));
+
+    assert_eq!(session.read_battery_voltage().await.unwrap(), 40.0);
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].direction, Direction::BatteryToMaster);
+    assert_eq!(received[0].attribute, Attribute::BatteryPercent);
+    assert_eq!(received[0].payload, vec![0x57, 0x00]);
+  }
+
+  #[tokio::test]
+  async fn test_dropped_read_response_is_discarded() {
+    let keys = test_keys();
+    let mut session = MiSession::with_transport(MockTransport::default(), &keys);
+
+    // caller gives up before scooter answers, dropping the read future
+    let abandoned = tokio::time::timeout(Duration::from_millis(10), session.read_battery_voltage()).await;
+    assert!(abandoned.is_err());
+
+    let voltage = encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x34, 0xA0, 0x0F], 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+    let percent = encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], 1, Some([0x6a, 0xf8, 0x94, 0x11]));
+    session.transport_mut().responses.extend([Ok(voltage), Ok(percent)]);
+
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+    assert_eq!(session.transport().written.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_transport_error_is_passed_through() {
+    let mut transport = MockTransport::default();
+    transport.push_error(SessionError::Transport(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "link lost")));
+    let mut session = MiSession::with_transport(transport, &test_keys());
+
+    match session.read_battery_percent().await {
+      Err(SessionError::Transport(err)) => assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe),
+      other => panic!("Expected Transport, got {:?}", other)
+    }
+  }
+
+  #[tokio::test]
+  async fn test_disconnected_after_power_off() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    session.set_authorization_level(SecurityClass::Restricted);
+    session.power_off().await.unwrap();
+
+    assert!(matches!(session.beep().await, Err(SessionError::Disconnected)));
+  }
+
+  #[tokio::test]
+  async fn test_unauthorized() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+
+    match session.lock().await {
+      Err(SessionError::Unauthorized { required, granted }) => {
+        assert_eq!(required, SecurityClass::Restricted);
+        assert_eq!(granted, SecurityClass::Public);
+      },
+      other => panic!("Expected Unauthorized, got {:?}", other)
+    }
+  }
+
+  #[tokio::test]
+  async fn test_admin_can_write_register_rider_can_not() {
+    let keys = test_keys();
+    let reset_mileage = ScooterCommand::builder().attribute(Attribute::TotalMileage).write_u16(0x0000).build().unwrap();
+
+    let mut restricted = MiSession::with_transport(MockTransport::default(), &keys);
+    restricted.set_authorization_level(SecurityClass::Restricted);
+    match restricted.send(&reset_mileage).await {
+      Err(SessionError::Unauthorized { required, granted }) => {
+        assert_eq!(required, SecurityClass::Admin);
+        assert_eq!(granted, SecurityClass::Restricted);
+      },
+      other => panic!("Expected Unauthorized, got {:?}", other)
+    }
+    assert!(restricted.transport().written.is_empty());
+
+    let transport = MockTransport::with_responses(vec![MockTransport::write_reply(&keys, Attribute::TotalMileage, 0x01)]);
+    let mut admin = MiSession::with_transport(transport, &keys);
+    admin.set_authorization_level(SecurityClass::Admin);
+    admin.send(&reset_mileage).await.unwrap();
+    assert_eq!(admin.transport().written.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_write_waits_for_ack() {
+    let keys = test_keys();
+    let transport = MockTransport::with_responses(vec![MockTransport::write_reply(&keys, Attribute::Lock, 0x01)]);
+    let mut session = MiSession::with_transport(transport, &keys);
+    session.set_authorization_level(SecurityClass::Restricted);
+
+    session.lock().await.unwrap();
+    assert!(session.transport().responses.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_auto_reply_answers_reads_and_writes() {
+    let keys = test_keys();
+    let mut transport = MockTransport::default();
+    transport.auto_reply_with_simulated_responses(&keys);
+    let mut session = MiSession::with_transport(transport, &keys);
+    session.set_authorization_level(SecurityClass::Restricted);
+
+    session.lock().await.unwrap();
+    assert_eq!(session.read_battery_percent().await.unwrap(), 0);
+
+    assert_eq!(session.transport().written.len(), 2);
+    assert!(session.transport().responses.is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_write_rejected_with_nak() {
+    let keys = test_keys();
+    let transport = MockTransport::with_responses(vec![MockTransport::write_reply(&keys, Attribute::Lock, 0x00)]);
+    let mut session = MiSession::with_transport(transport, &keys);
+    session.set_authorization_level(SecurityClass::Restricted);
+
+    match session.lock().await {
+      Err(SessionError::CommandRejected { attribute, status }) => {
+        assert_eq!(attribute, Attribute::Lock);
+        assert_eq!(status, 0x00);
+      },
+      other => panic!("Expected CommandRejected, got {:?}", other)
+    }
+    assert!(session.mutation_log().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_mutation_log_records_acknowledged_writes_only() {
+    let keys = test_keys();
+    let responses = vec![
+      MockTransport::write_reply(&keys, Attribute::Lock, 0x01),
+      MockTransport::write_reply(&keys, Attribute::Unlock, 0x00),
+    ];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+    session.set_authorization_level(SecurityClass::Restricted);
+
+    session.lock().await.unwrap();
+    assert!(matches!(session.unlock().await, Err(SessionError::CommandRejected { .. })));
+
+    let logged: Vec<_> = session.mutation_log().entries().iter().map(|(_, cmd)| cmd.attribute.clone()).collect();
+    assert_eq!(logged, vec![Attribute::Lock]);
+  }
+
+  #[tokio::test]
+  async fn test_write_without_ack_times_out() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    session.set_timeout(Duration::from_millis(10));
+
+    assert!(matches!(session.beep().await, Err(SessionError::Timeout)));
+  }
+
+  #[tokio::test]
+  async fn test_converts_into_anyhow() {
+    async fn read(session: &mut MiSession<MockTransport>) -> Result<u8> {
+      Ok(session.read_battery_percent().await?)
+    }
+
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    session.set_timeout(Duration::from_millis(10));
+    let err = read(&mut session).await.unwrap_err();
+
+    assert!(matches!(err.downcast_ref::<SessionError>(), Some(SessionError::Timeout)));
+  }
+
+  fn fast_reconnect() -> ReconnectPolicy {
+    ReconnectPolicy { initial_delay: Duration::from_millis(1), max_delay: Duration::from_millis(4), multiplier: 2, max_attempts: 3 }
+  }
+
+  #[tokio::test]
+  async fn test_auto_reconnect_recovers_read() {
+    let keys = test_keys();
+    let mut transport = MockTransport::default();
+    transport.push_error(SessionError::Disconnected);
+    transport.responses.push_back(Ok(battery_percent_response(&keys)));
+    transport.failing_reconnects = 1;
+
+    let mut session = MiSession::with_transport(transport, &keys);
+    session.set_reconnect_policy(fast_reconnect());
+    session.set_auto_reconnect(true);
+
+    // same keychain decrypts response on new link
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+    assert_eq!(session.transport().reconnects, 2);
+    // read was sent again after reconnect
+    assert_eq!(session.transport().written.len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_disconnect_without_auto_reconnect() {
+    let keys = test_keys();
+    let mut transport = MockTransport::default();
+    transport.push_error(SessionError::Disconnected);
+
+    let mut session = MiSession::with_transport(transport, &keys);
+
+    assert!(matches!(session.read_battery_percent().await, Err(SessionError::Disconnected)));
+    assert_eq!(session.transport().reconnects, 0);
+  }
+
+  #[tokio::test]
+  async fn test_reconnect_gives_up() {
+    let mut transport = MockTransport::default();
+    transport.failing_reconnects = usize::MAX;
+
+    let mut session = MiSession::with_transport(transport, &test_keys());
+    session.set_reconnect_policy(fast_reconnect());
+
+    assert!(matches!(session.reconnect().await, Err(SessionError::Disconnected)));
+    assert_eq!(session.transport().reconnects, 3);
+  }
+
+  #[tokio::test]
+  async fn test_closed_session_does_not_reconnect() {
+    let mut session = MiSession::with_transport(MockTransport::default(), &test_keys());
+    session.close();
+
+    assert!(matches!(session.reconnect().await, Err(SessionError::Disconnected)));
+    assert_eq!(session.transport().reconnects, 0);
+  }
+
+  fn battery_percent_response_with_counter(keys: &LoginKeychain, counter: u16) -> Vec<u8> {
+    encrypt_uart(&keys.dev, &[0x04, 0x25, 0x01, 0x32, 0x57, 0x00], counter as u32, Some([0x6a, 0xf8, 0x94, 0x11]))
+  }
+
+  #[tokio::test]
+  async fn test_counter_gap_resync() {
+    let keys = test_keys();
+    let responses = [7, 8, 10, 11].iter()
+      .map(|counter| battery_percent_response_with_counter(&keys, *counter))
+      .collect();
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    for _ in 0..2 {
+      assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+    }
+    assert_eq!(session.rx_counter(), Some(9));
+    assert_eq!(session.counter_resyncs(), 0);
+
+    // frame 9 never arrived, session follows scooter and keeps decoding
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+    assert_eq!(session.rx_counter(), Some(11));
+    assert_eq!(session.counter_resyncs(), 1);
+
+    assert_eq!(session.read_battery_percent().await.unwrap(), 87);
+    assert_eq!(session.counter_resyncs(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_manual_resync() {
+    let keys = test_keys();
+    let responses = vec![battery_percent_response_with_counter(&keys, 1), battery_percent_response_with_counter(&keys, 500)];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    session.read_battery_percent().await.unwrap();
+    session.resync_counter();
+    assert_eq!(session.rx_counter(), None);
+
+    session.read_battery_percent().await.unwrap();
+    assert_eq!(session.rx_counter(), Some(501));
+    assert_eq!(session.counter_resyncs(), 0);
+  }
+
+  #[test]
+  fn test_frame_counter() {
+    let keys = test_keys();
+    assert_eq!(uart_frame_counter(&battery_percent_response_with_counter(&keys, 0x0102)), Some(0x0102));
+    assert_eq!(uart_frame_counter(&[0x55, 0xAB, 0x01]), None);
+  }
+
+  #[test]
+  fn test_short_frame_fails_checksum() {
+    assert!(matches!(verify_frame_checksum(&[0x55, 0xAB, 0x01]), Err(SessionError::ChecksumMismatch)));
+  }
+
+  /**
+   * Decrypted command without the 4 random bytes, so frames sent at different times compare equal
+   */
+  fn sent_command(keys: &LoginKeychain, frame: &[u8]) -> Vec<u8> {
+    let plain = decrypt_uart(&keys.app, frame).unwrap();
+    plain[..plain.len() - 4].to_vec()
+  }
+
+  #[tokio::test]
+  async fn test_send_raw_matches_typed_command() {
+    let keys = test_keys();
+    let responses = vec![battery_percent_response(&keys), battery_percent_response(&keys)];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    session.read_battery_percent().await.unwrap();
+    let data = session.send_raw(Direction::MasterToBattery, ReadWrite::Read, 0x32, &[0x02]).await.unwrap();
+
+    assert_eq!(data, vec![0x57, 0x00]);
+    let written = &session.transport().written;
+    assert_eq!(sent_command(&keys, &written[0]), sent_command(&keys, &written[1]));
+  }
+
+  #[tokio::test]
+  async fn test_send_raw_returns_nak_status() {
+    let keys = test_keys();
+    let responses = vec![MockTransport::write_reply(&keys, Attribute::Cruise, 0x00)];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    let data = session.send_raw(Direction::MasterToMotor, ReadWrite::Write, 0x7C, &[0x01, 0x00]).await.unwrap();
+
+    assert_eq!(data, vec![0x00]);
+    assert!(session.mutation_log().is_empty());
+  }
+
+  #[tokio::test]
+  async fn test_send_raw_writes_unknown_address_with_admin() {
+    let keys = test_keys();
+    let responses = vec![MockTransport::write_reply(&keys, Attribute::Unknown(0xF2), 0x01)];
+    let mut session = MiSession::with_transport(MockTransport::with_responses(responses), &keys);
+
+    session.set_authorization_level(SecurityClass::Restricted);
+    assert!(matches!(
+      session.send_raw(Direction::MasterToMotor, ReadWrite::Write, 0xF2, &[0x01, 0x00]).await,
+      Err(SessionError::Unauthorized { required: SecurityClass::Admin, .. })
+    ));
+
+    session.set_authorization_level(SecurityClass::Admin);
+    let data = session.send_raw(Direction::MasterToMotor, ReadWrite::Write, 0xF2, &[0x01, 0x00]).await.unwrap();
+
+    assert_eq!(data, vec![0x01]);
+    let written = &session.transport().written;
+    assert_eq!(written.len(), 1);
+    assert_eq!(sent_command(&keys, &written[0]), vec![0x20, 0x03, 0xF2, 0x01, 0x00]);
   }
 }
@@ -1,5 +1,11 @@
 mod mi_session;
+mod transport;
+mod shared;
+mod manager;
+mod establish;
+mod reconnect;
 mod commands;
+mod frame_assembler;
 mod info;
 mod travel;
 mod battery;
@@ -7,9 +13,55 @@ mod payload;
 mod settings;
 mod lock;
 mod light;
+mod cruise;
+mod kers;
+mod ride_mode;
+mod version;
+mod telemetry;
+mod beep;
+mod power;
+mod temperature;
+mod range;
+mod model;
+mod keepalive;
+mod fault;
+mod alarm;
+mod current;
+mod status;
+#[cfg(feature = "capture")]
+mod capture;
+#[cfg(feature = "capture")]
+mod replay;
 
-pub use mi_session::MiSession;
+pub use mi_session::{MiSession, SessionError, DEFAULT_RESPONSE_TIMEOUT};
+pub use transport::{Transport, BleTransport, WriteMode};
+#[cfg(test)]
+pub(crate) use transport::MockTransport;
+pub use shared::SessionHandle;
+pub use manager::SessionManager;
+pub use reconnect::ReconnectPolicy;
 pub use payload::Payload;
-pub use info::{GeneralInfo, MotorInfo};
+pub use crate::frame::ScooterResponse;
+pub use frame_assembler::{FrameAssembler, Assembled, split_frame, BLE_MTU};
+pub use commands::{ScooterCommand, ScooterCommandBuilder, Direction, ReadWrite, Attribute, Priority, PrioritizedCommand, SecurityClass, MutationLog};
+pub use info::{GeneralInfo, MotorInfo, ScooterInfo};
+pub use travel::TripStatistics;
 pub use settings::{TailLight};
+pub use light::TailLightMode;
 pub use battery::{BatteryInfo};
+pub use kers::KersLevel;
+pub use ride_mode::RideMode;
+pub use version::format_version;
+pub use telemetry::{Telemetry, TelemetryReadout, Units};
+pub use beep::BEEP_PATTERN_GAP;
+pub use temperature::controller_temp_celsius;
+pub use range::{estimate_range_km, PACK_ENERGY_WH, DEFAULT_CONSUMPTION_WH_PER_KM};
+pub use model::Model;
+pub use keepalive::DEFAULT_KEEPALIVE_INTERVAL;
+pub use fault::ScooterFault;
+pub use alarm::AlarmFlags;
+pub use status::StatusBlock;
+#[cfg(feature = "capture")]
+pub use capture::{FrameTap, FrameDirection};
+#[cfg(feature = "capture")]
+pub use replay::ReplayTransport;
@@ -0,0 +1,44 @@
+//! Scooter model
+//!
+//! M365, M365 Pro and Mi 1S speak the same protocol: register addresses and units are
+//! identical, battery voltage is reported in 10 mV on all of them. What differs is the
+//! hardware, mainly the battery pack, so the model matters for values computed on our
+//! side such as remaining range.
+//!
+//! ## Protocol Reference
+//! - Model is not reported by any known register, neither serial number (0x10) nor
+//!   firmware versions tell the models apart reliably
+//! - It has to be set with `MiSession::set_model`, sessions assume M365 until then
+
+/// Xiaomi scooter which session talks to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Model {
+    /// Original M365, 36 V 7.8 Ah pack
+    #[default]
+    M365,
+    /// M365 Pro, 36 V 12.8 Ah pack
+    M365Pro,
+    /// Mi 1S, 36 V 7.65 Ah pack
+    Mi1S,
+}
+
+impl Model {
+    /// Usable energy of stock pack in Wh
+    pub fn pack_energy_wh(&self) -> f32 {
+        match self {
+            Model::M365 => 280.8,
+            Model::M365Pro => 460.8,
+            Model::Mi1S => 275.4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_m365_pack_matches_range_constant() {
+        assert_eq!(Model::M365.pack_energy_wh(), super::super::range::PACK_ENERGY_WH);
+    }
+}
@@ -122,6 +122,15 @@ impl Payload {
     let string = String::from_utf8_lossy(&string_bytes);
     Ok(string.into_owned())
   }
+
+  /**
+   * Bytes not read yet, in the order scooter sent them
+   */
+  pub fn into_bytes(self) -> Vec<u8> {
+    let mut bytes = self.bytes;
+    bytes.reverse();
+    bytes
+  }
 }
 
 impl From<Vec<u8>> for Payload {
@@ -0,0 +1,136 @@
+//! Power off and reboot for M365 scooter
+//!
+//! Remote power cycle for scooter whose firmware has hung. Scooter drops the BLE link right
+//! after receiving either command, so no response is awaited and session is closed afterwards.
+//! Reconnect and log in again to get new session.
+//!
+//! ## Protocol Reference
+//! - Power Off: Write 0x0001 to address 0x79
+//! - Reboot: Write 0x0001 to address 0x78
+//! - Direction: Master to Motor Controller (0x20)
+//! - Command Type: Write (0x03)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+
+use anyhow::Result;
+
+fn power_command(attribute: Attribute) -> ScooterCommand {
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute,
+        payload: vec![0x01, 0x00] // 0x0001 little-endian
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Turn the scooter off
+    ///
+    /// Returns as soon as the command is written, BLE link drops and session is closed
+    /// (see `is_closed`). Requires session authorized for `SecurityClass::Restricted`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::SecurityClass;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_authorization_level(SecurityClass::Restricted);
+    /// session.power_off().await?;
+    /// assert!(session.is_closed());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn power_off(&mut self) -> Result<(), SessionError> {
+        tracing::debug!("Powering scooter off");
+
+        self.send(&power_command(Attribute::PowerOff)).await?;
+        self.close();
+
+        Ok(())
+    }
+
+    /// Restart the scooter
+    ///
+    /// Same as `power_off`, scooter comes back on its own but current session is closed,
+    /// reconnect and log in once it is advertising again. Requires session authorized for
+    /// `SecurityClass::Restricted`.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::SecurityClass;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_authorization_level(SecurityClass::Restricted);
+    /// session.reboot().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn reboot(&mut self) -> Result<(), SessionError> {
+        tracing::debug!("Rebooting scooter");
+
+        self.send(&power_command(Attribute::Reboot)).await?;
+        self.close();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::commands::SecurityClass;
+    use super::super::transport::MockTransport;
+    use crate::mi_crypto::{EncryptionKey, LoginKeychain};
+
+    fn test_session() -> MiSession<MockTransport> {
+        let key = EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        let keys = LoginKeychain { dev: key.clone(), app: key };
+        MiSession::with_transport(MockTransport::default(), &keys)
+    }
+
+    #[test]
+    fn test_power_off_command_bytes() {
+        let cmd = power_command(Attribute::PowerOff);
+
+        // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x79) + Payload(0x01, 0x00)
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x79, 0x01, 0x00]);
+        assert_eq!(cmd.checksum().unwrap(), [0x5E, 0xFF]);
+    }
+
+    #[test]
+    fn test_reboot_command_bytes() {
+        let cmd = power_command(Attribute::Reboot);
+
+        // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x78) + Payload(0x01, 0x00)
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x04, 0x20, 0x03, 0x78, 0x01, 0x00]);
+        assert_eq!(cmd.checksum().unwrap(), [0x5F, 0xFF]);
+    }
+
+    #[test]
+    fn test_power_commands_are_restricted() {
+        assert_eq!(power_command(Attribute::PowerOff).security_class(), SecurityClass::Restricted);
+        assert_eq!(power_command(Attribute::Reboot).security_class(), SecurityClass::Restricted);
+    }
+
+    #[tokio::test]
+    async fn test_power_off_closes_session() {
+        let mut session = test_session();
+        session.set_authorization_level(SecurityClass::Restricted);
+
+        session.power_off().await.unwrap();
+
+        assert!(session.is_closed());
+        assert_eq!(session.transport().written.len(), 1);
+        assert!(session.beep().await.is_err());
+        assert_eq!(session.transport().written.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reboot_requires_restricted() {
+        let mut session = test_session();
+
+        assert!(session.reboot().await.is_err());
+        assert!(!session.is_closed());
+    }
+}
@@ -0,0 +1,175 @@
+//! Remaining range for M365 scooter
+//!
+//! Firmware keeps its own estimate of distance left, which takes recent consumption into account.
+//! When it has none (reads 0 right after power on) or the read fails, range is computed from
+//! battery percent, pack energy of the model set with `MiSession::set_model` and consumption
+//! set with `MiSession::set_consumption`.
+//!
+//! ## Protocol Reference
+//! - Read 2 bytes from address 0x25, Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01)
+//! - Value: unsigned 16-bit, hundredths of km (0x04D2 = 12.34 km)
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+
+use anyhow::Result;
+
+/// Usable energy of stock M365 pack (36 V, 7.8 Ah)
+pub const PACK_ENERGY_WH: f32 = 280.8;
+
+/// Stock pack is rated for 30 km
+pub const DEFAULT_CONSUMPTION_WH_PER_KM: f32 = PACK_ENERGY_WH / 30.0;
+
+/// Distance in km which charge left in stock M365 pack is good for, 0 for nonsensical consumption
+pub fn estimate_range_km(battery_percent: u8, wh_per_km: f32) -> f32 {
+    range_for_pack(PACK_ENERGY_WH, battery_percent, wh_per_km)
+}
+
+fn range_for_pack(pack_energy_wh: f32, battery_percent: u8, wh_per_km: f32) -> f32 {
+    if !wh_per_km.is_finite() || wh_per_km <= 0.0 {
+        return 0.0;
+    }
+
+    let energy_left = pack_energy_wh * battery_percent.min(100) as f32 / 100.0;
+    energy_left / wh_per_km
+}
+
+fn parse_estimated_range(payload: Payload) -> Result<f32> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(payload.pop_u16()? as f32 / 100.0)
+}
+
+impl<T: Transport> MiSession<T> {
+    /// How far the scooter can go in km, firmware estimate with fallback to computation from battery percent
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_consumption(11.0); // heavy rider
+    /// println!("{:.1} km left", session.read_remaining_range().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_remaining_range(&mut self) -> Result<f32, SessionError> {
+        tracing::debug!("Reading remaining range");
+
+        match self.read_estimated_range().await {
+            Ok(range) if range > 0.0 => return Ok(range),
+            Ok(_) => tracing::debug!("Firmware has no range estimate, computing from battery"),
+            Err(SessionError::Disconnected) => return Err(SessionError::Disconnected),
+            Err(err) => tracing::debug!("Could not read range estimate: {}, computing from battery", err),
+        }
+
+        let percent = self.read_battery_percent().await?;
+        let range = range_for_pack(self.model().pack_energy_wh(), percent, self.consumption());
+        tracing::debug!("remaining range: {}km from {}% at {} Wh/km", range, percent, self.consumption());
+
+        Ok(range)
+    }
+
+    async fn read_estimated_range(&mut self) -> Result<f32, SessionError> {
+        self.send(&ScooterCommand::read(Attribute::DistanceLeft.destination(), Attribute::DistanceLeft, 0x02)).await?;
+
+        let payload = self.read_frame().await?;
+        Ok(parse_estimated_range(payload)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transport::MockTransport;
+    use super::super::Model;
+    use crate::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
+
+    fn test_keys() -> LoginKeychain {
+        LoginKeychain {
+            dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+            app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] },
+        }
+    }
+
+    fn response(keys: &LoginKeychain, direction: u8, attribute: u8, value: u16) -> Vec<u8> {
+        let mut plain = vec![0x04, direction, 0x01, attribute];
+        plain.extend_from_slice(&value.to_le_bytes());
+        encrypt_uart(&keys.dev, &plain, 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+    }
+
+    fn assert_km(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.001, "{} km != {} km", actual, expected);
+    }
+
+    #[test]
+    fn test_estimate_range() {
+        assert_km(estimate_range_km(100, DEFAULT_CONSUMPTION_WH_PER_KM), 30.0);
+        assert_km(estimate_range_km(50, PACK_ENERGY_WH / 20.0), 10.0);
+        assert_km(estimate_range_km(0, DEFAULT_CONSUMPTION_WH_PER_KM), 0.0);
+        assert_km(estimate_range_km(250, DEFAULT_CONSUMPTION_WH_PER_KM), 30.0);
+    }
+
+    #[test]
+    fn test_estimate_range_invalid_consumption() {
+        assert_eq!(estimate_range_km(80, 0.0), 0.0);
+        assert_eq!(estimate_range_km(80, -5.0), 0.0);
+        assert_eq!(estimate_range_km(80, f32::NAN), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_firmware_estimate() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![response(&keys, 0x23, 0x25, 1234)]);
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        assert_eq!(session.read_remaining_range().await.unwrap(), 12.34);
+        assert_eq!(session.transport().written.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_when_firmware_reports_zero() {
+        let keys = test_keys();
+        let transport = MockTransport::with_responses(vec![
+            response(&keys, 0x23, 0x25, 0),
+            response(&keys, 0x25, 0x32, 50),
+        ]);
+        let mut session = MiSession::with_transport(transport, &keys);
+        session.set_consumption(PACK_ENERGY_WH / 40.0);
+
+        assert_km(session.read_remaining_range().await.unwrap(), 20.0);
+        assert_eq!(session.transport().written.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_uses_model_pack() {
+        let keys = test_keys();
+        let mut ranges = Vec::new();
+        for model in [Model::M365, Model::M365Pro] {
+            let transport = MockTransport::with_responses(vec![
+                response(&keys, 0x23, 0x25, 0),
+                response(&keys, 0x25, 0x32, 50),
+            ]);
+            let mut session = MiSession::with_transport(transport, &keys);
+            session.set_model(model);
+            session.set_consumption(10.0);
+            ranges.push(session.read_remaining_range().await.unwrap());
+        }
+
+        // half of 280.8 Wh and 460.8 Wh at 10 Wh/km
+        assert_km(ranges[0], 14.04);
+        assert_km(ranges[1], 23.04);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_when_estimate_read_fails() {
+        let keys = test_keys();
+        let mut transport = MockTransport::default();
+        transport.push_error(SessionError::Timeout);
+        transport.responses.push_back(Ok(response(&keys, 0x25, 0x32, 100)));
+        let mut session = MiSession::with_transport(transport, &keys);
+
+        assert_km(session.read_remaining_range().await.unwrap(), 30.0);
+    }
+}
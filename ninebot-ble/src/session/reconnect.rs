@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+/**
+ * How long to wait between attempts to bring dropped BLE link back. Delay starts at `initial_delay`
+ * and is multiplied after every failed attempt, but never grows over `max_delay`
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+  pub initial_delay: Duration,
+  pub max_delay: Duration,
+  pub multiplier: u32,
+  /**
+   * Give up and report Disconnected after this many failed attempts
+   */
+  pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+  fn default() -> Self {
+    Self {
+      initial_delay: Duration::from_millis(500),
+      max_delay: Duration::from_secs(8),
+      multiplier: 2,
+      max_attempts: 6
+    }
+  }
+}
+
+impl ReconnectPolicy {
+  /**
+   * Delay after failed attempt, first attempt is 0
+   */
+  pub fn delay(&self, attempt: u32) -> Duration {
+    let factor = self.multiplier.saturating_pow(attempt);
+    self.initial_delay.saturating_mul(factor).min(self.max_delay)
+  }
+
+  /**
+   * Delays after each of `max_attempts` attempts
+   */
+  pub fn schedule(&self) -> Vec<Duration> {
+    (0..self.max_attempts).map(|attempt| self.delay(attempt)).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_default_schedule() {
+    let schedule: Vec<u64> = ReconnectPolicy::default().schedule().iter().map(|delay| delay.as_millis() as u64).collect();
+    assert_eq!(schedule, vec![500, 1000, 2000, 4000, 8000, 8000]);
+  }
+
+  #[test]
+  fn test_delay_is_capped() {
+    let policy = ReconnectPolicy { max_delay: Duration::from_secs(3), ..Default::default() };
+
+    assert_eq!(policy.delay(2), Duration::from_secs(2));
+    assert_eq!(policy.delay(3), Duration::from_secs(3));
+    assert_eq!(policy.delay(u32::MAX), Duration::from_secs(3));
+  }
+
+  #[test]
+  fn test_constant_delay() {
+    let policy = ReconnectPolicy { multiplier: 1, max_attempts: 3, ..Default::default() };
+    assert_eq!(policy.schedule(), vec![Duration::from_millis(500); 3]);
+  }
+}
@@ -0,0 +1,170 @@
+use super::SessionError;
+use super::capture::{FrameDirection, parse_frame_line};
+use super::transport::Transport;
+
+use std::collections::VecDeque;
+use std::io::BufRead;
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+
+/**
+ * One frame sent to scooter with everything scooter sent back before next one
+ */
+struct Exchange {
+  outbound: Vec<u8>,
+  inbound: Vec<Vec<u8>>
+}
+
+/**
+ * Plays session captured by FrameTap back, so bug report can be reproduced without the scooter.
+ * Session has to use the keychain from the recording to decrypt replayed frames.
+ *
+ * Commands are encrypted with random bytes, so they can't be compared byte by byte. Written frame only has
+ * to have the same length as recorded one, anything else means session went different way than recording
+ */
+pub struct ReplayTransport {
+  exchanges: VecDeque<Exchange>,
+  frames: VecDeque<Vec<u8>>
+}
+
+impl ReplayTransport {
+  pub fn from_log(log: &str) -> Result<Self> {
+    Self::from_reader(log.as_bytes())
+  }
+
+  pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+    let mut exchanges : VecDeque<Exchange> = VecDeque::new();
+    let mut unsolicited : VecDeque<Vec<u8>> = VecDeque::new();
+
+    for (number, line) in reader.lines().enumerate() {
+      let line = line?;
+      if line.trim().is_empty() {
+        continue;
+      }
+
+      let (direction, frame) = parse_frame_line(&line)
+        .map_err(|err| anyhow!("Line {}: {}", number + 1, err))?;
+
+      match direction {
+        FrameDirection::Outbound => exchanges.push_back(Exchange { outbound: frame, inbound: Vec::new() }),
+        FrameDirection::Inbound => match exchanges.back_mut() {
+          Some(exchange) => exchange.inbound.push(frame),
+          None => unsolicited.push_back(frame)
+        }
+      }
+    }
+
+    Ok(Self { exchanges, frames: unsolicited })
+  }
+
+  /**
+   * Recorded commands session didn't send yet
+   */
+  pub fn remaining(&self) -> usize {
+    self.exchanges.len()
+  }
+}
+
+#[async_trait]
+impl Transport for ReplayTransport {
+  async fn write_frame(&mut self, frame: &[u8]) -> Result<(), SessionError> {
+    // recording ends where the link was closed
+    let exchange = self.exchanges.pop_front().ok_or(SessionError::Disconnected)?;
+
+    if exchange.outbound.len() != frame.len() {
+      return Err(anyhow!("Replay diverged: recorded command has {} bytes, session sent {}", exchange.outbound.len(), frame.len()).into())
+    }
+
+    self.frames.extend(exchange.inbound);
+    Ok(())
+  }
+
+  async fn read_frame(&mut self) -> Result<Vec<u8>, SessionError> {
+    match self.frames.pop_front() {
+      Some(frame) => Ok(frame),
+      None => std::future::pending().await // scooter didn't answer in recording either
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::MiSession;
+  use super::super::capture::format_frame_line;
+  use super::super::commands::{ScooterCommand, Attribute};
+  use crate::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
+
+  use std::time::Duration;
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  /**
+   * Telemetry read and its 0xB0 status block answer: 61%, 18.5 km/h, 2186 m, 28.0 °C
+   */
+  fn recorded_session(keys: &LoginKeychain) -> String {
+    let request = ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x20);
+    let outbound = encrypt_uart(&keys.app, &request.try_as_bytes().unwrap(), 0, Some([0x01, 0x02, 0x03, 0x04]));
+
+    let mut response = vec![0x22, 0x23, 0x01, 0xB0];
+    response.extend_from_slice(&[0x00; 8]);
+    response.extend_from_slice(&[0x3D, 0x00, 0x44, 0x48, 0x50, 0x46, 0x8A, 0x08, 0x00, 0x00, 0x05, 0x00, 0x7C, 0x02, 0x18, 0x01]);
+    response.extend_from_slice(&[0x00; 8]);
+    let inbound = encrypt_uart(&keys.dev, &response, 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    [
+      format_frame_line(0.004, FrameDirection::Outbound, &outbound),
+      format_frame_line(0.087, FrameDirection::Inbound, &inbound)
+    ].join("\n")
+  }
+
+  #[tokio::test]
+  async fn test_replay_telemetry() {
+    let keys = test_keys();
+    let transport = ReplayTransport::from_log(&recorded_session(&keys)).unwrap();
+    assert_eq!(transport.remaining(), 1);
+
+    let mut session = MiSession::with_transport(transport, &keys);
+    let telemetry = session.read_telemetry().await.unwrap();
+
+    assert_eq!(telemetry.speed_kmh, 18.5);
+    assert_eq!(telemetry.battery_percent, 61);
+    assert_eq!(telemetry.odometer_km, 2.186);
+    assert_eq!(telemetry.temperature_c, 28.0);
+    assert_eq!(session.transport().remaining(), 0);
+
+    // recording is over
+    assert!(matches!(session.read_telemetry().await, Err(SessionError::Disconnected)));
+  }
+
+  #[tokio::test]
+  async fn test_replay_diverged() {
+    let keys = test_keys();
+    let mut session = MiSession::with_transport(ReplayTransport::from_log(&recorded_session(&keys)).unwrap(), &keys);
+
+    assert!(matches!(session.beep().await, Err(SessionError::Other(_))));
+  }
+
+  #[tokio::test]
+  async fn test_unanswered_command_times_out() {
+    let keys = test_keys();
+    let log = recorded_session(&keys);
+    let outbound_only = log.lines().next().unwrap();
+
+    let mut session = MiSession::with_transport(ReplayTransport::from_log(outbound_only).unwrap(), &keys);
+    session.set_timeout(Duration::from_millis(10));
+
+    assert!(matches!(session.read_telemetry().await, Err(SessionError::Timeout)));
+  }
+
+  #[test]
+  fn test_invalid_log() {
+    let error = ReplayTransport::from_log("0.001 -> 55 AB\nbroken").err().unwrap();
+    assert!(error.to_string().starts_with("Line 2"));
+  }
+}
@@ -0,0 +1,135 @@
+//! Riding mode for M365 scooter
+//!
+//! Switches between eco, drive and sport mode, which limit top speed and
+//! acceleration.
+//!
+//! ## Protocol Reference
+//! - Eco: 0x0002, Drive: 0x0001, Sport: 0x0004 at address 0x75
+//! - Top speed of each mode is fixed by firmware. No documented register
+//!   changes it, so there is no speed limit setter (see "Speed Limits" in
+//!   doc/BLE_PROTOCOL_GUIDE.md)
+//! - Direction: Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01) / Write (0x03)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, ReadWrite, Attribute};
+
+use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RideMode {
+    Eco,
+    Drive,
+    Sport,
+}
+
+impl RideMode {
+    fn value(&self) -> u16 {
+        match self {
+            RideMode::Eco => 0x0002,
+            RideMode::Drive => 0x0001,
+            RideMode::Sport => 0x0004,
+        }
+    }
+}
+
+impl TryFrom<u16> for RideMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0002 => Ok(RideMode::Eco),
+            0x0001 => Ok(RideMode::Drive),
+            0x0004 => Ok(RideMode::Sport),
+            _ => Err(anyhow!("Unknown ride mode: 0x{:04X}", value)),
+        }
+    }
+}
+
+/// Build ride mode write command
+fn set_ride_mode_command(mode: RideMode) -> ScooterCommand {
+    ScooterCommand {
+        direction: Direction::MasterToMotor,
+        read_write: ReadWrite::Write,
+        attribute: Attribute::RideMode,
+        payload: mode.value().to_le_bytes().to_vec()
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Switch riding mode
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::RideMode;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// session.set_ride_mode(RideMode::Sport).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn set_ride_mode(&mut self, mode: RideMode) -> Result<(), SessionError> {
+        tracing::debug!("Setting ride mode: {:?}", mode);
+
+        self.send(&set_ride_mode_command(mode)).await?;
+
+        Ok(())
+    }
+
+    /// Read active riding mode, unknown values are reported as error
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let mode = session.read_ride_mode().await?;
+    /// println!("Mode: {:?}", mode);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_ride_mode(&mut self) -> Result<RideMode, SessionError> {
+        tracing::debug!("Reading ride mode");
+
+        self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::RideMode, 0x02)).await?;
+
+        let mut payload = self.read(2).await?;
+        payload.pop_head()?;
+
+        Ok(RideMode::try_from(payload.pop_u16()?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ride_mode_command_bytes() {
+        let expected = [
+            (RideMode::Eco, 0x02),
+            (RideMode::Drive, 0x01),
+            (RideMode::Sport, 0x04),
+        ];
+
+        for (mode, byte) in expected {
+            let bytes = set_ride_mode_command(mode).try_as_bytes().unwrap();
+            // Expected: Length(4) + Dir(0x20) + Cmd(0x03) + Addr(0x75) + Payload(mode, 0x00)
+            assert_eq!(bytes, vec![0x04, 0x20, 0x03, 0x75, byte, 0x00]);
+        }
+    }
+
+    #[test]
+    fn test_ride_mode_from_value() {
+        assert_eq!(RideMode::try_from(0x0002).unwrap(), RideMode::Eco);
+        assert_eq!(RideMode::try_from(0x0001).unwrap(), RideMode::Drive);
+        assert_eq!(RideMode::try_from(0x0004).unwrap(), RideMode::Sport);
+    }
+
+    #[test]
+    fn test_unknown_ride_mode() {
+        assert!(RideMode::try_from(0x0000).is_err());
+        assert!(RideMode::try_from(0x0003).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use super::{MiSession, Payload};
+use super::{MiSession, Payload, Transport, SessionError};
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
 
 use anyhow::Result;
@@ -66,12 +66,12 @@ impl TryFrom<Payload> for SupplementaryInfo {
   }
 }
 
-impl MiSession {
-  pub async fn supplementary_info(&mut self) -> Result<SupplementaryInfo> {
+impl<T: Transport> MiSession<T> {
+  pub async fn supplementary_info(&mut self) -> Result<SupplementaryInfo, SessionError> {
     tracing::debug!("Reading supplementary information");
 
     self.send(&ScooterCommand {
-      direction: Direction::MasterToBattery,
+      direction: Attribute::Supplementary.destination(),
       read_write: ReadWrite::Read,
       attribute: Attribute::Supplementary,
       payload: vec![0x06]
@@ -82,7 +82,7 @@ impl MiSession {
     Ok(SupplementaryInfo::try_from(payload)?)
   }
 
-  pub async fn is_cruise_on(&mut self) -> Result<bool> {
+  pub async fn is_cruise_on(&mut self) -> Result<bool, SessionError> {
     tracing::debug!("Reading cruise state");
 
     self.send(&ScooterCommand {
@@ -98,7 +98,7 @@ impl MiSession {
     Ok(payload.pop_bool()?)
   }
 
-  pub async fn tail_light(&mut self) -> Result<TailLight> {
+  pub async fn tail_light(&mut self) -> Result<TailLight, SessionError> {
     tracing::debug!("Reading tail light state");
 
     self.send(&ScooterCommand {
@@ -116,43 +116,7 @@ impl MiSession {
     )
   }
 
-  pub async fn set_tail_light(&mut self, mode : TailLight) -> Result<()> {
-    tracing::debug!("Setting tail light: {:?}", mode);
-
-    let mode : u8 = match mode {
-      TailLight::OnBrake => 0x01,
-      TailLight::Always => 0x02,
-      _ => 0x00
-    };
-
-    let payload = vec![mode, 0x00];
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Write,
-      attribute: Attribute::TailLight,
-      payload
-    }).await?;
-
-    Ok(())
-  }
-
-  pub async fn set_cruise(&mut self, on : bool) -> Result<()> {
-    tracing::debug!("Setting cruise enabled: {}", on);
-
-    let payload = if on {
-      vec![0x01, 0x00]
-    } else {
-      vec![0x00, 0x00]
-    };
-
-    self.send(&ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Write,
-      attribute: Attribute::Cruise,
-      payload
-    }).await?;
-
-    Ok(())
+  pub async fn set_cruise(&mut self, on : bool) -> Result<(), SessionError> {
+    self.set_cruise_control(on).await
   }
 }
@@ -0,0 +1,158 @@
+use super::{MiSession, Payload, SessionError};
+use super::commands::ScooterCommand;
+use super::transport::{Transport, BleTransport};
+
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+/**
+ * Cloneable handle to one session. Every clone talks over the same BLE link, access is serialized, so telemetry
+ * poller and user action can't interleave their frames and pick up each other's responses
+ */
+pub struct SessionHandle<T: Transport = BleTransport> {
+  session: Arc<Mutex<MiSession<T>>>
+}
+
+impl<T: Transport> SessionHandle<T> {
+  pub fn new(session: MiSession<T>) -> Self {
+    Self {
+      session: Arc::new(Mutex::new(session))
+    }
+  }
+
+  /**
+   * Wait until nobody else is using the link and borrow session. Everything called on the guard,
+   * e.g. `handle.session().await.read_speed().await`, runs without other handles writing in between
+   */
+  pub async fn session(&self) -> MutexGuard<'_, MiSession<T>> {
+    self.session.lock().await
+  }
+
+  /**
+   * Send command, writes wait for scooter's ACK but reads don't wait for response
+   */
+  pub async fn send(&self, cmd: &ScooterCommand) -> Result<bool, SessionError> {
+    self.session().await.send(cmd).await
+  }
+
+  /**
+   * Send read command and wait for its response as one round-trip
+   */
+  pub async fn request(&self, cmd: &ScooterCommand) -> Result<Payload, SessionError> {
+    let mut session = self.session().await;
+    session.send(cmd).await?;
+    session.read_frame().await
+  }
+}
+
+impl<T: Transport> Clone for SessionHandle<T> {
+  fn clone(&self) -> Self {
+    Self {
+      session: Arc::clone(&self.session)
+    }
+  }
+}
+
+impl<T: Transport> From<MiSession<T>> for SessionHandle<T> {
+  fn from(session: MiSession<T>) -> Self {
+    Self::new(session)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::commands::{Attribute, Direction};
+  use crate::mi_crypto::{encrypt_uart, decrypt_uart, EncryptionKey, LoginKeychain};
+
+  use std::collections::VecDeque;
+  use async_trait::async_trait;
+
+  fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+      dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+      app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] }
+    }
+  }
+
+  /**
+   * Answers every read with value of the register, yields between write and read so concurrent callers get a chance to interleave
+   */
+  struct FakeScooter {
+    keys: LoginKeychain,
+    replies: VecDeque<Vec<u8>>
+  }
+
+  #[async_trait]
+  impl Transport for FakeScooter {
+    async fn write_frame(&mut self, frame: &[u8]) -> Result<(), SessionError> {
+      let request = decrypt_uart(&self.keys.app, frame).unwrap();
+      let (direction, attribute) = (request[0], request[2]);
+
+      let value: u16 = match attribute {
+        0x32 => 87,     // battery percent
+        0x3E => 0x0118, // frame temperature, 28.0 °C
+        _ => 0
+      };
+
+      let mut reply = vec![0x04, direction + 3, 0x01, attribute];
+      reply.extend_from_slice(&value.to_le_bytes());
+      self.replies.push_back(encrypt_uart(&self.keys.dev, &reply, 0, None));
+
+      tokio::task::yield_now().await;
+      Ok(())
+    }
+
+    async fn read_frame(&mut self) -> Result<Vec<u8>, SessionError> {
+      tokio::task::yield_now().await;
+      Ok(self.replies.pop_front().expect("Read without request"))
+    }
+  }
+
+  fn fake_scooter() -> SessionHandle<FakeScooter> {
+    let keys = test_keys();
+    let transport = FakeScooter { keys: keys.clone(), replies: VecDeque::new() };
+
+    SessionHandle::new(MiSession::with_transport(transport, &keys))
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+  async fn test_concurrent_reads_get_own_responses() {
+    let handle = fake_scooter();
+
+    for _ in 0..20 {
+      let battery = handle.clone();
+      let battery = tokio::spawn(async move { battery.session().await.read_battery_percent().await });
+
+      let temperature = handle.clone();
+      let temperature = tokio::spawn(async move { temperature.session().await.read_controller_temp().await });
+
+      assert_eq!(battery.await.unwrap().unwrap(), 87);
+      assert_eq!(temperature.await.unwrap().unwrap(), 28);
+    }
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+  async fn test_concurrent_requests() {
+    let handle = fake_scooter();
+
+    let first = handle.clone();
+    let first = tokio::spawn(async move {
+      first.request(&ScooterCommand::read(Direction::MasterToBattery, Attribute::BatteryPercent, 0x02)).await
+    });
+
+    let second = handle.clone();
+    let second = tokio::spawn(async move {
+      second.request(&ScooterCommand::read(Direction::MasterToMotor, Attribute::FrameTemperature, 0x02)).await
+    });
+
+    let mut first = first.await.unwrap().unwrap();
+    let mut second = second.await.unwrap().unwrap();
+    first.pop_head().unwrap();
+    second.pop_head().unwrap();
+
+    assert_eq!(first.pop_u16().unwrap(), 87);
+    assert_eq!(second.pop_u16().unwrap(), 0x0118);
+    assert_eq!(handle.session().await.transport().replies.len(), 0);
+  }
+}
@@ -0,0 +1,205 @@
+//! Whole status block for M365 scooter
+//!
+//! Registers 0xB0-0xBF of the motor controller can be read as one contiguous block. It
+//! carries everything a dashboard refreshes (fault, warnings, battery, speed, mileage,
+//! temperature), so one request replaces the individual reads. `read_telemetry` reads the
+//! same block but keeps only the four values HUD shows.
+//!
+//! ## Protocol Reference
+//! - Read 32 bytes from address 0xB0, Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01)
+//! - Response layout (little endian):
+//!   - 0x00: fault code (u16), same values as `read_error_code`
+//!   - 0x02: warning flags (u16), same bits as `read_alarm_status`
+//!   - 0x04: status flags (u16), bit 0 is lock as in `read_lock_state`, other bits are not known yet
+//!   - 0x06: work mode (u16)
+//!   - 0x08: battery percent (u16)
+//!   - 0x0A: speed in meters per hour (i16)
+//!   - 0x0C: average speed in meters per hour (u16)
+//!   - 0x0E: total mileage in meters (u32)
+//!   - 0x12: trip distance in meters (i16)
+//!   - 0x14: uptime in seconds (i16)
+//!   - 0x16: frame temperature in 0.1 °C (i16)
+//!   - 0x18-0x1F: unknown
+//! - Current is not part of the block, `read_current` asks BMS for it
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+use super::fault::ScooterFault;
+use super::alarm::AlarmFlags;
+
+use std::time::Duration;
+use anyhow::Result;
+
+/// Bytes requested from 0xB0, whole block including the unknown tail
+const STATUS_BLOCK_LENGTH: u8 = 0x20;
+
+/// Everything motor controller reports in the 0xB0 block
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusBlock {
+    /// Fault which keeps scooter from riding, `ScooterFault::None` when everything is fine
+    pub fault: ScooterFault,
+    /// Warnings which don't stop the scooter
+    pub alarms: AlarmFlags,
+    /// Raw status bits, bit 0 is lock, the rest is kept for diagnostics until their meaning is known
+    pub flags: u16,
+    /// Raw work mode
+    pub work_mode: u16,
+    /// Charge left between 0 and 100
+    pub battery_percent: u16,
+    /// Current speed in kilometers per hour, negative when rolling backwards
+    pub speed_kmh: f32,
+    /// Average speed in kilometers per hour
+    pub average_speed_kmh: f32,
+    /// Total mileage in meters
+    pub total_distance_m: u32,
+    /// Distance of current trip in meters
+    pub trip_distance_m: i16,
+    /// Time since scooter was powered on
+    pub uptime: Duration,
+    /// Frame temperature in celsius
+    pub frame_temperature_c: f32,
+}
+
+fn parse_status_block(payload: Payload) -> Result<StatusBlock> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(StatusBlock {
+        fault: ScooterFault::from(payload.pop_u16()?),
+        alarms: AlarmFlags::from_bits_retain(payload.pop_u16()?),
+        flags: payload.pop_u16()?,
+        work_mode: payload.pop_u16()?,
+        battery_percent: payload.pop_u16()?,
+        speed_kmh: payload.pop_i16()? as f32 / 1000.0,
+        average_speed_kmh: payload.pop_u16()? as f32 / 1000.0,
+        total_distance_m: payload.pop_u32()?,
+        trip_distance_m: payload.pop_i16()?,
+        uptime: Duration::from_secs(payload.pop_i16()?.max(0) as u64),
+        frame_temperature_c: payload.pop_i16()? as f32 / 10.0,
+    })
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read the whole 0xB0 status block with single request
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let status = session.read_status_block().await?;
+    /// println!("{} km/h, {}%, {:?}", status.speed_kmh, status.battery_percent, status.fault);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_status_block(&mut self) -> Result<StatusBlock, SessionError> {
+        tracing::debug!("Reading status block");
+
+        self.send(&ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, STATUS_BLOCK_LENGTH)).await?;
+
+        let payload = self.read_frame().await?;
+        let status = parse_status_block(payload)?;
+        tracing::debug!("status block: {:?}", status);
+
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{fault, lock};
+    use hex_literal::hex;
+
+    /// Parked scooter: no fault, 64% battery, 1306.083 km on the clock, 88 s since power on, 25 °C
+    const CAPTURED_BLOCK: [u8; 39] = hex!("2301b00000000000080000400000000000e3ed130000005800fa000000000000000000676598f0");
+
+    /// Scooter with brake fault and low battery warning in drive mode at 18.5 km/h
+    fn riding_block() -> Vec<u8> {
+        let mut bytes = vec![0x23, 0x01, 0xB0];
+        bytes.extend_from_slice(&15u16.to_le_bytes()); // brake fault
+        bytes.extend_from_slice(&0x0001u16.to_le_bytes()); // low battery
+        bytes.extend_from_slice(&[0x00, 0x00, 0x02, 0x00]);
+        bytes.extend_from_slice(&9u16.to_le_bytes());
+        bytes.extend_from_slice(&18_500i16.to_le_bytes());
+        bytes.extend_from_slice(&14_200u16.to_le_bytes());
+        bytes.extend_from_slice(&2_186u32.to_le_bytes());
+        bytes.extend_from_slice(&640i16.to_le_bytes());
+        bytes.extend_from_slice(&636i16.to_le_bytes());
+        bytes.extend_from_slice(&280i16.to_le_bytes());
+        bytes.extend_from_slice(&[0x00; 8]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_captured_block() {
+        let status = parse_status_block(Payload::from(&CAPTURED_BLOCK[..])).unwrap();
+
+        assert_eq!(status, StatusBlock {
+            fault: ScooterFault::None,
+            alarms: AlarmFlags::empty(),
+            flags: 0x0800,
+            work_mode: 0,
+            battery_percent: 64,
+            speed_kmh: 0.0,
+            average_speed_kmh: 0.0,
+            total_distance_m: 1_306_083,
+            trip_distance_m: 0,
+            uptime: Duration::from_secs(88),
+            frame_temperature_c: 25.0,
+        });
+    }
+
+    #[test]
+    fn test_parse_block_while_riding() {
+        let status = parse_status_block(Payload::from(riding_block())).unwrap();
+
+        assert_eq!(status.fault, ScooterFault::BrakeFault);
+        assert_eq!(status.alarms, AlarmFlags::LOW_BATTERY);
+        assert_eq!(status.work_mode, 2);
+        assert_eq!(status.battery_percent, 9);
+        assert_eq!(status.speed_kmh, 18.5);
+        assert_eq!(status.average_speed_kmh, 14.2);
+        assert_eq!(status.trip_distance_m, 640);
+        assert_eq!(status.uptime, Duration::from_secs(636));
+        assert_eq!(status.frame_temperature_c, 28.0);
+    }
+
+    #[test]
+    fn test_error_code_is_first_word_of_block() {
+        // read_error_code asks for 2 bytes from 0xB0, scooter answers with the start of the same block
+        for block in [CAPTURED_BLOCK.to_vec(), riding_block()] {
+            let status = parse_status_block(Payload::from(block.clone())).unwrap();
+            assert_eq!(fault::parse_error_code(Payload::from(block)).unwrap(), status.fault);
+        }
+    }
+
+    #[test]
+    fn test_captured_flags_are_unlocked() {
+        let status = parse_status_block(Payload::from(&CAPTURED_BLOCK[..])).unwrap();
+
+        assert_eq!(status.flags, 0x0800);
+        assert!(!lock::lock_state_from_raw(status.flags));
+    }
+
+    #[test]
+    fn test_0xba_is_uptime() {
+        // 88 s since power on, read as current it would be 0.88 A with motor standing still
+        let offset = 3 + 2 * (0xBA - 0xB0);
+        assert_eq!(CAPTURED_BLOCK[offset..offset + 2], 88u16.to_le_bytes());
+        assert_eq!(parse_status_block(Payload::from(&CAPTURED_BLOCK[..])).unwrap().uptime, Duration::from_secs(88));
+
+        assert_eq!(Attribute::try_from(0xBA).ok(), None);
+    }
+
+    #[test]
+    fn test_truncated_block() {
+        assert!(parse_status_block(Payload::from(vec![0x23, 0x01, 0xB0, 0x00, 0x00])).is_err());
+    }
+
+    #[test]
+    fn test_status_block_command() {
+        let cmd = ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, STATUS_BLOCK_LENGTH);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB0, 0x20]);
+    }
+}
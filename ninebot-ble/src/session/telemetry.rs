@@ -0,0 +1,405 @@
+//! Telemetry snapshot for dashboards
+//!
+//! Motor controller exposes combined status block which contains speed, battery charge, total mileage
+//! and frame temperature, so whole snapshot is one request and one (multi-notification) response
+//! instead of four round-trips.
+//!
+//! ## Protocol Reference
+//! - Read 32 bytes from address 0xB0, Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01)
+//! - Response layout (little endian):
+//!   - 0x00-0x07: unknown / work mode
+//!   - 0x08: battery percent (u16)
+//!   - 0x0A: speed in meters per hour (i16)
+//!   - 0x0C: average speed in meters per hour (u16)
+//!   - 0x0E: total mileage in meters (u32)
+//!   - 0x12: trip distance in meters (i16)
+//!   - 0x14: uptime in seconds (i16)
+//!   - 0x16: frame temperature in 0.1 °C (i16)
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::info::MotorInfo;
+use super::commands::{ScooterCommand, Attribute};
+
+use std::time::Duration;
+use anyhow::Result;
+use futures::Stream;
+use futures::future::BoxFuture;
+use serde::{Serialize, Deserialize};
+use tokio::time::MissedTickBehavior;
+
+/// Values which HUD refreshes on every tick. Field names in JSON are camelCase,
+/// e.g. `{"speedKmh":18.5,"batteryPercent":61,"odometerKm":2.186,"temperatureC":28.0}`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Telemetry {
+    /// Current speed in kilometers per hour, negative when rolling backwards
+    pub speed_kmh: f32,
+    /// Charge left between 0 and 100
+    pub battery_percent: u8,
+    /// Total mileage in kilometers
+    pub odometer_km: f32,
+    /// Frame temperature in celsius
+    pub temperature_c: f32,
+}
+
+/// Kilometers in one mile
+const KM_PER_MILE: f32 = 1.609_344;
+
+/// Units HUD shows telemetry in, scooter itself always reports metric
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    pub fn speed_label(&self) -> &'static str {
+        match self {
+            Units::Metric => "km/h",
+            Units::Imperial => "mph",
+        }
+    }
+
+    pub fn distance_label(&self) -> &'static str {
+        match self {
+            Units::Metric => "km",
+            Units::Imperial => "mi",
+        }
+    }
+
+    pub fn temperature_label(&self) -> &'static str {
+        match self {
+            Units::Metric => "°C",
+            Units::Imperial => "°F",
+        }
+    }
+}
+
+/// Telemetry converted for display, see `Telemetry::in_units`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryReadout {
+    pub units: Units,
+    /// km/h or mph
+    pub speed: f32,
+    pub battery_percent: u8,
+    /// km or miles
+    pub odometer: f32,
+    /// °C or °F
+    pub temperature: f32,
+}
+
+impl Telemetry {
+    pub fn speed_mph(&self) -> f32 {
+        self.speed_kmh / KM_PER_MILE
+    }
+
+    pub fn odometer_miles(&self) -> f32 {
+        self.odometer_km / KM_PER_MILE
+    }
+
+    pub fn temp_fahrenheit(&self) -> f32 {
+        self.temperature_c * 9.0 / 5.0 + 32.0
+    }
+
+    /// Values in units rider picked, telemetry itself stays metric
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use ninebot_ble::session::Units;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let readout = session.read_telemetry().await?.in_units(Units::Imperial);
+    /// println!("{:.0} {}", readout.speed, readout.units.speed_label());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn in_units(&self, units: Units) -> TelemetryReadout {
+        match units {
+            Units::Metric => TelemetryReadout {
+                units,
+                speed: self.speed_kmh,
+                battery_percent: self.battery_percent,
+                odometer: self.odometer_km,
+                temperature: self.temperature_c,
+            },
+            Units::Imperial => TelemetryReadout {
+                units,
+                speed: self.speed_mph(),
+                battery_percent: self.battery_percent,
+                odometer: self.odometer_miles(),
+                temperature: self.temp_fahrenheit(),
+            },
+        }
+    }
+
+    /// Serialize snapshot into the JSON document HUD receives over its socket
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let json = session.read_telemetry().await?.to_json();
+    /// println!("{}", json);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Telemetry has only numeric fields")
+    }
+}
+
+impl From<MotorInfo> for Telemetry {
+    fn from(info: MotorInfo) -> Self {
+        Telemetry {
+            speed_kmh: info.speed_kmh,
+            battery_percent: info.battery_percent.min(100) as u8,
+            odometer_km: info.total_distance_m as f32 / 1000.0,
+            temperature_c: info.frame_temperature,
+        }
+    }
+}
+
+impl TryFrom<Payload> for Telemetry {
+    type Error = anyhow::Error;
+
+    fn try_from(payload: Payload) -> Result<Self, Self::Error> {
+        Ok(MotorInfo::try_from(payload)?.into())
+    }
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read speed, battery, odometer and temperature with single request to the 0xB0 status block
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let telemetry = session.read_telemetry().await?;
+    /// println!("{} km/h, {}%", telemetry.speed_kmh, telemetry.battery_percent);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_telemetry(&mut self) -> Result<Telemetry, SessionError> {
+        tracing::debug!("Reading telemetry");
+
+        self.send(&ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x20)).await?;
+
+        let payload = self.read_frame().await?;
+
+        Ok(Telemetry::try_from(payload)?)
+    }
+
+    /// Poll telemetry every `interval`. Failed read is yielded as `Err` and polling continues on next tick,
+    /// stream never ends by itself. Nothing runs in background, so dropping the stream stops polling
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # use std::time::Duration;
+    /// # use futures::StreamExt;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// let mut telemetry = Box::pin(session.telemetry_stream(Duration::from_millis(500)));
+    /// while let Some(snapshot) = telemetry.next().await {
+    ///     match snapshot {
+    ///         Ok(snapshot) => println!("{} km/h", snapshot.speed_kmh),
+    ///         Err(err) => eprintln!("Skipping refresh: {}", err),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn telemetry_stream(&mut self, interval: Duration) -> impl Stream<Item = Result<Telemetry, SessionError>> + '_ {
+        poll_telemetry(self, interval)
+    }
+}
+
+/// Anything which can produce telemetry snapshot, lets stream be tested without scooter
+trait TelemetrySource {
+    fn next_telemetry(&mut self) -> BoxFuture<'_, Result<Telemetry, SessionError>>;
+}
+
+impl<T: Transport> TelemetrySource for MiSession<T> {
+    fn next_telemetry(&mut self) -> BoxFuture<'_, Result<Telemetry, SessionError>> {
+        Box::pin(self.read_telemetry())
+    }
+}
+
+fn poll_telemetry<S: TelemetrySource>(source: &mut S, interval: Duration) -> impl Stream<Item = Result<Telemetry, SessionError>> + '_ {
+    let mut ticker = tokio::time::interval(interval);
+    // Slow BLE read should push next refresh back, not cause burst of reads to catch up
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    futures::stream::unfold((source, ticker), |(source, mut ticker)| async move {
+        ticker.tick().await;
+        let telemetry = source.next_telemetry().await;
+
+        Some((telemetry, (source, ticker)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use futures::StreamExt;
+
+    // Captured 0xB0 block while riding, followed by 4 random bytes
+    const STATUS_BLOCK: [u8; 39] = [
+        0x23, 0x01, 0xB0,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x3D, 0x00, // 61%
+        0x44, 0x48, // 18.5 km/h
+        0x50, 0x46, // 18 km/h average
+        0x8A, 0x08, 0x00, 0x00, // 2186 m
+        0x05, 0x00,
+        0x7C, 0x02,
+        0x18, 0x01, // 28.0 °C
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x6A, 0xF8, 0x94, 0x11,
+    ];
+
+    #[test]
+    fn test_parse_status_block() {
+        let telemetry = Telemetry::try_from(Payload::from(&STATUS_BLOCK[..])).unwrap();
+
+        assert_eq!(telemetry, Telemetry {
+            speed_kmh: 18.5,
+            battery_percent: 61,
+            odometer_km: 2.186,
+            temperature_c: 28.0,
+        });
+    }
+
+    #[test]
+    fn test_parse_truncated_status_block() {
+        assert!(Telemetry::try_from(Payload::from(&STATUS_BLOCK[..20])).is_err());
+    }
+
+    #[test]
+    fn test_json_field_names() {
+        let telemetry = Telemetry { speed_kmh: 18.5, battery_percent: 61, odometer_km: 120.5, temperature_c: 28.0 };
+
+        assert_eq!(telemetry.to_json(), r#"{"speedKmh":18.5,"batteryPercent":61,"odometerKm":120.5,"temperatureC":28.0}"#);
+    }
+
+    #[test]
+    fn test_json_speed_is_number() {
+        let json: serde_json::Value = serde_json::from_str(&snapshot(12.0).to_json()).unwrap();
+
+        assert!(json["speedKmh"].is_number());
+        assert_eq!(json["speedKmh"].as_f64(), Some(12.0));
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let telemetry = snapshot(-3.5);
+        assert_eq!(serde_json::from_str::<Telemetry>(&telemetry.to_json()).unwrap(), telemetry);
+    }
+
+    #[test]
+    fn test_json_enums() {
+        use super::super::{RideMode, KersLevel};
+
+        assert_eq!(serde_json::to_string(&RideMode::Sport).unwrap(), r#""sport""#);
+        assert_eq!(serde_json::to_string(&KersLevel::Medium).unwrap(), r#""medium""#);
+        assert_eq!(serde_json::from_str::<RideMode>(r#""eco""#).unwrap(), RideMode::Eco);
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.01, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_imperial_conversions() {
+        let telemetry = Telemetry { speed_kmh: 25.0, battery_percent: 50, odometer_km: 100.0, temperature_c: 28.0 };
+
+        assert_close(telemetry.speed_mph(), 15.53);
+        assert_close(telemetry.odometer_miles(), 62.14);
+        assert_close(telemetry.temp_fahrenheit(), 82.4);
+    }
+
+    #[test]
+    fn test_fahrenheit_below_zero() {
+        let telemetry = Telemetry { temperature_c: -40.0, ..snapshot(0.0) };
+        assert_close(telemetry.temp_fahrenheit(), -40.0);
+    }
+
+    #[test]
+    fn test_in_units() {
+        let telemetry = Telemetry { speed_kmh: 25.0, battery_percent: 50, odometer_km: 100.0, temperature_c: 0.0 };
+
+        let metric = telemetry.in_units(Units::Metric);
+        assert_eq!((metric.speed, metric.odometer, metric.temperature), (25.0, 100.0, 0.0));
+
+        let imperial = telemetry.in_units(Units::Imperial);
+        assert_close(imperial.speed, 15.53);
+        assert_close(imperial.odometer, 62.14);
+        assert_close(imperial.temperature, 32.0);
+        assert_eq!(imperial.battery_percent, 50);
+        assert_eq!(imperial.units.speed_label(), "mph");
+        // converting doesn't touch the snapshot
+        assert_eq!(telemetry.speed_kmh, 25.0);
+    }
+
+    #[test]
+    fn test_telemetry_read_command() {
+        let cmd = ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x20);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0xB0, 0x20]);
+    }
+
+    struct MockSession {
+        responses: VecDeque<Result<Telemetry, SessionError>>,
+        reads: usize,
+    }
+
+    impl TelemetrySource for MockSession {
+        fn next_telemetry(&mut self) -> BoxFuture<'_, Result<Telemetry, SessionError>> {
+            self.reads += 1;
+            let response = self.responses.pop_front().unwrap_or(Err(SessionError::Timeout));
+            Box::pin(async move { response })
+        }
+    }
+
+    fn snapshot(speed_kmh: f32) -> Telemetry {
+        Telemetry { speed_kmh, battery_percent: 80, odometer_km: 120.5, temperature_c: 24.0 }
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_snapshots() {
+        let mut session = MockSession {
+            responses: VecDeque::from(vec![Ok(snapshot(0.0)), Ok(snapshot(5.5)), Ok(snapshot(12.0))]),
+            reads: 0,
+        };
+
+        let speeds: Vec<f32> = poll_telemetry(&mut session, Duration::from_millis(1))
+            .take(3)
+            .map(|telemetry| telemetry.unwrap().speed_kmh)
+            .collect()
+            .await;
+
+        assert_eq!(speeds, vec![0.0, 5.5, 12.0]);
+        // stream was dropped after third item, no more reads were issued
+        assert_eq!(session.reads, 3);
+    }
+
+    #[tokio::test]
+    async fn test_stream_continues_after_error() {
+        let mut session = MockSession {
+            responses: VecDeque::from(vec![Ok(snapshot(1.0)), Err(SessionError::Timeout), Ok(snapshot(2.0))]),
+            reads: 0,
+        };
+
+        let results: Vec<Result<Telemetry, SessionError>> = poll_telemetry(&mut session, Duration::from_millis(1))
+            .take(3)
+            .collect()
+            .await;
+
+        assert_eq!(results[0].as_ref().unwrap().speed_kmh, 1.0);
+        assert!(matches!(results[1], Err(SessionError::Timeout)));
+        assert_eq!(results[2].as_ref().unwrap().speed_kmh, 2.0);
+    }
+}
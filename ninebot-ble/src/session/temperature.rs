@@ -0,0 +1,97 @@
+//! Controller temperature for M365 scooter
+//!
+//! Motor controller measures temperature of the frame it is mounted in, which is what the
+//! overheating warning should watch. M365 motor has no sensor of its own, so there is no
+//! separate motor temperature register.
+//!
+//! ## Protocol Reference
+//! - Read 2 bytes from address 0x3E, Master to Motor Controller (0x20)
+//! - Command Type: Read (0x01)
+//! - Value: signed 16-bit, tenths of °C on most firmwares (0x0118 = 28.0 °C), whole °C on some
+
+use super::{MiSession, Payload, Transport, SessionError};
+use super::commands::{ScooterCommand, Attribute};
+
+use anyhow::Result;
+
+/// Controller never reports more than this in whole degrees, anything above is in tenths
+const MAX_WHOLE_DEGREES: i16 = 150;
+
+/// Convert raw frame temperature to whole °C, no matter which scaling firmware uses
+pub fn controller_temp_celsius(raw: i16) -> i16 {
+    if raw.abs() > MAX_WHOLE_DEGREES {
+        (raw as f32 / 10.0).round() as i16
+    } else {
+        raw
+    }
+}
+
+pub(super) fn parse_controller_temp(payload: Payload) -> Result<i16> {
+    let mut payload = payload;
+    payload.pop_head()?;
+
+    Ok(controller_temp_celsius(payload.pop_i16()?))
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read motor controller temperature in °C, negative below freezing
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// if session.read_controller_temp().await? > 60 {
+    ///     println!("Controller is overheating!");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_controller_temp(&mut self) -> Result<i16, SessionError> {
+        tracing::debug!("Reading controller temperature");
+
+        self.send(&ScooterCommand::read(Attribute::FrameTemperature.destination(), Attribute::FrameTemperature, 0x02)).await?;
+
+        let payload = self.read_frame().await?;
+        let temperature = parse_controller_temp(payload)?;
+        tracing::debug!("controller temperature: {}°C", temperature);
+
+        Ok(temperature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_response(raw: i16) -> Payload {
+        let mut bytes = vec![0x23, 0x01, 0x3E];
+        bytes.extend_from_slice(&raw.to_le_bytes());
+        bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+        Payload::from(bytes)
+    }
+
+    #[test]
+    fn test_deci_degrees() {
+        assert_eq!(parse_controller_temp(temp_response(0x0118)).unwrap(), 28);
+        assert_eq!(parse_controller_temp(temp_response(655)).unwrap(), 66);
+    }
+
+    #[test]
+    fn test_whole_degrees() {
+        assert_eq!(parse_controller_temp(temp_response(28)).unwrap(), 28);
+        assert_eq!(parse_controller_temp(temp_response(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_below_zero() {
+        // -15.3 °C as 0xFF67
+        assert_eq!(parse_controller_temp(temp_response(-153)).unwrap(), -15);
+        assert_eq!(parse_controller_temp(temp_response(-7)).unwrap(), -7);
+    }
+
+    #[test]
+    fn test_controller_temp_read_command() {
+        let cmd = ScooterCommand::read(Attribute::FrameTemperature.destination(), Attribute::FrameTemperature, 0x02);
+        assert_eq!(cmd.try_as_bytes().unwrap(), vec![0x03, 0x20, 0x01, 0x3E, 0x02]);
+    }
+}
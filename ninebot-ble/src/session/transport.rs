@@ -0,0 +1,246 @@
+use super::frame_assembler::FrameAssembler;
+use super::mi_session::SessionError;
+use crate::protocol::MiProtocol;
+use crate::uart_service::UartService;
+use crate::consts::Registers;
+use crate::connection::ConnectionHelper;
+
+use std::collections::VecDeque;
+use std::io;
+use anyhow::Result;
+use async_trait::async_trait;
+use btleplug::api::WriteType;
+use btleplug::platform::Peripheral;
+
+/**
+ * GATT write used for frames. Without response is faster and fits frequent polling, but lost write is
+ * noticed only when response doesn't come. With response every chunk is confirmed by scooter
+ */
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WriteMode {
+  WithResponse,
+  #[default]
+  WithoutResponse
+}
+
+impl From<WriteMode> for WriteType {
+  fn from(mode: WriteMode) -> Self {
+    match mode {
+      WriteMode::WithResponse => WriteType::WithResponse,
+      WriteMode::WithoutResponse => WriteType::WithoutResponse
+    }
+  }
+}
+
+/**
+ * Moves encrypted frames between session and scooter. Session only encrypts, decrypts and parses,
+ * so everything above transport can be tested with canned frames instead of real scooter
+ */
+#[async_trait]
+pub trait Transport: Send {
+  /**
+   * Send one complete encrypted frame (55 AB ...)
+   */
+  async fn write_frame(&mut self, frame: &[u8]) -> Result<(), SessionError>;
+
+  /**
+   * Send frame with given write mode. Transports which have only one way of writing ignore the mode
+   */
+  async fn write_frame_as(&mut self, frame: &[u8], _mode: WriteMode) -> Result<(), SessionError> {
+    self.write_frame(frame).await
+  }
+
+  /**
+   * Wait for next complete encrypted frame, no matter into how many chunks it was split on the way.
+   * May wait forever, session decides how long it is willing to wait for response
+   */
+  async fn read_frame(&mut self) -> Result<Vec<u8>, SessionError>;
+
+  /**
+   * Bring dropped link back and subscribe to notifications again. Transports which can't reconnect stay disconnected
+   */
+  async fn reconnect(&mut self) -> Result<(), SessionError> {
+    Err(SessionError::Disconnected)
+  }
+}
+
+/**
+ * Transport over Ninebot UART characteristics of connected scooter
+ */
+pub struct BleTransport {
+  device: Peripheral,
+  protocol: MiProtocol,
+  assembler: FrameAssembler,
+  frames: VecDeque<Vec<u8>>,
+}
+
+impl BleTransport {
+  pub async fn new(device: &Peripheral) -> Result<Self> {
+    let protocol = MiProtocol::new(device).await?;
+
+    Ok(Self {
+      device: device.clone(),
+      protocol,
+      assembler: FrameAssembler::new(),
+      frames: VecDeque::new()
+    })
+  }
+
+  /**
+   * UART service and characteristics resolved on connect
+   */
+  pub fn uart(&self) -> UartService {
+    self.protocol.uart()
+  }
+}
+
+#[async_trait]
+impl Transport for BleTransport {
+  async fn write_frame(&mut self, frame: &[u8]) -> Result<(), SessionError> {
+    self.write_frame_as(frame, WriteMode::default()).await
+  }
+
+  async fn write_frame_as(&mut self, frame: &[u8], mode: WriteMode) -> Result<(), SessionError> {
+    self.protocol.write_nb_parcel_as(&Registers::TX, frame, mode.into()).await
+      .map_err(|err| match err.downcast_ref::<btleplug::Error>() {
+        Some(btleplug::Error::NotConnected) => SessionError::Disconnected,
+        _ => SessionError::Transport(io::Error::other(format!("{:#}", err)))
+      })?;
+
+    Ok(())
+  }
+
+  async fn read_frame(&mut self) -> Result<Vec<u8>, SessionError> {
+    while self.frames.is_empty() {
+      let notification = self.protocol.next().await
+        .ok_or(SessionError::Disconnected)?; // notification stream ends when link drops
+
+      if notification.uuid != self.protocol.uart().rx {
+        continue;
+      }
+
+      let frames = self.assembler.push(&notification.value);
+      self.frames.extend(frames);
+    }
+
+    Ok(self.frames.pop_front().unwrap())
+  }
+
+  async fn reconnect(&mut self) -> Result<(), SessionError> {
+    ConnectionHelper::new(&self.device).connect().await
+      .map_err(|err| SessionError::Transport(io::Error::other(err)))?;
+
+    // old notification stream ended with the link, new one needs fresh subscription
+    self.protocol = MiProtocol::new(&self.device).await
+      .map_err(|err| SessionError::Transport(io::Error::other(format!("{:#}", err))))?;
+    self.assembler = FrameAssembler::new();
+    self.frames.clear();
+
+    Ok(())
+  }
+}
+
+/**
+ * Transport which replies with scripted frames and remembers everything that was written
+ */
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MockTransport {
+  pub responses: VecDeque<Result<Vec<u8>, SessionError>>,
+  pub written: Vec<Vec<u8>>,
+  /**
+   * Mode of every write in `written`, frames sent with plain `write_frame` count as default mode
+   */
+  pub write_modes: Vec<WriteMode>,
+  /**
+   * Number of reconnect attempts which fail before link comes back
+   */
+  pub failing_reconnects: usize,
+  pub reconnects: usize,
+  /**
+   * Keys for answering every written command with its `simulate_response`, see `auto_reply_with_simulated_responses`
+   */
+  auto_reply: Option<crate::mi_crypto::LoginKeychain>,
+}
+
+#[cfg(test)]
+impl MockTransport {
+  pub fn with_responses(responses: Vec<Vec<u8>>) -> Self {
+    Self {
+      responses: responses.into_iter().map(Ok).collect(),
+      ..Default::default()
+    }
+  }
+
+  /**
+   * Scooter's answer to write of `attribute`, status 0x01 is ACK and anything else NAK
+   */
+  pub fn write_reply(keys: &crate::mi_crypto::LoginKeychain, attribute: super::commands::Attribute, status: u8) -> Vec<u8> {
+    let direction = attribute.destination().reply().value();
+    crate::mi_crypto::encrypt_uart(&keys.dev, &[0x03, direction, 0x03, attribute.address(), status], 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+  }
+
+  /**
+   * Answer every command written from now on with `ScooterCommand::simulate_response`: ACK for writes,
+   * zero filled payload of requested length for reads. Answers queue up after already scripted responses
+   */
+  pub fn auto_reply_with_simulated_responses(&mut self, keys: &crate::mi_crypto::LoginKeychain) {
+    self.auto_reply = Some(keys.clone());
+  }
+
+  fn simulated_response(keys: &crate::mi_crypto::LoginKeychain, frame: &[u8]) -> Vec<u8> {
+    let decrypted = crate::mi_crypto::decrypt_uart(&keys.app, frame).expect("Mock received frame it can't decrypt");
+
+    // length byte travels in clear, decrypted part ends with 4 random bytes
+    let mut bytes = vec![frame[2]];
+    bytes.extend_from_slice(&decrypted[..decrypted.len() - 4]);
+    let command = super::commands::ScooterCommand::try_from_bytes(&bytes).expect("Mock received invalid command");
+
+    let response = command.simulate_response().try_as_bytes().unwrap();
+    crate::mi_crypto::encrypt_uart(&keys.dev, &response, 0, Some([0x6a, 0xf8, 0x94, 0x11]))
+  }
+
+  /**
+   * Fail next read with given error instead of returning frame
+   */
+  pub fn push_error(&mut self, error: SessionError) {
+    self.responses.push_back(Err(error));
+  }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Transport for MockTransport {
+  async fn write_frame(&mut self, frame: &[u8]) -> Result<(), SessionError> {
+    self.write_frame_as(frame, WriteMode::default()).await
+  }
+
+  async fn write_frame_as(&mut self, frame: &[u8], mode: WriteMode) -> Result<(), SessionError> {
+    self.written.push(frame.to_vec());
+    self.write_modes.push(mode);
+
+    if let Some(keys) = &self.auto_reply {
+      let response = Self::simulated_response(keys, frame);
+      self.responses.push_back(Ok(response));
+    }
+
+    Ok(())
+  }
+
+  async fn read_frame(&mut self) -> Result<Vec<u8>, SessionError> {
+    match self.responses.pop_front() {
+      Some(response) => response,
+      None => std::future::pending().await // scooter which never replies
+    }
+  }
+
+  async fn reconnect(&mut self) -> Result<(), SessionError> {
+    self.reconnects += 1;
+
+    if self.reconnects <= self.failing_reconnects {
+      return Err(SessionError::Disconnected)
+    }
+
+    Ok(())
+  }
+}
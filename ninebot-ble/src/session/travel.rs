@@ -1,13 +1,24 @@
-use super::MiSession;
+use super::{MiSession, Payload, Transport, SessionError};
 use super::commands::{ScooterCommand, Direction, Attribute, ReadWrite};
 
+use std::time::Duration;
 use anyhow::Result;
+use serde::Serialize;
 
-impl MiSession {
+/**
+ * Speeds of current trip in kilometers per hour, scooter keeps both in one block so they are read together
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct TripStatistics {
+  pub average_speed: f32,
+  pub max_speed: f32
+}
+
+impl<T: Transport> MiSession<T> {
   /**
    * Get travel distance left in kilometers
    */
-  pub async fn distance_left(&mut self) -> Result<f32> {
+  pub async fn distance_left(&mut self) -> Result<f32, SessionError> {
     tracing::debug!("Reading distance left");
 
     let cmd = ScooterCommand {
@@ -32,23 +43,20 @@ impl MiSession {
   /**
    * Get current speed in kilometers per hour
    */
-  pub async fn speed(&mut self) -> Result<f32> {
-    tracing::debug!("Reading speed");
-
-    let cmd = ScooterCommand {
-      direction: Direction::MasterToMotor,
-      read_write: ReadWrite::Read,
-      attribute: Attribute::Speed,
-      payload: vec![0x02]
-    };
+  pub async fn speed(&mut self) -> Result<f32, SessionError> {
+    self.read_speed().await
+  }
 
-    self.send(&cmd).await?;
+  /**
+   * Current speed in kilometers per hour, negative when rolling backwards
+   */
+  pub async fn read_speed(&mut self) -> Result<f32, SessionError> {
+    tracing::debug!("Reading speed");
 
-    let mut payload = self.read(2).await?;
-    payload.pop_head()?;
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::Speed, 0x02)).await?;
 
-    let speed = payload.pop_i16()?;
-    let speed = speed as f32 / 1000.0;
+    let payload = self.read(2).await?;
+    let speed = parse_speed(payload)?;
     tracing::debug!("speed: {}km/h", speed);
 
     Ok(speed)
@@ -57,7 +65,7 @@ impl MiSession {
   /**
    * Read current travel distance in meters
    */
-  pub async fn trip_distance(&mut self) -> Result<u16> {
+  pub async fn trip_distance(&mut self) -> Result<u16, SessionError> {
     tracing::debug!("Reading distance");
 
     let cmd = ScooterCommand {
@@ -76,4 +84,237 @@ impl MiSession {
 
     Ok(trip_distance)
   }
+
+  /**
+   * Distance of current trip in kilometers
+   */
+  pub async fn read_trip_distance(&mut self) -> Result<f32, SessionError> {
+    tracing::debug!("Reading trip distance");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::TripDistance, 0x02)).await?;
+
+    let payload = self.read_frame().await?;
+    let trip_distance = parse_trip_distance(payload)?;
+    tracing::debug!("trip distance: {}km", trip_distance);
+
+    Ok(trip_distance)
+  }
+
+  /**
+   * Average and max speed of current trip in one round-trip
+   */
+  pub async fn read_trip_statistics(&mut self) -> Result<TripStatistics, SessionError> {
+    tracing::debug!("Reading trip statistics");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::TripStatistics, 0x04)).await?;
+
+    let payload = self.read_frame().await?;
+    let statistics = parse_trip_statistics(payload)?;
+    tracing::debug!("trip statistics: {:?}", statistics);
+
+    Ok(statistics)
+  }
+
+  /**
+   * Average speed of current trip in kilometers per hour, use `read_trip_statistics` when max speed is needed too
+   */
+  pub async fn read_average_speed(&mut self) -> Result<f32, SessionError> {
+    Ok(self.read_trip_statistics().await?.average_speed)
+  }
+
+  /**
+   * Max speed reached during current trip in kilometers per hour
+   */
+  pub async fn read_max_speed(&mut self) -> Result<f32, SessionError> {
+    Ok(self.read_trip_statistics().await?.max_speed)
+  }
+
+  /**
+   * Time spent actually riding during current trip, standing still is not counted
+   */
+  pub async fn read_riding_time(&mut self) -> Result<Duration, SessionError> {
+    tracing::debug!("Reading riding time");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::RidingTime, 0x02)).await?;
+
+    let payload = self.read_frame().await?;
+    let riding_time = parse_riding_time(payload)?;
+    tracing::debug!("riding time: {:?}", riding_time);
+
+    Ok(riding_time)
+  }
+
+  /**
+   * Time since scooter was powered on
+   */
+  pub async fn read_uptime(&mut self) -> Result<Duration, SessionError> {
+    tracing::debug!("Reading uptime");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::Uptime, 0x04)).await?;
+
+    let payload = self.read_frame().await?;
+    let uptime = parse_uptime(payload)?;
+    tracing::debug!("uptime: {:?}", uptime);
+
+    Ok(uptime)
+  }
+
+  /**
+   * Total distance scooter has ever travelled in kilometers
+   */
+  pub async fn read_odometer(&mut self) -> Result<f32, SessionError> {
+    tracing::debug!("Reading odometer");
+
+    self.send(&ScooterCommand::read(Direction::MasterToMotor, Attribute::TotalMileage, 0x04)).await?;
+
+    let payload = self.read_frame().await?;
+    let odometer = parse_odometer(payload)?;
+    tracing::debug!("odometer: {}km", odometer);
+
+    Ok(odometer)
+  }
+}
+
+/**
+ * Speed is signed meters per hour, reading it as unsigned would turn slow reverse into ~65 km/h
+ */
+pub(super) fn parse_speed(payload: Payload) -> Result<f32> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_i16()? as f32 / 1000.0)
+}
+
+/**
+ * Trip distance is reported in tens of meters
+ */
+pub(super) fn parse_trip_distance(payload: Payload) -> Result<f32> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_u16()? as f32 / 100.0)
+}
+
+/**
+ * Mileage is 32-bit value in meters, u16 would overflow after 65km
+ */
+pub(super) fn parse_odometer(payload: Payload) -> Result<f32> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(payload.pop_u32()? as f32 / 1000.0)
+}
+
+/**
+ * Average speed followed by max speed, both in meters per hour like live speed
+ */
+fn parse_trip_statistics(payload: Payload) -> Result<TripStatistics> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(TripStatistics {
+    average_speed: payload.pop_u16()? as f32 / 1000.0,
+    max_speed: payload.pop_u16()? as f32 / 1000.0
+  })
+}
+
+/**
+ * Riding time is reset with the trip, 16-bit seconds are enough for 18 hours
+ */
+fn parse_riding_time(payload: Payload) -> Result<Duration> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(Duration::from_secs(payload.pop_u16()? as u64))
+}
+
+/**
+ * Uptime is 32-bit seconds
+ */
+fn parse_uptime(payload: Payload) -> Result<Duration> {
+  let mut payload = payload;
+  payload.pop_head()?;
+
+  Ok(Duration::from_secs(payload.pop_u32()? as u64))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn speed_response(raw: i16) -> Payload {
+    let mut bytes = vec![0x23, 0x01, 0xB5];
+    bytes.extend_from_slice(&raw.to_le_bytes());
+    bytes.extend_from_slice(&[0x6a, 0xf8, 0x94, 0x11]); // random bytes
+    Payload::from(bytes)
+  }
+
+  #[test]
+  fn test_parse_positive_speed() {
+    assert_eq!(parse_speed(speed_response(18_500)).unwrap(), 18.5);
+  }
+
+  #[test]
+  fn test_parse_zero_speed() {
+    assert_eq!(parse_speed(speed_response(0)).unwrap(), 0.0);
+  }
+
+  #[test]
+  fn test_parse_reverse_speed() {
+    // 0xFE0C as u16 would be 65.036 km/h
+    assert_eq!(parse_speed(speed_response(-500)).unwrap(), -0.5);
+  }
+
+  #[test]
+  fn test_parse_trip_distance() {
+    let payload = Payload::from(vec![0x23, 0x01, 0xB9, 0x35, 0x01, 0x6a, 0xf8, 0x94, 0x11]);
+    assert_eq!(parse_trip_distance(payload).unwrap(), 3.09);
+  }
+
+  #[test]
+  fn test_parse_odometer() {
+    // 0x0001E240 = 123456 meters
+    let payload = Payload::from(vec![0x23, 0x01, 0x29, 0x40, 0xE2, 0x01, 0x00, 0x6a, 0xf8, 0x94, 0x11]);
+    assert_eq!(parse_odometer(payload).unwrap(), 123.456);
+  }
+
+  #[test]
+  fn test_parse_odometer_above_u16() {
+    // 0x00989680 = 10 000 000 meters, upper word must not be dropped
+    let payload = Payload::from(vec![0x23, 0x01, 0x29, 0x80, 0x96, 0x98, 0x00]);
+    assert_eq!(parse_odometer(payload).unwrap(), 10_000.0);
+  }
+
+  #[test]
+  fn test_parse_odometer_truncated() {
+    let payload = Payload::from(vec![0x23, 0x01, 0x29, 0x40, 0xE2]);
+    assert!(parse_odometer(payload).is_err());
+  }
+
+  #[test]
+  fn test_parse_trip_statistics() {
+    // 0x3E1C = 15.9 km/h average, 0x6590 = 26.0 km/h max
+    let payload = Payload::from(vec![0x23, 0x01, 0x3A, 0x1C, 0x3E, 0x90, 0x65, 0x6a, 0xf8, 0x94, 0x11]);
+    assert_eq!(parse_trip_statistics(payload).unwrap(), TripStatistics { average_speed: 15.9, max_speed: 26.0 });
+  }
+
+  #[test]
+  fn test_parse_trip_statistics_truncated() {
+    let payload = Payload::from(vec![0x23, 0x01, 0x3A, 0x1C, 0x3E]);
+    assert!(parse_trip_statistics(payload).is_err());
+  }
+
+  #[test]
+  fn test_parse_riding_time() {
+    // 0x02D0 = 720 seconds
+    let payload = Payload::from(vec![0x23, 0x01, 0x3C, 0xD0, 0x02, 0x6a, 0xf8, 0x94, 0x11]);
+    assert_eq!(parse_riding_time(payload).unwrap(), Duration::from_secs(12 * 60));
+  }
+
+  #[test]
+  fn test_parse_uptime() {
+    // 0x00012345 = 74565 seconds, 20 h 42 min 45 s
+    let payload = Payload::from(vec![0x23, 0x01, 0x3D, 0x45, 0x23, 0x01, 0x00, 0x6a, 0xf8, 0x94, 0x11]);
+    assert_eq!(parse_uptime(payload).unwrap(), Duration::from_secs(20 * 3600 + 42 * 60 + 45));
+  }
 }
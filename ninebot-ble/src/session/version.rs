@@ -0,0 +1,81 @@
+//! Firmware versions of M365 scooter modules
+//!
+//! Each module (BLE, ESC, BMS) reports its version as a packed 16-bit word,
+//! e.g. 0x0146 is version 1.4.6.
+//!
+//! ## Protocol Reference
+//! - BLE: Read 2 bytes from address 0x9B, Master to Motor Controller (0x20)
+//! - ESC: Read 2 bytes from address 0x1A, Master to Motor Controller (0x20)
+//! - BMS: Read 2 bytes from address 0x17, Master to Battery (0x22)
+//! - Command Type: Read (0x01)
+
+use super::{MiSession, Transport, SessionError};
+use super::commands::{ScooterCommand, Direction, Attribute};
+
+use anyhow::Result;
+
+/// Format packed version word: major in bits 8-11, minor in bits 4-7, patch in bits 0-3
+pub fn format_version(word: u16) -> String {
+    format!("{}.{}.{}", (word >> 8) & 0x0F, (word >> 4) & 0x0F, word & 0x0F)
+}
+
+impl<T: Transport> MiSession<T> {
+    /// Read BLE module firmware version, e.g. "1.4.0"
+    pub async fn read_ble_version(&mut self) -> Result<String, SessionError> {
+        self.read_version(Direction::MasterToMotor, Attribute::BleVersion).await
+    }
+
+    /// Read motor controller (ESC) firmware version
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use anyhow::Result;
+    /// # async fn example(session: &mut ninebot_ble::session::MiSession) -> Result<()> {
+    /// println!("ESC firmware: {}", session.read_esc_version().await?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn read_esc_version(&mut self) -> Result<String, SessionError> {
+        self.read_version(Direction::MasterToMotor, Attribute::EscVersion).await
+    }
+
+    /// Read battery management system (BMS) firmware version
+    pub async fn read_bms_version(&mut self) -> Result<String, SessionError> {
+        self.read_version(Direction::MasterToBattery, Attribute::BmsVersion).await
+    }
+
+    async fn read_version(&mut self, direction: Direction, attribute: Attribute) -> Result<String, SessionError> {
+        tracing::debug!("Reading {:?}", attribute);
+
+        self.send(&ScooterCommand::read(direction, attribute, 0x02)).await?;
+
+        let mut payload = self.read(2).await?;
+        payload.pop_head()?;
+
+        Ok(format_version(payload.pop_u16()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_version() {
+        assert_eq!(format_version(0x0140), "1.4.0");
+        assert_eq!(format_version(0x0146), "1.4.6");
+        assert_eq!(format_version(0x0157), "1.5.7");
+        assert_eq!(format_version(0x0000), "0.0.0");
+    }
+
+    #[test]
+    fn test_format_version_ignores_high_nibble() {
+        assert_eq!(format_version(0xF2AB), "2.10.11");
+    }
+
+    #[test]
+    fn test_bms_version_read_goes_to_battery() {
+        let bytes = ScooterCommand::read(Direction::MasterToBattery, Attribute::BmsVersion, 0x02).try_as_bytes().unwrap();
+        assert_eq!(bytes, vec![0x03, 0x22, 0x01, 0x17, 0x02]);
+    }
+}
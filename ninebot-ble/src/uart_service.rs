@@ -0,0 +1,130 @@
+use crate::consts::Registers;
+
+use btleplug::api::Characteristic;
+use thiserror::Error;
+use uuid::Uuid;
+
+/**
+ * UART over which scooter talks Ninebot protocol. Service and characteristic UUIDs are not the same on
+ * every firmware revision, so they are resolved on connect and kept here for debugging
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UartService {
+  pub service: Uuid,
+  /**
+   * Characteristic we write commands to
+   */
+  pub tx: Uuid,
+  /**
+   * Characteristic scooter notifies responses on
+   */
+  pub rx: Uuid,
+}
+
+#[derive(Error, Debug)]
+pub enum UartServiceError {
+  #[error("Scooter has no known UART service, found characteristics: {0:?}")]
+  NotFound(Vec<Uuid>)
+}
+
+impl UartService {
+  /**
+   * Known UART layouts in order of preference. Stock M365, Pro and 1S firmwares use Nordic UART,
+   * aftermarket BLE boards use HM-10 style service with single characteristic for both directions
+   */
+  pub fn candidates() -> Vec<UartService> {
+    vec![
+      UartService {
+        service: Registers::UART.to_uuid(),
+        tx: Registers::TX.to_uuid(),
+        rx: Registers::RX.to_uuid()
+      },
+      UartService {
+        service: Uuid::from_u128(0x0000ffe0_0000_1000_8000_00805f9b34fb),
+        tx: Uuid::from_u128(0x0000ffe1_0000_1000_8000_00805f9b34fb),
+        rx: Uuid::from_u128(0x0000ffe1_0000_1000_8000_00805f9b34fb)
+      }
+    ]
+  }
+
+  /**
+   * Pick first candidate whose both characteristics are present, returns it with its TX and RX characteristic
+   */
+  pub fn resolve(characteristics: &[Characteristic]) -> Result<(UartService, Characteristic, Characteristic), UartServiceError> {
+    let find = |service: Uuid, uuid: Uuid| {
+      characteristics.iter()
+        .find(|ch| ch.service_uuid == service && ch.uuid == uuid)
+        .cloned()
+    };
+
+    for candidate in Self::candidates() {
+      if let (Some(tx), Some(rx)) = (find(candidate.service, candidate.tx), find(candidate.service, candidate.rx)) {
+        tracing::debug!("Resolved UART: {:?}", candidate);
+        return Ok((candidate, tx, rx))
+      }
+
+      tracing::debug!("UART not present: {:?}", candidate);
+    }
+
+    Err(UartServiceError::NotFound(characteristics.iter().map(|ch| ch.uuid).collect()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use btleplug::api::CharPropFlags;
+  use std::collections::BTreeSet;
+
+  fn characteristic(service: Uuid, uuid: Uuid) -> Characteristic {
+    Characteristic {
+      uuid,
+      service_uuid: service,
+      properties: CharPropFlags::empty(),
+      descriptors: BTreeSet::new()
+    }
+  }
+
+  fn auth_characteristics() -> Vec<Characteristic> {
+    vec![
+      characteristic(Registers::AUTH.to_uuid(), Registers::UPNP.to_uuid()),
+      characteristic(Registers::AUTH.to_uuid(), Registers::AVDTP.to_uuid())
+    ]
+  }
+
+  #[test]
+  fn test_resolve_nordic_uart() {
+    let mut characteristics = auth_characteristics();
+    characteristics.push(characteristic(Registers::UART.to_uuid(), Registers::RX.to_uuid()));
+    characteristics.push(characteristic(Registers::UART.to_uuid(), Registers::TX.to_uuid()));
+
+    let (service, tx, rx) = UartService::resolve(&characteristics).unwrap();
+    assert_eq!(service, UartService::candidates()[0]);
+    assert_eq!(tx.uuid, Registers::TX.to_uuid());
+    assert_eq!(rx.uuid, Registers::RX.to_uuid());
+  }
+
+  #[test]
+  fn test_resolve_fallback() {
+    let fallback = UartService::candidates()[1];
+    let mut characteristics = auth_characteristics();
+    // Nordic service with only one of its characteristics can't be used
+    characteristics.push(characteristic(Registers::UART.to_uuid(), Registers::TX.to_uuid()));
+    characteristics.push(characteristic(fallback.service, fallback.tx));
+
+    let (service, tx, rx) = UartService::resolve(&characteristics).unwrap();
+    assert_eq!(service, fallback);
+    assert_eq!(tx.service_uuid, fallback.service);
+    assert_eq!(rx.uuid, fallback.rx);
+  }
+
+  #[test]
+  fn test_characteristic_from_other_service_is_ignored() {
+    let mut characteristics = auth_characteristics();
+    characteristics.push(characteristic(Registers::AUTH.to_uuid(), Registers::TX.to_uuid()));
+    characteristics.push(characteristic(Registers::AUTH.to_uuid(), Registers::RX.to_uuid()));
+
+    let error = UartService::resolve(&characteristics).unwrap_err();
+    assert!(matches!(&error, UartServiceError::NotFound(found) if found.len() == 4));
+  }
+}
@@ -0,0 +1,49 @@
+use ninebot_ble::cli::{self, CliCommand};
+use ninebot_ble::mi_crypto::{encrypt_uart, EncryptionKey, LoginKeychain};
+use ninebot_ble::session::{Attribute, MiSession, ReplayTransport, ScooterCommand};
+
+fn test_keys() -> LoginKeychain {
+    LoginKeychain {
+        dev: EncryptionKey { key: [0x11; 16], iv: [0x12; 4] },
+        app: EncryptionKey { key: [0x21; 16], iv: [0x22; 4] },
+    }
+}
+
+fn frame_line(arrow: &str, frame: &[u8]) -> String {
+    let hex: Vec<String> = frame.iter().map(|byte| format!("{:02X}", byte)).collect();
+    format!("0.000 {} {}", arrow, hex.join(" "))
+}
+
+/// Command as the session sends it and scooter's response, in the format FrameTap records
+fn recorded_exchange(keys: &LoginKeychain, command: &ScooterCommand, response: &ScooterCommand) -> String {
+    let outbound = encrypt_uart(&keys.app, &command.try_as_bytes().unwrap(), 0, Some([0x01, 0x02, 0x03, 0x04]));
+    let inbound = encrypt_uart(&keys.dev, &response.try_as_bytes().unwrap(), 0, Some([0x6a, 0xf8, 0x94, 0x11]));
+
+    [frame_line("->", &outbound), frame_line("<-", &inbound)].join("\n")
+}
+
+async fn run_replay(log: &str, command: CliCommand) -> (String, usize) {
+    let keys = test_keys();
+    let transport = ReplayTransport::from_log(log).unwrap();
+    let mut session = MiSession::with_transport(transport, &keys);
+
+    let mut out = Vec::new();
+    cli::run(&mut session, command, &mut out).await.unwrap();
+
+    (String::from_utf8(out).unwrap(), session.transport().remaining())
+}
+
+#[tokio::test]
+async fn it_prints_telemetry() {
+    let keys = test_keys();
+    let request = ScooterCommand::read(Attribute::MotorInfo.destination(), Attribute::MotorInfo, 0x20);
+    let mut payload = vec![0x00; 8];
+    payload.extend_from_slice(&[0x3D, 0x00, 0x44, 0x48, 0x50, 0x46, 0x8A, 0x08, 0x00, 0x00, 0x05, 0x00, 0x7C, 0x02, 0x18, 0x01]);
+    payload.extend_from_slice(&[0x00; 8]);
+    let response = ScooterCommand { payload, ..request.simulate_response() };
+
+    let (out, remaining) = run_replay(&recorded_exchange(&keys, &request, &response), CliCommand::Telemetry).await;
+
+    assert_eq!(out, "speed: 18.5 km/h\nbattery: 61%\nodometer: 2.2 km\ntemperature: 28.0 °C\n");
+    assert_eq!(remaining, 0);
+}
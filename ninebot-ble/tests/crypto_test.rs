@@ -1,4 +1,4 @@
-use ninebot_ble::mi_crypto;
+use ninebot_ble::mi_crypto::{self, KeySchedule};
 use p256::{ecdh::EphemeralSecret, EncodedPoint};
 use rand_core::OsRng;
 
@@ -14,8 +14,295 @@ fn it_calculates_did() {
     ];
 
     let (did_ct, token) =
-        mi_crypto::calc_did(&scooter_secret, remote_public_key.as_bytes(), &remote_info);
+        mi_crypto::calc_did(KeySchedule::V1, &scooter_secret, remote_public_key.as_bytes(), &remote_info).unwrap();
 
     assert_eq!(24, did_ct.len());
     assert_eq!(12, token.len());
 }
+
+#[test]
+fn it_rejects_truncated_remote_key() {
+    let scooter_secret = EphemeralSecret::random(&mut OsRng);
+    let remote_secret = EphemeralSecret::random(&mut OsRng);
+    let remote_public_key = EncodedPoint::from(remote_secret.public_key());
+
+    let result = mi_crypto::calc_did(KeySchedule::V1, &scooter_secret, &remote_public_key.as_bytes()[..40], &[0u8; 24]);
+
+    assert!(matches!(result, Err(mi_crypto::MiCryptoError::BadRemoteKeyLength(40))));
+}
+
+#[test]
+fn it_rejects_remote_key_off_curve() {
+    let scooter_secret = EphemeralSecret::random(&mut OsRng);
+    let mut remote_key = [0xFFu8; 65];
+    remote_key[0] = 0x04;
+
+    let result = mi_crypto::calc_did(KeySchedule::V1, &scooter_secret, &remote_key, &[0u8; 24]);
+
+    assert!(matches!(result, Err(mi_crypto::MiCryptoError::EcdhFailure)));
+}
+
+fn test_keychain() -> mi_crypto::LoginKeychain {
+    mi_crypto::LoginKeychain {
+        dev: mi_crypto::EncryptionKey { key: [0x01; 16], iv: [0x02; 4] },
+        app: mi_crypto::EncryptionKey { key: [0x03; 16], iv: [0x04; 4] },
+    }
+}
+
+#[test]
+fn it_roundtrips_keychain() {
+    let keys = test_keychain();
+    let bytes = keys.to_bytes();
+
+    assert_eq!(41, bytes.len());
+    assert_eq!(0xC1, bytes[0]);
+
+    let restored = mi_crypto::LoginKeychain::from_bytes(&bytes).unwrap();
+    assert_eq!(keys.dev.key, restored.dev.key);
+    assert_eq!(keys.dev.iv, restored.dev.iv);
+    assert_eq!(keys.app.key, restored.app.key);
+    assert_eq!(keys.app.iv, restored.app.iv);
+}
+
+#[test]
+fn it_rejects_invalid_keychain() {
+    let mut bytes = test_keychain().to_bytes();
+
+    assert!(mi_crypto::LoginKeychain::from_bytes(&bytes[..40]).is_err());
+    assert!(mi_crypto::LoginKeychain::from_bytes(&[]).is_err());
+
+    assert!(matches!(
+        mi_crypto::LoginKeychain::from_bytes(&bytes[..40]),
+        Err(mi_crypto::MiCryptoError::InvalidKeychain)
+    ));
+
+    bytes[0] = 0xC2; // unknown version
+    assert!(matches!(
+        mi_crypto::LoginKeychain::from_bytes(&bytes),
+        Err(mi_crypto::MiCryptoError::UnsupportedVersion(0xC2))
+    ));
+}
+
+#[test]
+fn it_wipes_keychain_on_drop() {
+    // ManuallyDrop keeps the memory around, so it can be inspected after drop ran
+    let mut keys = std::mem::ManuallyDrop::new(test_keychain());
+    unsafe { std::mem::ManuallyDrop::drop(&mut keys) };
+
+    assert_eq!(keys.dev.key, [0u8; 16]);
+    assert_eq!(keys.dev.iv, [0u8; 4]);
+    assert_eq!(keys.app.key, [0u8; 16]);
+    assert_eq!(keys.app.iv, [0u8; 4]);
+}
+
+#[test]
+fn it_rejects_tampered_uart_frame() {
+    let keys = test_keychain();
+    let mut frame = mi_crypto::encrypt_uart(&keys.app, &[0x03, 0x20, 0x01, 0x10, 0x0e], 0, None);
+    assert!(mi_crypto::decrypt_uart(&keys.app, &frame).is_ok());
+
+    frame[6] ^= 0x01; // flip single ciphertext bit
+
+    assert!(matches!(
+        mi_crypto::decrypt_uart(&keys.app, &frame),
+        Err(mi_crypto::MiCryptoError::AuthTagMismatch)
+    ));
+}
+
+#[test]
+fn it_rejects_uart_frame_encrypted_with_other_key() {
+    let keys = test_keychain();
+    let frame = mi_crypto::encrypt_uart(&keys.app, &[0x03, 0x20, 0x01, 0x10, 0x0e], 0, None);
+
+    assert!(matches!(
+        mi_crypto::decrypt_uart(&keys.dev, &frame),
+        Err(mi_crypto::MiCryptoError::AuthTagMismatch)
+    ));
+}
+
+#[test]
+fn it_rejects_short_uart_frame() {
+    assert!(matches!(
+        mi_crypto::decrypt_uart(&test_keychain().dev, &[0x55, 0xAB, 0x03]),
+        Err(mi_crypto::MiCryptoError::FrameTooShort(3))
+    ));
+}
+
+#[test]
+fn it_rejects_uart_frame_without_random_bytes() {
+    // long enough for header, counter, tag and checksum, but ciphertext can't hold the 4 random bytes
+    let frame = [0x55, 0xAB, 0x00, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0xFF, 0xFF];
+
+    assert!(matches!(
+        mi_crypto::decrypt_uart(&test_keychain().dev, &frame),
+        Err(mi_crypto::MiCryptoError::ShortCiphertext(6))
+    ));
+}
+
+#[test]
+fn it_rejects_login_rand_keys_of_wrong_length() {
+    let token: mi_crypto::AuthToken = [0x42; 12];
+
+    assert!(matches!(
+        mi_crypto::calc_login_did(KeySchedule::V1, &[0x01u8; 15], &[0x02u8; 16], &token),
+        Err(mi_crypto::MiCryptoError::InvalidKeyLength(15))
+    ));
+    assert!(matches!(
+        mi_crypto::calc_login_did(KeySchedule::V1, &[0x01u8; 16], &[], &token),
+        Err(mi_crypto::MiCryptoError::InvalidKeyLength(0))
+    ));
+}
+
+#[test]
+fn it_exports_error_from_crate_root() {
+    let err: ninebot_ble::MiCryptoError = mi_crypto::MiCryptoError::AuthTagMismatch;
+    assert!(matches!(err, ninebot_ble::MiCryptoError::AuthTagMismatch));
+}
+
+const REMOTE_INFO: [u8; 24] = [
+    0x01, 0x00, 0x00, 0x00, 0x00, 0x62, 0x6c, 0x74, 0x2e, 0x33, 0x2e, 0x31, 0x36, 0x33, 0x39,
+    0x34, 0x74, 0x33, 0x67, 0x34, 0x6c, 0x63, 0x30, 0x30,
+];
+
+#[test]
+fn it_uses_mible_derivation_labels() {
+    // Any change here breaks interop with every scooter, keep them byte for byte
+    assert_eq!(mi_crypto::SETUP_INFO, &[
+        0x6d, 0x69, 0x62, 0x6c, 0x65, 0x2d, 0x73, 0x65, 0x74, 0x75, 0x70, 0x2d, 0x69, 0x6e, 0x66, 0x6f,
+    ]);
+    assert_eq!(mi_crypto::LOGIN_INFO, &[
+        0x6d, 0x69, 0x62, 0x6c, 0x65, 0x2d, 0x6c, 0x6f, 0x67, 0x69, 0x6e, 0x2d, 0x69, 0x6e, 0x66, 0x6f,
+    ]);
+    assert_eq!(mi_crypto::DID_AAD, &[0x64, 0x65, 0x76, 0x49, 0x44]);
+}
+
+#[test]
+fn it_calculates_register_payload() {
+    // HKDF-SHA256 with "mible-setup-info" and AES-CCM over "devID", computed independently
+    let shared_secret: Vec<u8> = (0x20..0x40).collect();
+
+    let (did_ct, token) = mi_crypto::calc_register(KeySchedule::V1, &shared_secret, &REMOTE_INFO).unwrap();
+
+    assert_eq!(token, [0x81, 0x5f, 0x1b, 0x1c, 0x6a, 0xaa, 0x51, 0x78, 0x19, 0x1d, 0xfb, 0xc9]);
+    assert_eq!(did_ct, vec![
+        0x55, 0x83, 0x8b, 0xd8, 0x88, 0xb3, 0x85, 0xd5, 0x3b, 0x2a, 0x5d, 0x61, 0x46, 0xdd, 0x64, 0x49,
+        0xb5, 0xc3, 0xce, 0x55, 0xed, 0x6c, 0xfb, 0x22,
+    ]);
+}
+
+#[test]
+fn it_calculates_login_keys() {
+    // HKDF-SHA256 with "mible-login-info" and HMAC-SHA256 over both salts, computed independently
+    let token: mi_crypto::AuthToken = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c];
+    let rand_key: Vec<u8> = (0x10..0x20).collect();
+    let remote_key: Vec<u8> = (0x30..0x40).collect();
+
+    let (info, expected_remote_info, keys) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &token).unwrap();
+
+    assert_eq!(keys.dev.key, [0xfb, 0x05, 0xd3, 0x80, 0xf0, 0xf9, 0xb1, 0x64, 0x30, 0x50, 0x71, 0x2d, 0x5c, 0x2b, 0x22, 0x6f]);
+    assert_eq!(keys.app.key, [0x04, 0x0d, 0x39, 0xc6, 0x63, 0x24, 0xd7, 0xd4, 0x49, 0x24, 0x24, 0xb3, 0x25, 0xae, 0x2e, 0x97]);
+    assert_eq!(keys.dev.iv, [0x64, 0x7c, 0x29, 0x73]);
+    assert_eq!(keys.app.iv, [0xc7, 0x30, 0x46, 0xaf]);
+    assert_eq!(info, [
+        0xf4, 0x80, 0x99, 0xc5, 0x18, 0x8d, 0x8f, 0xf8, 0xe7, 0xa3, 0x33, 0x59, 0x30, 0xce, 0x30, 0x36,
+        0x9d, 0xbe, 0x8d, 0xbd, 0x53, 0x25, 0xa1, 0x63, 0x63, 0x7f, 0x01, 0x47, 0xba, 0xf5, 0xa0, 0x62,
+    ]);
+    assert_eq!(expected_remote_info, [
+        0x8f, 0xb4, 0xd3, 0xe1, 0xcc, 0xdc, 0x19, 0x2e, 0x6b, 0xfb, 0x60, 0x3c, 0x34, 0x39, 0x5a, 0x97,
+        0x85, 0x6a, 0xcc, 0xce, 0xde, 0x2d, 0xd8, 0x80, 0xb7, 0x97, 0x7b, 0x9e, 0x87, 0x15, 0xd5, 0x6a,
+    ]);
+}
+
+#[test]
+fn it_derives_same_token_on_both_sides() {
+    let app_secret = EphemeralSecret::random(&mut OsRng);
+    let scooter_secret = EphemeralSecret::random(&mut OsRng);
+    let app_public = EncodedPoint::from(app_secret.public_key());
+    let scooter_public = EncodedPoint::from(scooter_secret.public_key());
+
+    let (app_did, app_token) = mi_crypto::calc_did(KeySchedule::V1, &app_secret, scooter_public.as_bytes(), &REMOTE_INFO).unwrap();
+    let (scooter_did, scooter_token) = mi_crypto::calc_did(KeySchedule::V1, &scooter_secret, app_public.as_bytes(), &REMOTE_INFO).unwrap();
+
+    assert_eq!(app_token, scooter_token);
+    assert_eq!(app_did, scooter_did);
+}
+
+#[test]
+fn it_rejects_short_remote_info() {
+    assert!(matches!(
+        mi_crypto::calc_register(KeySchedule::V1, &[0u8; 32], &[0x01, 0x00, 0x00, 0x00]),
+        Err(mi_crypto::MiCryptoError::InvalidRemoteInfo(4))
+    ));
+}
+
+#[test]
+fn it_parses_key_schedule_versions() {
+    assert_eq!(KeySchedule::from_version(1), Some(KeySchedule::V1));
+    assert_eq!(KeySchedule::from_version(2), Some(KeySchedule::V2));
+    assert_eq!(KeySchedule::from_version(0), None);
+    assert_eq!(KeySchedule::from_version(3), None);
+    assert_eq!(KeySchedule::default(), KeySchedule::V1);
+}
+
+#[test]
+fn it_refuses_v2_key_schedule() {
+    // V2 info strings are unknown until a V2 handshake is captured, nothing may be derived with guessed ones
+    let scooter_secret = EphemeralSecret::random(&mut OsRng);
+    let remote_public_key = EncodedPoint::from(EphemeralSecret::random(&mut OsRng).public_key());
+    let token: mi_crypto::AuthToken = [0x42; 12];
+
+    assert!(matches!(
+        mi_crypto::calc_register(KeySchedule::V2, &[0u8; 32], &REMOTE_INFO),
+        Err(mi_crypto::MiCryptoError::UnsupportedKeySchedule(KeySchedule::V2))
+    ));
+    assert!(matches!(
+        mi_crypto::calc_did(KeySchedule::V2, &scooter_secret, remote_public_key.as_bytes(), &REMOTE_INFO),
+        Err(mi_crypto::MiCryptoError::UnsupportedKeySchedule(KeySchedule::V2))
+    ));
+    assert!(matches!(
+        mi_crypto::calc_login_did(KeySchedule::V2, &[0x01u8; 16], &[0x02u8; 16], &token),
+        Err(mi_crypto::MiCryptoError::UnsupportedKeySchedule(KeySchedule::V2))
+    ));
+}
+
+#[test]
+fn it_verifies_remote_info_from_token() {
+    let token: mi_crypto::AuthToken = [0x42; 12];
+    let rand_key = [0x01u8; 16];
+    let remote_key = [0x02u8; 16];
+
+    let (_, expected, _) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &token).unwrap();
+    let (_, forged, _) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &[0x43; 12]).unwrap();
+
+    assert!(mi_crypto::verify_remote_info(&expected, &expected));
+    assert!(!mi_crypto::verify_remote_info(&forged, &expected));
+    assert!(!mi_crypto::verify_remote_info(&expected[..31], &expected));
+}
+
+#[test]
+fn it_returns_keys_when_scooter_answers_with_same_token() {
+    let token: mi_crypto::AuthToken = [0x42; 12];
+    let (rand_key, remote_key) = ([0x01u8; 16], [0x02u8; 16]);
+
+    let (_, expected, keys) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &token).unwrap();
+    // scooter derives the same remote info from its copy of the token
+    let (_, device_response, _) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &token).unwrap();
+
+    let verified = mi_crypto::verify_login(&device_response, &expected, keys.clone()).unwrap();
+    assert_eq!(verified.dev.key, keys.dev.key);
+    assert_eq!(verified.app.key, keys.app.key);
+}
+
+#[test]
+fn it_rejects_login_response_from_other_token() {
+    let token: mi_crypto::AuthToken = [0x42; 12];
+    let (rand_key, remote_key) = ([0x01u8; 16], [0x02u8; 16]);
+
+    let (_, expected, keys) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &token).unwrap();
+    let (_, device_response, _) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, &[0x43; 12]).unwrap();
+
+    assert!(matches!(
+        mi_crypto::verify_login(&device_response, &expected, keys),
+        Err(mi_crypto::MiCryptoError::AuthTagMismatch)
+    ));
+}
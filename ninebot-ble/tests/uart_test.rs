@@ -47,3 +47,37 @@ fn it_decrypt_uart() {
 
     assert_eq!("26354/00467353", text)
 }
+
+#[test]
+fn it_decrypts_frame_at_counter_one() {
+    let encryption_key = EncryptionKey {
+        key: hex!("5066d82368375a1f6a0a3eba1317b525"),
+        iv: hex!("28cee53e"),
+    };
+
+    let cmd: [u8; 5] = hex!("032001100e");
+    let frame = encrypt_uart(&encryption_key, &cmd, 1, None);
+
+    assert_eq!(&frame[3..5], &[0x01, 0x00]);
+    assert_eq!(&decrypt_uart(&encryption_key, &frame).unwrap()[..4], &cmd[1..]);
+}
+
+#[test]
+fn it_decrypts_frame_past_two_counter_bytes() {
+    let encryption_key = EncryptionKey {
+        key: hex!("5066d82368375a1f6a0a3eba1317b525"),
+        iv: hex!("28cee53e"),
+    };
+
+    let cmd: [u8; 5] = hex!("032001100e");
+    let frame = encrypt_uart(&encryption_key, &cmd, 0x10000, None);
+
+    // only the low two counter bytes are sent, nonce must not depend on the rest
+    assert_eq!(frame, encrypt_uart(&encryption_key, &cmd, 0, Some(frame_rand(&encryption_key, &frame))));
+    assert_eq!(&decrypt_uart(&encryption_key, &frame).unwrap()[..4], &cmd[1..]);
+}
+
+fn frame_rand(encryption_key: &EncryptionKey, frame: &[u8]) -> [u8; 4] {
+    let decrypted = decrypt_uart(encryption_key, frame).unwrap();
+    decrypted[decrypted.len() - 4..].try_into().unwrap()
+}
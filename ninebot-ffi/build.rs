@@ -0,0 +1,21 @@
+fn main() {
+    #[cfg(feature = "c-api")]
+    generate_header();
+}
+
+// Header is regenerated on every build with the feature, commit it together with changes to c_api.rs
+#[cfg(feature = "c-api")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("Invalid cbindgen.toml");
+
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Could not generate C header")
+        .write_to_file(format!("{}/include/ninebot_ffi.h", crate_dir));
+
+    println!("cargo:rerun-if-changed=src/c_api.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,175 @@
+/* Generated by cbindgen from src/c_api.rs, do not edit */
+
+#ifndef NINEBOT_FFI_H
+#define NINEBOT_FFI_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Bytes owned by the library, release with `m365_buffer_free`
+typedef struct M365Buffer {
+  uint8_t *data;
+  uintptr_t len;
+} M365Buffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Install logging, safe to call any number of times
+void m365_init(void);
+
+// Start handshake, returns [8 bytes context][65 bytes public key] or empty buffer on failure.
+// Context is big endian and has to be passed to exactly one of `m365_process_handshake*` or `m365_register*`.
+// Call failing with MalformedInput or UnsupportedKeySchedule keeps the context, so it can be retried with corrected
+// remote key or with key schedule 1
+struct M365Buffer m365_prepare_handshake(void);
+
+// Finish login handshake, returns [status][12 bytes token][DID ciphertext]
+//
+// # Safety
+// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_process_handshake(int64_t ctx,
+                                         const uint8_t *remote_key,
+                                         uintptr_t remote_key_len,
+                                         const uint8_t *remote_info,
+                                         uintptr_t remote_info_len);
+
+// Same as `m365_process_handshake`, context older than `max_age_ms` is rejected with NullSession
+//
+// # Safety
+// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_process_handshake_with_timeout(int64_t ctx,
+                                                      const uint8_t *remote_key,
+                                                      uintptr_t remote_key_len,
+                                                      const uint8_t *remote_info,
+                                                      uintptr_t remote_info_len,
+                                                      int64_t max_age_ms);
+
+// Same as `m365_process_handshake_with_timeout` with explicit key schedule, 1 or 2. Anything else is
+// MalformedInput, 2 is UnsupportedKeySchedule until its derivation is known
+//
+// # Safety
+// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_process_handshake_with_key_schedule(int64_t ctx,
+                                                           const uint8_t *remote_key,
+                                                           uintptr_t remote_key_len,
+                                                           const uint8_t *remote_info,
+                                                           uintptr_t remote_info_len,
+                                                           int64_t max_age_ms,
+                                                           int32_t key_schedule);
+
+// Register never paired scooter, key may be bare 64 bytes as scooter sends it. Returns [status][12 bytes token][DID ciphertext]
+//
+// # Safety
+// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_register(int64_t ctx,
+                                const uint8_t *remote_key,
+                                uintptr_t remote_key_len,
+                                const uint8_t *remote_info,
+                                uintptr_t remote_info_len);
+
+// Same as `m365_register` with explicit key schedule, see `m365_process_handshake_with_key_schedule`
+//
+// # Safety
+// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_register_with_key_schedule(int64_t ctx,
+                                                  const uint8_t *remote_key,
+                                                  uintptr_t remote_key_len,
+                                                  const uint8_t *remote_info,
+                                                  uintptr_t remote_info_len,
+                                                  int32_t key_schedule);
+
+// Log in with token from registration, returns [status][8 bytes big endian session id][login data].
+// Remote info is what scooter sent after its rand key, status is AuthFailure when it doesn't match the token
+//
+// # Safety
+// Every pointer must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_login(const uint8_t *token,
+                             uintptr_t token_len,
+                             const uint8_t *rand_key,
+                             uintptr_t rand_key_len,
+                             const uint8_t *remote_key,
+                             uintptr_t remote_key_len,
+                             const uint8_t *remote_info,
+                             uintptr_t remote_info_len);
+
+// Same as `m365_login` with explicit key schedule, 1 or 2. Anything else is MalformedInput, 2 is
+// UnsupportedKeySchedule until its derivation is known
+//
+// # Safety
+// Every pointer must point to at least `*_len` readable bytes or be null
+struct M365Buffer m365_login_with_key_schedule(const uint8_t *token,
+                                               uintptr_t token_len,
+                                               const uint8_t *rand_key,
+                                               uintptr_t rand_key_len,
+                                               const uint8_t *remote_key,
+                                               uintptr_t remote_key_len,
+                                               const uint8_t *remote_info,
+                                               uintptr_t remote_info_len,
+                                               int32_t key_schedule);
+
+// Encrypt command for scooter, returns [status][frame]. Negative counter lets session pick next one,
+// counter past u32::MAX is rejected with MalformedInput
+//
+// # Safety
+// `payload` must point to at least `payload_len` readable bytes or be null
+struct M365Buffer m365_encrypt(uint64_t session_id,
+                               const uint8_t *payload,
+                               uintptr_t payload_len,
+                               int64_t counter);
+
+// Encrypt several commands with sequential counters. Input and output are [2 bytes big endian length][bytes]
+// entries, returns [status][frames] with frames in the same order as commands
+//
+// # Safety
+// `commands` must point to at least `commands_len` readable bytes or be null
+struct M365Buffer m365_encrypt_batch(uint64_t session_id, const uint8_t *commands, uintptr_t commands_len);
+
+// Decrypt frame from scooter, returns [status][plaintext]
+//
+// # Safety
+// `encrypted` must point to at least `encrypted_len` readable bytes or be null
+struct M365Buffer m365_decrypt(uint64_t session_id, const uint8_t *encrypted, uintptr_t encrypted_len);
+
+// Returns [status][serialized keychain], store it securely, it is enough to talk to the scooter
+struct M365Buffer m365_export_session(uint64_t session_id);
+
+// Register session from `m365_export_session` blob, returns its id or 0 if blob is invalid
+//
+// # Safety
+// `keychain` must point to at least `keychain_len` readable bytes or be null
+uint64_t m365_import_session(const uint8_t *keychain, uintptr_t keychain_len);
+
+// Returns [status][session state], keychain and counter together, so resumed session doesn't drift
+struct M365Buffer m365_export_session_state(uint64_t session_id);
+
+// Register session from `m365_export_session_state` blob, returns its id or 0 if blob is invalid
+//
+// # Safety
+// `state` must point to at least `state_len` readable bytes or be null
+uint64_t m365_import_session_state(const uint8_t *state, uintptr_t state_len);
+
+// Counter which will be used for next frame, -1 if session is unknown
+int64_t m365_get_counter(uint64_t session_id);
+
+// Restore counter persisted with `m365_get_counter`, returns status: NullSession if session is unknown,
+// MalformedInput if value is outside 0..=0xFFFF
+uint8_t m365_reset_counter(uint64_t session_id, int64_t value);
+
+// Forget session, unknown or already freed id is ignored
+void m365_free_session(uint64_t session_id);
+
+// Wipe and release buffer returned by any `m365_*` function, null buffer is ignored
+//
+// # Safety
+// Buffer must come from this library and must not be used or freed again afterwards
+void m365_buffer_free(struct M365Buffer buffer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NINEBOT_FFI_H */
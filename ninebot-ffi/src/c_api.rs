@@ -0,0 +1,400 @@
+// C ABI for consumers which are not on the JVM (Swift, desktop). Same byte layouts as the JNI exports:
+// every returned buffer starts with status byte, payload follows only on success. Header is generated
+// by cbindgen into include/ninebot_ffi.h when building with the `c-api` feature.
+
+use super::*;
+
+/// Bytes owned by the library, release with `m365_buffer_free`
+#[repr(C)]
+pub struct M365Buffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl M365Buffer {
+    fn new(bytes: &[u8]) -> Self {
+        let bytes: Box<[u8]> = bytes.into();
+        let len = bytes.len();
+        M365Buffer { data: Box::into_raw(bytes) as *mut u8, len }
+    }
+}
+
+// Null pointer is read as empty input, so it ends up as MalformedInput instead of crash
+unsafe fn input<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, len)
+    }
+}
+
+/// Install logging, safe to call any number of times
+#[no_mangle]
+pub extern "C" fn m365_init() {
+    init_logging();
+}
+
+/// Start handshake, returns [8 bytes context][65 bytes public key] or empty buffer on failure.
+/// Context is big endian and has to be passed to exactly one of `m365_process_handshake*` or `m365_register*`.
+/// Call failing with MalformedInput or UnsupportedKeySchedule keeps the context, so it can be retried with corrected
+/// remote key or with key schedule 1
+#[no_mangle]
+pub extern "C" fn m365_prepare_handshake() -> M365Buffer {
+    M365Buffer::new(&prepare_handshake())
+}
+
+/// Finish login handshake, returns [status][12 bytes token][DID ciphertext]
+///
+/// # Safety
+/// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_process_handshake(
+    ctx: i64,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+) -> M365Buffer {
+    m365_process_handshake_with_timeout(ctx, remote_key, remote_key_len, remote_info, remote_info_len, DEFAULT_HANDSHAKE_MAX_AGE_MS)
+}
+
+/// Same as `m365_process_handshake`, context older than `max_age_ms` is rejected with NullSession
+///
+/// # Safety
+/// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_process_handshake_with_timeout(
+    ctx: i64,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+    max_age_ms: i64,
+) -> M365Buffer {
+    m365_process_handshake_with_key_schedule(
+        ctx, remote_key, remote_key_len, remote_info, remote_info_len, max_age_ms, DEFAULT_KEY_SCHEDULE,
+    )
+}
+
+/// Same as `m365_process_handshake_with_timeout` with explicit key schedule, 1 or 2. Anything else is
+/// MalformedInput, 2 is UnsupportedKeySchedule until its derivation is known
+///
+/// # Safety
+/// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_process_handshake_with_key_schedule(
+    ctx: i64,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+    max_age_ms: i64,
+    key_schedule: i32,
+) -> M365Buffer {
+    let (remote_key, remote_info) = (input(remote_key, remote_key_len), input(remote_info, remote_info_len));
+    handshake(ctx, remote_key, remote_info, max_age_ms, key_schedule, handshake_frame)
+}
+
+/// Register never paired scooter, key may be bare 64 bytes as scooter sends it. Returns [status][12 bytes token][DID ciphertext]
+///
+/// # Safety
+/// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_register(
+    ctx: i64,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+) -> M365Buffer {
+    m365_register_with_key_schedule(ctx, remote_key, remote_key_len, remote_info, remote_info_len, DEFAULT_KEY_SCHEDULE)
+}
+
+/// Same as `m365_register` with explicit key schedule, see `m365_process_handshake_with_key_schedule`
+///
+/// # Safety
+/// `remote_key` and `remote_info` must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_register_with_key_schedule(
+    ctx: i64,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+    key_schedule: i32,
+) -> M365Buffer {
+    let (remote_key, remote_info) = (input(remote_key, remote_key_len), input(remote_info, remote_info_len));
+    handshake(ctx, remote_key, remote_info, DEFAULT_HANDSHAKE_MAX_AGE_MS, key_schedule, register_frame)
+}
+
+// Unknown key schedule is rejected before the context is touched, so it stays usable
+fn handshake(
+    ctx: i64,
+    remote_key: &[u8],
+    remote_info: &[u8],
+    max_age_ms: i64,
+    key_schedule: i32,
+    frame: HandshakeFrame,
+) -> M365Buffer {
+    let output = match super::key_schedule(key_schedule) {
+        Ok(schedule) => process_handshake(ctx, remote_key, remote_info, max_age_ms, schedule, frame),
+        Err(status) => Zeroizing::new(status.frame(&[])),
+    };
+    M365Buffer::new(&output)
+}
+
+/// Log in with token from registration, returns [status][8 bytes big endian session id][login data].
+/// Remote info is what scooter sent after its rand key, status is AuthFailure when it doesn't match the token
+///
+/// # Safety
+/// Every pointer must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_login(
+    token: *const u8,
+    token_len: usize,
+    rand_key: *const u8,
+    rand_key_len: usize,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+) -> M365Buffer {
+    m365_login_with_key_schedule(
+        token, token_len,
+        rand_key, rand_key_len,
+        remote_key, remote_key_len,
+        remote_info, remote_info_len,
+        DEFAULT_KEY_SCHEDULE,
+    )
+}
+
+/// Same as `m365_login` with explicit key schedule, 1 or 2. Anything else is MalformedInput, 2 is
+/// UnsupportedKeySchedule until its derivation is known
+///
+/// # Safety
+/// Every pointer must point to at least `*_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_login_with_key_schedule(
+    token: *const u8,
+    token_len: usize,
+    rand_key: *const u8,
+    rand_key_len: usize,
+    remote_key: *const u8,
+    remote_key_len: usize,
+    remote_info: *const u8,
+    remote_info_len: usize,
+    key_schedule: i32,
+) -> M365Buffer {
+    let Ok(schedule) = super::key_schedule(key_schedule) else {
+        return M365Buffer::new(&FfiStatus::MalformedInput.frame(&[]));
+    };
+    M365Buffer::new(&login(
+        schedule,
+        input(token, token_len),
+        input(rand_key, rand_key_len),
+        input(remote_key, remote_key_len),
+        input(remote_info, remote_info_len),
+    ))
+}
+
+/// Encrypt command for scooter, returns [status][frame]. Negative counter lets session pick next one,
+/// counter past u32::MAX is rejected with MalformedInput
+///
+/// # Safety
+/// `payload` must point to at least `payload_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_encrypt(session_id: u64, payload: *const u8, payload_len: usize, counter: i64) -> M365Buffer {
+    M365Buffer::new(&encrypt(session_id, input(payload, payload_len), counter))
+}
+
+/// Encrypt several commands with sequential counters. Input and output are [2 bytes big endian length][bytes]
+/// entries, returns [status][frames] with frames in the same order as commands
+///
+/// # Safety
+/// `commands` must point to at least `commands_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_encrypt_batch(session_id: u64, commands: *const u8, commands_len: usize) -> M365Buffer {
+    M365Buffer::new(&encrypt_batch(session_id, input(commands, commands_len)))
+}
+
+/// Decrypt frame from scooter, returns [status][plaintext]
+///
+/// # Safety
+/// `encrypted` must point to at least `encrypted_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_decrypt(session_id: u64, encrypted: *const u8, encrypted_len: usize) -> M365Buffer {
+    M365Buffer::new(&decrypt(session_id, input(encrypted, encrypted_len)))
+}
+
+/// Returns [status][serialized keychain], store it securely, it is enough to talk to the scooter
+#[no_mangle]
+pub extern "C" fn m365_export_session(session_id: u64) -> M365Buffer {
+    M365Buffer::new(&export_session(session_id))
+}
+
+/// Register session from `m365_export_session` blob, returns its id or 0 if blob is invalid
+///
+/// # Safety
+/// `keychain` must point to at least `keychain_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_import_session(keychain: *const u8, keychain_len: usize) -> u64 {
+    import_session(&SESSIONS, input(keychain, keychain_len)).unwrap_or(0)
+}
+
+/// Returns [status][session state], keychain and counter together, so resumed session doesn't drift
+#[no_mangle]
+pub extern "C" fn m365_export_session_state(session_id: u64) -> M365Buffer {
+    M365Buffer::new(&export_session_state(session_id))
+}
+
+/// Register session from `m365_export_session_state` blob, returns its id or 0 if blob is invalid
+///
+/// # Safety
+/// `state` must point to at least `state_len` readable bytes or be null
+#[no_mangle]
+pub unsafe extern "C" fn m365_import_session_state(state: *const u8, state_len: usize) -> u64 {
+    import_session_state(&SESSIONS, input(state, state_len)).unwrap_or(0)
+}
+
+/// Counter which will be used for next frame, -1 if session is unknown
+#[no_mangle]
+pub extern "C" fn m365_get_counter(session_id: u64) -> i64 {
+    counter(session_id)
+}
+
+/// Restore counter persisted with `m365_get_counter`, returns status: NullSession if session is unknown,
+/// MalformedInput if value is outside 0..=0xFFFF
+#[no_mangle]
+pub extern "C" fn m365_reset_counter(session_id: u64, value: i64) -> u8 {
+    reset_counter(session_id, value) as u8
+}
+
+/// Forget session, unknown or already freed id is ignored
+#[no_mangle]
+pub extern "C" fn m365_free_session(session_id: u64) {
+    SESSIONS.remove(session_id);
+}
+
+/// Wipe and release buffer returned by any `m365_*` function, null buffer is ignored
+///
+/// # Safety
+/// Buffer must come from this library and must not be used or freed again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn m365_buffer_free(buffer: M365Buffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+
+    let mut bytes = Box::from_raw(std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len));
+    bytes.zeroize();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take(buffer: M365Buffer) -> Vec<u8> {
+        let bytes = unsafe { std::slice::from_raw_parts(buffer.data, buffer.len).to_vec() };
+        unsafe { m365_buffer_free(buffer) };
+        bytes
+    }
+
+    #[test]
+    fn test_handshake_login_round_trip() {
+        let prepared = take(m365_prepare_handshake());
+        assert_eq!(prepared.len(), 8 + 65);
+        let ctx = decode_handle(&prepared).unwrap() as i64;
+
+        let (_, scooter) = mi_crypto::gen_key_pair();
+        let scooter_key = scooter.to_encoded_point(false).as_bytes().to_vec();
+        let remote_info = [0x01u8; 20];
+
+        let handshake = take(unsafe {
+            m365_process_handshake(ctx, scooter_key.as_ptr(), scooter_key.len(), remote_info.as_ptr(), remote_info.len())
+        });
+        assert_eq!(handshake[0], FfiStatus::Success as u8);
+        assert_eq!(handshake.len(), 1 + 12 + 16 + 4); // status, token, did and CCM tag
+
+        let token = &handshake[1..13];
+        let (rand_key, remote_key) = ([0x33u8; 16], [0x44u8; 16]);
+        let (_, remote_info, _) = mi_crypto::calc_login_did(KeySchedule::V1, &rand_key, &remote_key, token.try_into().unwrap()).unwrap();
+        let login = take(unsafe {
+            m365_login(
+                token.as_ptr(), token.len(),
+                rand_key.as_ptr(), rand_key.len(),
+                remote_key.as_ptr(), remote_key.len(),
+                remote_info.as_ptr(), remote_info.len(),
+            )
+        });
+        assert_eq!(login[0], FfiStatus::Success as u8);
+        let session_id = decode_handle(&login[1..]).unwrap();
+
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+        let encrypted = take(unsafe { m365_encrypt(session_id, command.as_ptr(), command.len(), -1) });
+        assert_eq!(encrypted[0], FfiStatus::Success as u8);
+        assert_eq!(m365_get_counter(session_id), 1);
+
+        m365_free_session(session_id);
+        assert_eq!(m365_get_counter(session_id), -1);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        // Same key both ways, so frames encrypted for scooter can be decrypted back
+        let key = mi_crypto::EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        let blob = mi_crypto::LoginKeychain { dev: key.clone(), app: key }.to_bytes();
+
+        let session_id = unsafe { m365_import_session(blob.as_ptr(), blob.len()) };
+        assert_ne!(session_id, 0);
+        assert_eq!(take(m365_export_session(session_id))[1..], blob[..]);
+
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+        let encrypted = take(unsafe { m365_encrypt(session_id, command.as_ptr(), command.len(), 7) });
+        assert_eq!(encrypted[0], FfiStatus::Success as u8);
+
+        let decrypted = take(unsafe { m365_decrypt(session_id, encrypted[1..].as_ptr(), encrypted.len() - 1) });
+        assert_eq!(decrypted[0], FfiStatus::Success as u8);
+        assert_eq!(&decrypted[1..5], &command[1..]);
+
+        assert_eq!(m365_reset_counter(session_id, 100), FfiStatus::Success as u8);
+        assert_eq!(m365_get_counter(session_id), 100);
+        m365_free_session(session_id);
+        assert_eq!(m365_reset_counter(session_id, 0), FfiStatus::NullSession as u8);
+    }
+
+    #[test]
+    fn test_null_pointers() {
+        let null = std::ptr::null();
+
+        assert_eq!(take(unsafe { m365_process_handshake(0, null, 0, null, 0) }), vec![FfiStatus::NullSession as u8]);
+        assert_eq!(unsafe { m365_import_session(null, 0) }, 0);
+        assert_eq!(take(unsafe { m365_login(null, 0, null, 0, null, 0, null, 0) }), vec![FfiStatus::MalformedInput as u8]);
+
+        let ctx = decode_handle(&take(m365_prepare_handshake())).unwrap() as i64;
+        assert_eq!(take(unsafe { m365_register(ctx, null, 0, null, 0) }), vec![FfiStatus::MalformedInput as u8]);
+
+        unsafe { m365_buffer_free(M365Buffer { data: std::ptr::null_mut(), len: 0 }) };
+    }
+
+    #[test]
+    fn test_key_schedule_selection() {
+        let null = std::ptr::null();
+        let ctx = decode_handle(&take(m365_prepare_handshake())).unwrap() as i64;
+        let (_, scooter) = mi_crypto::gen_key_pair();
+        let scooter_key = scooter.to_encoded_point(false).as_bytes().to_vec();
+        let remote_info = [0x01u8; 20];
+        let register = |key_schedule| take(unsafe {
+            m365_register_with_key_schedule(ctx, scooter_key.as_ptr(), scooter_key.len(), remote_info.as_ptr(), remote_info.len(), key_schedule)
+        });
+
+        // unknown and unsupported schedules both leave the context for the V1 call
+        assert_eq!(register(7), vec![FfiStatus::MalformedInput as u8]);
+        assert_eq!(register(2), vec![FfiStatus::UnsupportedKeySchedule as u8]);
+        assert_eq!(register(1)[0], FfiStatus::Success as u8);
+
+        assert_eq!(
+            take(unsafe { m365_login_with_key_schedule(null, 0, null, 0, null, 0, null, 0, 0) }),
+            vec![FfiStatus::MalformedInput as u8]
+        );
+    }
+}
@@ -1,146 +1,659 @@
 use jni::JNIEnv;
 use jni::objects::JClass;
-use jni::sys::{jbyteArray, jlong};
+use jni::sys::{jbyteArray, jint, jlong};
 mod mi_crypto;
+#[cfg(feature = "c-api")]
+mod c_api;
 use elliptic_curve::sec1::ToEncodedPoint;
 use p256::ecdh::EphemeralSecret;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+pub use mi_crypto::{KeySchedule, MiCryptoError};
+use once_cell::sync::Lazy;
+use zeroize::{Zeroize, Zeroizing};
 // use pretty_hex::*;
 
 // We need to store state across JNI calls. 
-// For simplicity, we can return the state to Java as a pointer (jlong), 
-// effectively manually managing memory (unsafe but standard JNI pattern).
+// Handshake contexts live in HANDSHAKES and logged in sessions in SESSIONS, Java only gets an opaque id
+// for either, so stale or reused handle is an error, not UB.
 
+// Ephemeral secret is only valid for a single handshake, older contexts are rejected
+const DEFAULT_HANDSHAKE_MAX_AGE_MS: jlong = 60_000;
+
+// Key schedule version used by exports without a keySchedule parameter, see KeySchedule::from_version
+const DEFAULT_KEY_SCHEDULE: jint = 1;
+
+// EphemeralSecret wipes its scalar when dropped and LoginKeychain wipes keys when dropped, so freeing
+// either state (including early returns and freeSession) leaves no key material in freed memory
 struct KeyExchangeState {
-    secret: Option<EphemeralSecret>,
+    secret: EphemeralSecret,
+    created_at: Instant,
 }
 
 struct SessionState {
     keys: mi_crypto::LoginKeychain,
+    // Next UART counter. Frame carries only two counter bytes, so it wraps around to 0 after u16::MAX
+    counter: AtomicU32,
 }
 
-#[no_mangle]
-pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_init(
-    _env: JNIEnv,
-    _class: JClass,
-) {
-    // optional logging init
+impl SessionState {
+    fn new(keys: mi_crypto::LoginKeychain) -> Self {
+        SessionState { keys, counter: AtomicU32::new(0) }
+    }
+
+    fn next_counter(&self) -> u32 {
+        let next = |counter: u32| Some(counter.wrapping_add(1) & u16::MAX as u32);
+        // closure never returns None, so update can't fail
+        let counter = self.counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, next).unwrap();
+        counter & u16::MAX as u32
+    }
 }
 
-#[no_mangle]
-pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_prepareHandshake(
-    env: JNIEnv,
-    _class: JClass,
-) -> jbyteArray {
+// Ids are never reused, 0 is reserved for "no session"
+struct Registry<T> {
+    next_id: AtomicU64,
+    sessions: Mutex<HashMap<u64, T>>,
+}
+
+type SessionRegistry = Registry<SessionState>;
+
+impl<T> Registry<T> {
+    fn new() -> Self {
+        Registry {
+            next_id: AtomicU64::new(1),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn insert(&self, session: T) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock().insert(id, session);
+        id
+    }
+
+    // Put entry taken out with remove back under its old id
+    fn restore(&self, id: u64, session: T) {
+        self.lock().insert(id, session);
+    }
+
+    fn with_session<R>(&self, id: u64, f: impl FnOnce(Option<&T>) -> R) -> R {
+        let sessions = self.lock();
+        f(sessions.get(&id))
+    }
+
+    fn remove(&self, id: u64) -> Option<T> {
+        self.lock().remove(&id)
+    }
+
+    // Id was handed out by this registry at some point, it may be gone already
+    fn issued(&self, id: u64) -> bool {
+        id != 0 && id < self.next_id.load(Ordering::Relaxed)
+    }
+
+    // Panic while holding the lock is caught at the JNI boundary, the map itself is still fine
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, T>> {
+        self.sessions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+static SESSIONS: Lazy<SessionRegistry> = Lazy::new(Registry::new);
+// Context is removed once handshake is done with it, so its id can't be used twice
+static HANDSHAKES: Lazy<Registry<KeyExchangeState>> = Lazy::new(Registry::new);
+
+// Leading byte of exportSessionState blob: [version][counter, 4 bytes little endian][keychain].
+// Bump it whenever layout changes, so old blobs are rejected instead of restoring a wrong counter
+const SESSION_STATE_V1: u8 = 0x01;
+
+// First byte of every array returned from encrypt, encryptBatch, decrypt, processHandshake, register and login.
+// Payload follows only on Success, so empty plaintext is no longer confused with failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+enum FfiStatus {
+    Success = 0,
+    AuthFailure = 1,
+    MalformedInput = 2,
+    NullSession = 3,
+    // Handshake context was already consumed by earlier processHandshake or register call
+    ContextAlreadyUsed = 4,
+    // Key schedule is known but can't derive keys yet, handshake context is kept for retry with V1
+    UnsupportedKeySchedule = 5,
+}
+
+impl FfiStatus {
+    fn frame(self, payload: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(1 + payload.len());
+        output.push(self as u8);
+        output.extend_from_slice(payload);
+        output
+    }
+}
+
+fn framed(result: Result<Vec<u8>, FfiStatus>) -> Vec<u8> {
+    match result {
+        Ok(payload) => FfiStatus::Success.frame(&payload),
+        Err(status) => status.frame(&[]),
+    }
+}
+
+// Same as framed, for payloads carrying token or keys. Both the payload and the frame are wiped on drop
+fn framed_secret(result: Result<Zeroizing<Vec<u8>>, FfiStatus>) -> Zeroizing<Vec<u8>> {
+    match result {
+        Ok(payload) => Zeroizing::new(FfiStatus::Success.frame(&payload)),
+        Err(status) => Zeroizing::new(status.frame(&[])),
+    }
+}
+
+// Version number passed over FFI, anything but 1 or 2 is MalformedInput
+fn key_schedule(version: i32) -> Result<KeySchedule, FfiStatus> {
+    u8::try_from(version).ok().and_then(KeySchedule::from_version).ok_or(FfiStatus::MalformedInput)
+}
+
+fn to_java(env: &JNIEnv, data: &[u8]) -> jbyteArray {
+    env.byte_array_from_slice(data).unwrap_or_else(|_| std::ptr::null_mut())
+}
+
+fn handshake_frame(
+    schedule: KeySchedule,
+    secret: &EphemeralSecret,
+    remote_key: &[u8],
+    remote_info: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, FfiStatus> {
+    let calculated = mi_crypto::calc_did(schedule, secret, remote_key, remote_info);
+    let (did_ct, mut token) = calculated.map_err(|err| match err {
+        MiCryptoError::BadRemoteKeyLength(_) | MiCryptoError::EcdhFailure | MiCryptoError::InvalidRemoteInfo(_) => {
+            FfiStatus::MalformedInput
+        }
+        MiCryptoError::UnsupportedKeySchedule(_) => FfiStatus::UnsupportedKeySchedule,
+        _ => FfiStatus::AuthFailure,
+    })?;
+
+    // [12 bytes Token][Rest DID Ciphertext]
+    let mut output = Zeroizing::new(Vec::with_capacity(token.len() + did_ct.len()));
+    output.extend_from_slice(&token);
+    output.extend_from_slice(&did_ct);
+    token.zeroize();
+    Ok(output)
+}
+
+// Scooter sends its key over AVDTP as bare X and Y, add SEC1 prefix so it can go straight to register
+fn register_frame(
+    schedule: KeySchedule,
+    secret: &EphemeralSecret,
+    remote_key: &[u8],
+    remote_info: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, FfiStatus> {
+    match remote_key.len() {
+        64 => handshake_frame(schedule, secret, &[&[0x04], remote_key].concat(), remote_info),
+        _ => handshake_frame(schedule, secret, remote_key, remote_info),
+    }
+}
+
+// Rand keys are only read, calc_login_did checks both are 16 bytes before deriving anything from them.
+// Session is opened only when remote info scooter sent proves it holds the same token
+fn login_session(
+    schedule: KeySchedule,
+    token: &[u8],
+    rand_key: &[u8],
+    remote_key: &[u8],
+    remote_info: &[u8],
+) -> Result<(SessionState, Vec<u8>), FfiStatus> {
+    let mut token: mi_crypto::AuthToken = token.try_into().map_err(|_| FfiStatus::MalformedInput)?;
+
+    let derived = mi_crypto::calc_login_did(schedule, rand_key, remote_key, &token);
+    token.zeroize();
+    let (info, expected_remote_info, keys) = derived.map_err(|err| match err {
+        MiCryptoError::InvalidKeyLength(_) => FfiStatus::MalformedInput,
+        MiCryptoError::UnsupportedKeySchedule(_) => FfiStatus::UnsupportedKeySchedule,
+        _ => FfiStatus::AuthFailure,
+    })?;
+    let keys = mi_crypto::verify_login(remote_info, &expected_remote_info, keys).map_err(|_| FfiStatus::AuthFailure)?;
+
+    Ok((SessionState::new(keys), info.to_vec()))
+}
+
+fn import_session(registry: &SessionRegistry, keychain: &[u8]) -> Result<u64, FfiStatus> {
+    let keys = mi_crypto::LoginKeychain::from_bytes(keychain).map_err(|_| FfiStatus::MalformedInput)?;
+    Ok(registry.insert(SessionState::new(keys)))
+}
+
+fn session_state_bytes(session: &SessionState) -> Zeroizing<Vec<u8>> {
+    let keychain = Zeroizing::new(session.keys.to_bytes());
+    let mut blob = Zeroizing::new(Vec::with_capacity(1 + 4 + keychain.len()));
+    blob.push(SESSION_STATE_V1);
+    blob.extend_from_slice(&session.counter.load(Ordering::Relaxed).to_le_bytes());
+    blob.extend_from_slice(&keychain);
+    blob
+}
+
+fn import_session_state(registry: &SessionRegistry, blob: &[u8]) -> Result<u64, FfiStatus> {
+    let (&version, rest) = blob.split_first().ok_or(FfiStatus::MalformedInput)?;
+    if version != SESSION_STATE_V1 || rest.len() < 4 {
+        return Err(FfiStatus::MalformedInput);
+    }
+
+    let (counter, keychain) = rest.split_at(4);
+    let keys = mi_crypto::LoginKeychain::from_bytes(keychain).map_err(|_| FfiStatus::MalformedInput)?;
+    let session = SessionState::new(keys);
+    session.counter.store(u32::from_le_bytes(counter.try_into().unwrap()), Ordering::Relaxed);
+
+    Ok(registry.insert(session))
+}
+
+fn encrypt_frame(
+    session: Option<&SessionState>,
+    payload: &[u8],
+    counter_override: Option<u32>,
+) -> Result<Vec<u8>, FfiStatus> {
+    let session = session.ok_or(FfiStatus::NullSession)?;
+
+    // First byte is the size, encrypt_uart can't do anything without it
+    if payload.is_empty() {
+        return Err(FfiStatus::MalformedInput);
+    }
+
+    let counter = counter_override.unwrap_or_else(|| session.next_counter());
+    Ok(mi_crypto::encrypt_uart(&session.keys.app, payload, counter, None))
+}
+
+// Batch is a concatenation of [2 bytes big endian length][command] entries, same layout is used for the result.
+// Whole blob is checked before anything is encrypted, so bad batch never burns counters
+fn split_batch(blob: &[u8]) -> Result<Vec<&[u8]>, FfiStatus> {
+    let mut commands = Vec::new();
+    let mut rest = blob;
+
+    while !rest.is_empty() {
+        if rest.len() < 2 {
+            return Err(FfiStatus::MalformedInput);
+        }
+
+        let len = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+        if len == 0 || rest.len() < 2 + len {
+            return Err(FfiStatus::MalformedInput);
+        }
+
+        commands.push(&rest[2..2 + len]);
+        rest = &rest[2 + len..];
+    }
+
+    if commands.is_empty() {
+        return Err(FfiStatus::MalformedInput);
+    }
+
+    Ok(commands)
+}
+
+// Runs under the registry lock, so commands get sequential counters even with other threads encrypting
+fn encrypt_batch_frames(session: Option<&SessionState>, blob: &[u8]) -> Result<Vec<u8>, FfiStatus> {
+    let session = session.ok_or(FfiStatus::NullSession)?;
+    let commands = split_batch(blob)?;
+
+    let mut output = Vec::new();
+    for command in commands {
+        let frame = encrypt_frame(Some(session), command, None)?;
+        output.extend_from_slice(&(frame.len() as u16).to_be_bytes());
+        output.extend_from_slice(&frame);
+    }
+
+    Ok(output)
+}
+
+fn decrypt_frame(session: Option<&SessionState>, encrypted: &[u8]) -> Result<Vec<u8>, FfiStatus> {
+    let session = session.ok_or(FfiStatus::NullSession)?;
+
+    // Too short or garbled frame is malformed input, only failed tag check means wrong key or tampering
+    mi_crypto::decrypt_uart(&session.keys.dev, encrypted).map_err(|err| match err {
+        MiCryptoError::AuthTagMismatch => FfiStatus::AuthFailure,
+        _ => FfiStatus::MalformedInput,
+    })
+}
+
+// Tag under which native log lines show up in logcat
+#[cfg(feature = "android-log")]
+const LOG_TAG: &str = "M365Native";
+
+// Safe to call any number of times, logger is installed once per process and later calls do nothing
+fn init_logging() {
+    #[cfg(feature = "android-log")]
+    {
+        android_logger::init_once(
+            android_logger::Config::default()
+                .with_tag(LOG_TAG)
+                .with_max_level(log::LevelFilter::Debug),
+        );
+        log::debug!("Native logging initialized");
+    }
+}
+
+// Byte-level core shared by the JNI exports below and the C API (c_api.rs). Wrappers only convert
+// arguments and return values, so Android, iOS and desktop run exactly the same handshake logic.
+
+// Handshake context and session id are returned to Java as the leading 8 bytes of a byte array. They are
+// always big endian, which is the default byte order of Java's ByteBuffer.getLong
+fn encode_handle(id: u64) -> [u8; 8] {
+    id.to_be_bytes()
+}
+
+// Inverse of encode_handle, None if there are fewer than 8 bytes. Only tests read handles back natively
+#[cfg(test)]
+fn decode_handle(bytes: &[u8]) -> Option<u64> {
+    Some(u64::from_be_bytes(bytes.get(..8)?.try_into().ok()?))
+}
+
+type HandshakeFrame = fn(KeySchedule, &EphemeralSecret, &[u8], &[u8]) -> Result<Zeroizing<Vec<u8>>, FfiStatus>;
+
+// Returns [8 bytes context id][65 bytes public key], or empty array if key generation panicked.
+// Context is freed by process_handshake, unless it fails with MalformedInput or UnsupportedKeySchedule, then it
+// can be retried.
+fn prepare_handshake() -> Vec<u8> {
     // Wrap entire function in catch_unwind for FFI safety
     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
         let (secret, public) = mi_crypto::gen_key_pair();
         
-        let state = Box::new(KeyExchangeState {
-            secret: Some(secret),
+        let id = HANDSHAKES.insert(KeyExchangeState {
+            secret,
+            created_at: Instant::now(),
         });
-        let ptr = Box::into_raw(state) as i64;
         
         let pk_bytes = public.to_encoded_point(false).as_bytes().to_vec();
         
         let mut result = Vec::with_capacity(8 + pk_bytes.len());
-        result.extend_from_slice(&ptr.to_be_bytes());
+        result.extend_from_slice(&encode_handle(id));
         result.extend_from_slice(&pk_bytes);
         
         result
     }));
+
+    result.unwrap_or_default()
+}
+
+fn process_handshake(
+    ctx_id: i64,
+    remote_key: &[u8],
+    remote_info: &[u8],
+    max_age_ms: i64,
+    schedule: KeySchedule,
+    frame: HandshakeFrame,
+) -> Zeroizing<Vec<u8>> {
+    let ctx_id = ctx_id as u64;
+
+    // Taking context out of registry makes it single use, concurrent or repeated call with the same id finds nothing
+    let Some(state) = HANDSHAKES.remove(ctx_id) else {
+        let status = if HANDSHAKES.issued(ctx_id) { FfiStatus::ContextAlreadyUsed } else { FfiStatus::NullSession };
+        return Zeroizing::new(status.frame(&[]));
+    };
+
+    // Stale secret is as good as no context at all, it is freed when `state` goes out of scope
+    let max_age = Duration::from_millis(max_age_ms.max(0) as u64);
+    if state.created_at.elapsed() > max_age {
+        return Zeroizing::new(FfiStatus::NullSession.frame(&[]));
+    }
     
-    match result {
-        Ok(data) => env.byte_array_from_slice(&data).unwrap_or_else(|_| std::ptr::null_mut()),
-        Err(_) => env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
+    let result = frame(schedule, &state.secret, remote_key, remote_info);
+    if matches!(result, Err(FfiStatus::MalformedInput | FfiStatus::UnsupportedKeySchedule)) {
+        // Garbled input or unsupported schedule doesn't spend the secret, keep context so caller can retry
+        HANDSHAKES.restore(ctx_id, state);
+        return framed_secret(result);
     }
+
+    // Secret is single use, wipe it now on success and error alike instead of waiting for end of scope
+    drop(state);
+
+    framed_secret(result)
+}
+
+// Returns [status][8 bytes Session Id][Login Data...], AuthFailure when scooter's remote info doesn't match the token
+fn login(schedule: KeySchedule, token: &[u8], rand_key: &[u8], remote_key: &[u8], remote_info: &[u8]) -> Vec<u8> {
+    // Wrap in catch_unwind for FFI safety
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| -> Result<Vec<u8>, FfiStatus> {
+        let (session, info) = login_session(schedule, token, rand_key, remote_key, remote_info)?;
+        
+        let id = SESSIONS.insert(session);
+        
+        let mut result = Vec::new();
+        result.extend_from_slice(&encode_handle(id));
+        result.extend_from_slice(&info);
+        
+        Ok(result)
+    }))
+    .unwrap_or(Err(FfiStatus::MalformedInput));
+    
+    framed(result)
+}
+
+// Negative counter means session keeps track of it, anything else is used as is. Counter past u32::MAX is MalformedInput
+fn encrypt(session_id: u64, payload: &[u8], counter: i64) -> Vec<u8> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let counter_override = match counter {
+            counter if counter < 0 => None,
+            counter => Some(u32::try_from(counter).map_err(|_| FfiStatus::MalformedInput)?),
+        };
+
+        SESSIONS.with_session(session_id, |session| encrypt_frame(session, payload, counter_override))
+    }))
+    .unwrap_or(Err(FfiStatus::MalformedInput));
+
+    framed(result)
+}
+
+// Returns [status][length prefixed frames], one frame per command in the same order
+fn encrypt_batch(session_id: u64, commands: &[u8]) -> Vec<u8> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        SESSIONS.with_session(session_id, |session| encrypt_batch_frames(session, commands))
+    }))
+    .unwrap_or(Err(FfiStatus::MalformedInput));
+
+    framed(result)
+}
+
+fn decrypt(session_id: u64, encrypted: &[u8]) -> Vec<u8> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        SESSIONS.with_session(session_id, |session| decrypt_frame(session, encrypted))
+    }))
+    .unwrap_or(Err(FfiStatus::MalformedInput));
+
+    framed(result)
+}
+
+// Returns [status][serialized keychain], store it securely, it is enough to talk to the scooter
+fn export_session(session_id: u64) -> Zeroizing<Vec<u8>> {
+    let result = SESSIONS.with_session(session_id, |session| {
+        session.map(|session| Zeroizing::new(session.keys.to_bytes())).ok_or(FfiStatus::NullSession)
+    });
+
+    framed_secret(result)
+}
+
+// Returns [status][version][counter][serialized keychain], restoring it resumes with the exact counter
+fn export_session_state(session_id: u64) -> Zeroizing<Vec<u8>> {
+    let result = SESSIONS.with_session(session_id, |session| {
+        session.map(session_state_bytes).ok_or(FfiStatus::NullSession)
+    });
+
+    framed_secret(result)
+}
+
+// Counter which will be used for next frame, -1 if session is unknown
+fn counter(session_id: u64) -> i64 {
+    SESSIONS.with_session(session_id, |session| match session {
+        Some(session) => session.counter.load(Ordering::Relaxed) as i64,
+        None => -1,
+    })
+}
+
+// Restore counter persisted with counter(). Session counter wraps after u16::MAX, so anything outside
+// 0..=u16::MAX can't come from counter() and is rejected with MalformedInput instead of being truncated
+fn reset_counter(session_id: u64, value: i64) -> FfiStatus {
+    let Some(value) = u16::try_from(value).ok() else {
+        return FfiStatus::MalformedInput;
+    };
+
+    SESSIONS.with_session(session_id, |session| match session {
+        Some(session) => {
+            session.counter.store(value as u32, Ordering::Relaxed);
+            FfiStatus::Success
+        }
+        None => FfiStatus::NullSession,
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_init(
+    _env: JNIEnv,
+    _class: JClass,
+) {
+    init_logging();
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_prepareHandshake(
+    env: JNIEnv,
+    _class: JClass,
+) -> jbyteArray {
+    to_java(&env, &prepare_handshake())
 }
 
 #[no_mangle]
 pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_processHandshake(
+    env: JNIEnv,
+    class: JClass,
+    ctx_id: jlong,
+    remote_key: jbyteArray,
+    remote_info: jbyteArray,
+) -> jbyteArray {
+    Java_com_m365bleapp_ffi_M365Native_processHandshakeWithTimeout(
+        env,
+        class,
+        ctx_id,
+        remote_key,
+        remote_info,
+        DEFAULT_HANDSHAKE_MAX_AGE_MS,
+    )
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_processHandshakeWithTimeout(
+    env: JNIEnv,
+    class: JClass,
+    ctx_id: jlong,
+    remote_key: jbyteArray,
+    remote_info: jbyteArray,
+    max_age_ms: jlong,
+) -> jbyteArray {
+    Java_com_m365bleapp_ffi_M365Native_processHandshakeWithKeySchedule(
+        env,
+        class,
+        ctx_id,
+        remote_key,
+        remote_info,
+        max_age_ms,
+        DEFAULT_KEY_SCHEDULE,
+    )
+}
+
+// keySchedule is 1 or 2, anything else is MalformedInput. Only 1 derives keys, 2 returns UnsupportedKeySchedule
+// and keeps the context
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_processHandshakeWithKeySchedule(
     env: JNIEnv,
     _class: JClass,
-    ctx_ptr: jlong,
+    ctx_id: jlong,
     remote_key: jbyteArray,
     remote_info: jbyteArray,
+    max_age_ms: jlong,
+    key_schedule: jint,
 ) -> jbyteArray {
-    // Validate pointer
-    if ctx_ptr == 0 {
-        return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut());
-    }
-    
-    // Restore context
-    let mut state = unsafe { Box::from_raw(ctx_ptr as *mut KeyExchangeState) };
-    
-    // Safely take secret
-    let secret = match state.secret.take() {
-        Some(s) => s,
-        None => return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
-    };
-    
-    let remote_key_vec = match env.convert_byte_array(remote_key) {
-        Ok(v) => v,
-        Err(_) => return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
-    };
-    let remote_info_vec = match env.convert_byte_array(remote_info) {
-        Ok(v) => v,
-        Err(_) => return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
-    };
-    
-    // Call calc_did with catch_unwind
-    let (did_ct, token) = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-             mi_crypto::calc_did(&secret, &remote_key_vec, &remote_info_vec)
-    })) {
-        Ok(res) => res,
-        Err(_) => return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
+    run_handshake(&env, ctx_id, remote_key, remote_info, max_age_ms, key_schedule, handshake_frame)
+}
+
+// Registration of scooter which was never paired (or was reset). Takes key exactly as scooter sent it
+// (64 bytes, prefixed 65 is accepted too) and returns [status][12 bytes Token][DID ciphertext].
+// Store the token: register runs only on first connect, every later connection calls login with it.
+// Keychain for UART encryption is not part of registration, it comes from login.
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_register(
+    env: JNIEnv,
+    class: JClass,
+    ctx_id: jlong,
+    remote_key: jbyteArray,
+    remote_info: jbyteArray,
+) -> jbyteArray {
+    Java_com_m365bleapp_ffi_M365Native_registerWithKeySchedule(env, class, ctx_id, remote_key, remote_info, DEFAULT_KEY_SCHEDULE)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_registerWithKeySchedule(
+    env: JNIEnv,
+    _class: JClass,
+    ctx_id: jlong,
+    remote_key: jbyteArray,
+    remote_info: jbyteArray,
+    key_schedule: jint,
+) -> jbyteArray {
+    run_handshake(&env, ctx_id, remote_key, remote_info, DEFAULT_HANDSHAKE_MAX_AGE_MS, key_schedule, register_frame)
+}
+
+fn run_handshake(
+    env: &JNIEnv,
+    ctx_id: jlong,
+    remote_key: jbyteArray,
+    remote_info: jbyteArray,
+    max_age_ms: jlong,
+    key_schedule: jint,
+    frame: HandshakeFrame,
+) -> jbyteArray {
+    // Unknown version is rejected before the context is touched, so it stays usable
+    let schedule = match self::key_schedule(key_schedule) {
+        Ok(schedule) => schedule,
+        Err(status) => return to_java(env, &status.frame(&[])),
     };
 
-    // Return format: [12 bytes Token][Rest DID Ciphertext]
-    let mut output = Vec::new();
-    output.extend_from_slice(&token);
-    output.extend_from_slice(&did_ct);
-    
-    env.byte_array_from_slice(&output).unwrap_or_else(|_| std::ptr::null_mut())
+    // Unreadable array ends up as empty input, which handshake reports as malformed and keeps the context for retry
+    let remote_key_vec = env.convert_byte_array(remote_key).unwrap_or_default();
+    let remote_info_vec = env.convert_byte_array(remote_info).unwrap_or_default();
+
+    to_java(env, &process_handshake(ctx_id, &remote_key_vec, &remote_info_vec, max_age_ms, schedule, frame))
 }
 
 #[no_mangle]
 pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_login(
+     env: JNIEnv,
+     class: JClass,
+     token: jbyteArray,
+     rand_key: jbyteArray,
+     remote_key: jbyteArray,
+     // Scooter's HMAC proving it derived the same keys, checked before session is opened
+     remote_info: jbyteArray,
+) -> jbyteArray { // Returns [status][8 bytes Session Id][Login Data...]
+    Java_com_m365bleapp_ffi_M365Native_loginWithKeySchedule(env, class, token, rand_key, remote_key, remote_info, DEFAULT_KEY_SCHEDULE)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_loginWithKeySchedule(
      env: JNIEnv,
      _class: JClass,
      token: jbyteArray,
      rand_key: jbyteArray,
      remote_key: jbyteArray,
-     _remote_info: jbyteArray,
-) -> jbyteArray { // Returns [8 bytes Ptr][Login Data...]
-    // Wrap in catch_unwind for FFI safety
-    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        let token_vec = env.convert_byte_array(token).map_err(|_| "token conversion failed")?;
-        let mut rand_key_vec = env.convert_byte_array(rand_key).map_err(|_| "rand_key conversion failed")?;
-        let mut remote_key_vec = env.convert_byte_array(remote_key).map_err(|_| "remote_key conversion failed")?;
-        
-        if token_vec.len() != 12 { return Err("token length invalid"); }
-        
-        let mut token_arr = [0u8; 12];
-        token_arr.copy_from_slice(&token_vec);
-        
-        let (info, _, keys) = mi_crypto::calc_login_did(
-            &mut rand_key_vec,
-            &mut remote_key_vec,
-            &token_arr
-        );
-        
-        let session = Box::new(SessionState { keys });
-        let ptr = Box::into_raw(session) as i64;
-        
-        let mut result = Vec::new();
-        result.extend_from_slice(&ptr.to_be_bytes());
-        result.extend_from_slice(&info);
-        
-        Ok::<Vec<u8>, &str>(result)
-    }));
-    
-    match result {
-        Ok(Ok(data)) => env.byte_array_from_slice(&data).unwrap_or_else(|_| std::ptr::null_mut()),
-        _ => env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
+     remote_info: jbyteArray,
+     key_schedule: jint,
+) -> jbyteArray {
+    let Ok(schedule) = self::key_schedule(key_schedule) else {
+        return to_java(&env, &FfiStatus::MalformedInput.frame(&[]));
+    };
+    let arrays = (
+        env.convert_byte_array(token),
+        env.convert_byte_array(rand_key),
+        env.convert_byte_array(remote_key),
+        env.convert_byte_array(remote_info),
+    );
+    match arrays {
+        (Ok(token), Ok(rand_key), Ok(remote_key), Ok(remote_info)) => {
+            let (token, rand_key) = (Zeroizing::new(token), Zeroizing::new(rand_key));
+            to_java(&env, &login(schedule, &token, &rand_key, &remote_key, &remote_info))
+        }
+        _ => to_java(&env, &FfiStatus::MalformedInput.frame(&[])),
     }
 }
 
@@ -148,26 +661,27 @@ pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_login(
 pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_encrypt(
      env: JNIEnv,
      _class: JClass,
-     session_ptr: jlong,
+     session_id: jlong,
      payload: jbyteArray,
      counter: jlong,
 ) -> jbyteArray {
-     if session_ptr == 0 {
-         return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut());
+     match env.convert_byte_array(payload) {
+         Ok(payload) => to_java(&env, &encrypt(session_id as u64, &payload, counter)),
+         Err(_) => to_java(&env, &FfiStatus::MalformedInput.frame(&[])),
      }
-     
-     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-         let session = unsafe { &*(session_ptr as *mut SessionState) };
-         let payload_vec = env.convert_byte_array(payload).map_err(|_| "payload conversion failed")?;
-         
-         let encrypted = mi_crypto::encrypt_uart(&session.keys.app, &payload_vec, counter as u32, None);
-         
-         Ok::<Vec<u8>, &str>(encrypted)
-     }));
-     
-     match result {
-         Ok(Ok(data)) => env.byte_array_from_slice(&data).unwrap_or_else(|_| std::ptr::null_mut()),
-         _ => env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
+}
+
+// Commands and result are [2 bytes big endian length][bytes] entries, see encrypt_batch
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_encryptBatch(
+     env: JNIEnv,
+     _class: JClass,
+     session_id: jlong,
+     commands: jbyteArray,
+) -> jbyteArray {
+     match env.convert_byte_array(commands) {
+         Ok(commands) => to_java(&env, &encrypt_batch(session_id as u64, &commands)),
+         Err(_) => to_java(&env, &FfiStatus::MalformedInput.frame(&[])),
      }
 }
 
@@ -175,34 +689,571 @@ pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_encrypt(
 pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_decrypt(
      env: JNIEnv,
      _class: JClass,
-     session_ptr: jlong,
+     session_id: jlong,
      encrypted: jbyteArray,
 ) -> jbyteArray {
-     if session_ptr == 0 {
-         return env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut());
-     }
-     
-     let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-         let session = unsafe { &*(session_ptr as *mut SessionState) };
-         let encrypted_vec = env.convert_byte_array(encrypted).map_err(|_| "encrypted conversion failed")?;
-         
-         mi_crypto::decrypt_uart(&session.keys.dev, &encrypted_vec)
-             .map_err(|_| "decryption failed")
-     }));
-     
-     match result {
-         Ok(Ok(data)) => env.byte_array_from_slice(&data).unwrap_or_else(|_| std::ptr::null_mut()),
-         _ => env.byte_array_from_slice(&[]).unwrap_or_else(|_| std::ptr::null_mut()),
+     match env.convert_byte_array(encrypted) {
+         Ok(encrypted) => to_java(&env, &decrypt(session_id as u64, &encrypted)),
+         Err(_) => to_java(&env, &FfiStatus::MalformedInput.frame(&[])),
      }
 }
 
+// Returns [status][serialized keychain], store it securely, it is enough to talk to the scooter
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_exportSession(
+    env: JNIEnv,
+    _class: JClass,
+    session_id: jlong,
+) -> jbyteArray {
+    to_java(&env, &export_session(session_id as u64))
+}
+
+// Register session from exportSession blob, returns its id or 0 if blob is invalid
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_importSession(
+    env: JNIEnv,
+    _class: JClass,
+    keychain: jbyteArray,
+) -> jlong {
+    match env.convert_byte_array(keychain).map(|bytes| import_session(&SESSIONS, &Zeroizing::new(bytes))) {
+        Ok(Ok(id)) => id as jlong,
+        _ => 0,
+    }
+}
+
+// Returns [status][session state], keychain and counter together, so resumed session doesn't drift
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_exportSessionState(
+    env: JNIEnv,
+    _class: JClass,
+    session_id: jlong,
+) -> jbyteArray {
+    to_java(&env, &export_session_state(session_id as u64))
+}
+
+// Register session from exportSessionState blob, returns its id or 0 if blob is invalid
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_importSessionState(
+    env: JNIEnv,
+    _class: JClass,
+    state: jbyteArray,
+) -> jlong {
+    match env.convert_byte_array(state).map(|bytes| import_session_state(&SESSIONS, &Zeroizing::new(bytes))) {
+        Ok(Ok(id)) => id as jlong,
+        _ => 0,
+    }
+}
+
+// Returns counter which will be used for next frame, -1 if session is unknown
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_getCounter(
+    _env: JNIEnv,
+    _class: JClass,
+    session_id: jlong,
+) -> jlong {
+    counter(session_id as u64)
+}
+
+// Restore counter persisted with getCounter, returns status: NullSession if session is unknown,
+// MalformedInput if value is outside 0..=0xFFFF
+#[no_mangle]
+pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_resetCounter(
+    _env: JNIEnv,
+    _class: JClass,
+    session_id: jlong,
+    value: jlong,
+) -> jint {
+    reset_counter(session_id as u64, value) as jint
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_m365bleapp_ffi_M365Native_freeSession(
     _env: JNIEnv,
     _class: JClass,
-    ptr: jlong,
+    session_id: jlong,
 ) {
-    if ptr != 0 {
-        let _ = unsafe { Box::from_raw(ptr as *mut SessionState) };
+    // Unknown or already freed id is ignored
+    SESSIONS.remove(session_id as u64);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_session() -> SessionState {
+        // Same key both ways, so frames encrypted for scooter can be decrypted back
+        let key = mi_crypto::EncryptionKey { key: [0x11; 16], iv: [0x22; 4] };
+        SessionState::new(mi_crypto::LoginKeychain { dev: key.clone(), app: key })
+    }
+
+    #[test]
+    fn test_init_logging_twice() {
+        init_logging();
+        init_logging();
+    }
+
+    #[test]
+    fn test_status_is_first_byte() {
+        assert_eq!(FfiStatus::Success.frame(&[0xAA, 0xBB]), vec![0x00, 0xAA, 0xBB]);
+        assert_eq!(framed(Err(FfiStatus::AuthFailure)), vec![0x01]);
+        assert_eq!(framed(Err(FfiStatus::MalformedInput)), vec![0x02]);
+        assert_eq!(framed(Err(FfiStatus::NullSession)), vec![0x03]);
+        assert_eq!(framed(Err(FfiStatus::UnsupportedKeySchedule)), vec![0x05]);
+        assert_eq!(framed(Ok(vec![])), vec![0x00]);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_success() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        let encrypted = encrypt_frame(Some(&session), &command, Some(0)).unwrap();
+        let decrypted = decrypt_frame(Some(&session), &encrypted).unwrap();
+
+        // decrypted frame has 4 random bytes at the end and no size byte
+        assert_eq!(&decrypted[..4], &command[1..]);
+    }
+
+    #[test]
+    fn test_null_session() {
+        assert_eq!(encrypt_frame(None, &[0x03, 0x20, 0x01, 0x10, 0x0e], None), Err(FfiStatus::NullSession));
+        assert_eq!(decrypt_frame(None, &[0u8; 32]), Err(FfiStatus::NullSession));
+    }
+
+    #[test]
+    fn test_registry_rejects_bogus_id() {
+        let registry = SessionRegistry::new();
+        let id = registry.insert(test_session());
+        let bogus = id + 1000;
+
+        let result = registry.with_session(bogus, |session| decrypt_frame(session, &[0u8; 32]));
+        assert_eq!(result, Err(FfiStatus::NullSession));
+        assert!(registry.with_session(0, |session| session.is_none()));
+        assert!(registry.remove(bogus).is_none());
+    }
+
+    #[test]
+    fn test_registry_ids_are_not_reused() {
+        let registry = SessionRegistry::new();
+        let first = registry.insert(test_session());
+        assert!(registry.remove(first).is_some());
+        let second = registry.insert(test_session());
+
+        assert!(second > first);
+        assert!(registry.with_session(first, |session| session.is_none()));
+        assert!(registry.with_session(second, |session| session.is_some()));
+    }
+
+    #[test]
+    fn test_freed_session_is_unknown() {
+        let id = SESSIONS.insert(test_session());
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        assert!(SESSIONS.with_session(id, |session| encrypt_frame(session, &command, None)).is_ok());
+        assert!(SESSIONS.remove(id).is_some());
+        assert_eq!(SESSIONS.with_session(id, |session| encrypt_frame(session, &command, None)), Err(FfiStatus::NullSession));
+    }
+
+    #[test]
+    fn test_counter_advances_monotonically() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        for expected in 0..100u32 {
+            assert_eq!(session.counter.load(Ordering::Relaxed), expected);
+            encrypt_frame(Some(&session), &command, None).unwrap();
+        }
+        assert_eq!(session.counter.load(Ordering::Relaxed), 100);
+    }
+
+    #[test]
+    fn test_counter_override_and_wraparound() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        encrypt_frame(Some(&session), &command, Some(42)).unwrap();
+        assert_eq!(session.counter.load(Ordering::Relaxed), 0);
+
+        session.counter.store(u16::MAX as u32, Ordering::Relaxed);
+        encrypt_frame(Some(&session), &command, None).unwrap();
+        assert_eq!(session.counter.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_frame_at_counter_one_decrypts() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        // counter is little endian in the frame, big endian put it into the nonce as 0x0100
+        let frame = encrypt_frame(Some(&session), &command, Some(1)).unwrap();
+        assert_eq!(&frame[3..5], &[0x01, 0x00]);
+        assert_eq!(&mi_crypto::decrypt_uart(&session.keys.app, &frame).unwrap()[..4], &command[1..]);
+    }
+
+    #[test]
+    fn test_frame_past_two_counter_bytes_decrypts() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        let frame = encrypt_frame(Some(&session), &command, Some(0x10000)).unwrap();
+        assert_eq!(&frame[3..5], &[0x00, 0x00]);
+        assert_eq!(&mi_crypto::decrypt_uart(&session.keys.app, &frame).unwrap()[..4], &command[1..]);
+
+        // counter restored past two bytes continues from the wrapped value
+        session.counter.store(0x10001, Ordering::Relaxed);
+        let frame = encrypt_frame(Some(&session), &command, None).unwrap();
+        assert_eq!(&frame[3..5], &[0x01, 0x00]);
+        assert!(mi_crypto::decrypt_uart(&session.keys.app, &frame).is_ok());
+        assert_eq!(session.counter.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_encrypt_rejects_counter_past_u32() {
+        let id = SESSIONS.insert(test_session());
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+
+        assert_eq!(encrypt(id, &command, u32::MAX as i64 + 1), vec![FfiStatus::MalformedInput as u8]);
+        assert_eq!(encrypt(id, &command, u32::MAX as i64)[0], FfiStatus::Success as u8);
+        SESSIONS.remove(id);
+    }
+
+    #[test]
+    fn test_reset_counter_range() {
+        let id = SESSIONS.insert(test_session());
+
+        assert_eq!(reset_counter(id, u16::MAX as i64), FfiStatus::Success);
+        assert_eq!(counter(id), u16::MAX as i64);
+
+        // rejected values leave counter as it was instead of truncating into it
+        for value in [-1, u16::MAX as i64 + 1, u32::MAX as i64 + 1] {
+            assert_eq!(reset_counter(id, value), FfiStatus::MalformedInput);
+            assert_eq!(counter(id), u16::MAX as i64);
+        }
+
+        SESSIONS.remove(id);
+        assert_eq!(reset_counter(id, 0), FfiStatus::NullSession);
+    }
+
+    fn batch(commands: &[&[u8]]) -> Vec<u8> {
+        let mut blob = Vec::new();
+        for command in commands {
+            blob.extend_from_slice(&(command.len() as u16).to_be_bytes());
+            blob.extend_from_slice(command);
+        }
+        blob
+    }
+
+    #[test]
+    fn test_encrypt_batch() {
+        let session = test_session();
+        session.counter.store(5, Ordering::Relaxed);
+        let commands: [&[u8]; 3] = [
+            &[0x03, 0x20, 0x01, 0x10, 0x0e],
+            &[0x03, 0x20, 0x01, 0xb0, 0x20],
+            &[0x04, 0x20, 0x03, 0x7c, 0x01, 0x00],
+        ];
+
+        let output = encrypt_batch_frames(Some(&session), &batch(&commands)).unwrap();
+        let frames = split_batch(&output).unwrap();
+        assert_eq!(frames.len(), 3);
+
+        for (expected, (frame, command)) in (5u16..).zip(frames.iter().zip(commands)) {
+            assert_eq!(&frame[3..5], &expected.to_le_bytes());
+            let decrypted = decrypt_frame(Some(&session), frame).unwrap();
+            assert_eq!(&decrypted[..command.len() - 1], &command[1..]);
+        }
+        assert_eq!(session.counter.load(Ordering::Relaxed), 8);
+    }
+
+    #[test]
+    fn test_encrypt_batch_malformed() {
+        let session = test_session();
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+        let mut truncated = batch(&[&command, &command]);
+        truncated.pop();
+
+        assert_eq!(encrypt_batch_frames(Some(&session), &truncated), Err(FfiStatus::MalformedInput));
+        assert_eq!(encrypt_batch_frames(Some(&session), &[]), Err(FfiStatus::MalformedInput));
+        assert_eq!(encrypt_batch_frames(Some(&session), &[0x00, 0x00]), Err(FfiStatus::MalformedInput));
+        assert_eq!(encrypt_batch_frames(None, &batch(&[&command])), Err(FfiStatus::NullSession));
+        // nothing was encrypted, so no counter was used
+        assert_eq!(session.counter.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_export_import_session() {
+        let registry = SessionRegistry::new();
+        let original = registry.insert(test_session());
+        let blob = registry.with_session(original, |session| session.unwrap().keys.to_bytes());
+
+        let imported = import_session(&registry, &blob).unwrap();
+        assert_ne!(imported, original);
+
+        // frame encrypted by one session decrypts in the other
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+        let encrypted = registry.with_session(original, |session| encrypt_frame(session, &command, None)).unwrap();
+        let decrypted = registry.with_session(imported, |session| decrypt_frame(session, &encrypted)).unwrap();
+        assert_eq!(&decrypted[..4], &command[1..]);
+    }
+
+    #[test]
+    fn test_export_import_session_state() {
+        let registry = SessionRegistry::new();
+        let original = registry.insert(test_session());
+        registry.with_session(original, |session| session.unwrap().counter.store(41, Ordering::Relaxed));
+        let blob = registry.with_session(original, |session| session_state_bytes(session.unwrap()));
+        assert_eq!(&blob[..5], &[SESSION_STATE_V1, 41, 0, 0, 0]);
+
+        let imported = import_session_state(&registry, &blob).unwrap();
+        assert_ne!(imported, original);
+
+        // next frame continues with counter 41 instead of starting over at 0
+        let command = [0x03, 0x20, 0x01, 0x10, 0x0e];
+        let encrypted = registry.with_session(imported, |session| encrypt_frame(session, &command, None)).unwrap();
+        assert_eq!(&encrypted[3..5], &41u16.to_le_bytes());
+        assert_eq!(registry.with_session(imported, |session| session.unwrap().counter.load(Ordering::Relaxed)), 42);
+
+        let decrypted = registry.with_session(original, |session| decrypt_frame(session, &encrypted)).unwrap();
+        assert_eq!(&decrypted[..4], &command[1..]);
+    }
+
+    #[test]
+    fn test_import_invalid_session_state() {
+        let registry = SessionRegistry::new();
+        let mut blob = session_state_bytes(&test_session());
+
+        assert_eq!(import_session_state(&registry, &blob[..4]), Err(FfiStatus::MalformedInput));
+        assert_eq!(import_session_state(&registry, &test_session().keys.to_bytes()), Err(FfiStatus::MalformedInput));
+        blob[0] = 0x02;
+        assert_eq!(import_session_state(&registry, &blob), Err(FfiStatus::MalformedInput));
+        assert_eq!(import_session_state(&registry, &[]), Err(FfiStatus::MalformedInput));
+    }
+
+    #[test]
+    fn test_import_invalid_session() {
+        let registry = SessionRegistry::new();
+        let mut blob = test_session().keys.to_bytes();
+        blob[0] ^= 0xFF;
+
+        assert_eq!(import_session(&registry, &blob), Err(FfiStatus::MalformedInput));
+        assert_eq!(import_session(&registry, &[]), Err(FfiStatus::MalformedInput));
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let session = test_session();
+
+        assert_eq!(encrypt_frame(Some(&session), &[], None), Err(FfiStatus::MalformedInput));
+        assert_eq!(decrypt_frame(Some(&session), &[0x55, 0xab, 0x01]), Err(FfiStatus::MalformedInput));
+        assert_eq!(decrypt_frame(Some(&session), &[0u8; 32]), Err(FfiStatus::MalformedInput)); // bad header
+    }
+
+    #[test]
+    fn test_auth_failure() {
+        let session = test_session();
+        let mut encrypted = encrypt_frame(Some(&session), &[0x03, 0x20, 0x01, 0x10, 0x0e], Some(0)).unwrap();
+        encrypted[6] ^= 0xFF; // flip ciphertext byte, tag no longer matches
+
+        assert_eq!(decrypt_frame(Some(&session), &encrypted), Err(FfiStatus::AuthFailure));
+    }
+
+    #[test]
+    fn test_handshake_malformed_input() {
+        let (secret, public) = mi_crypto::gen_key_pair();
+        let remote_key = public.to_encoded_point(false).as_bytes().to_vec();
+
+        let mut off_curve = remote_key.clone();
+        off_curve[1..].fill(0xFF);
+
+        assert_eq!(handshake_frame(KeySchedule::V1, &secret, &remote_key[..10], &[0u8; 20]), Err(FfiStatus::MalformedInput));
+        assert_eq!(handshake_frame(KeySchedule::V1, &secret, &off_curve, &[0u8; 20]), Err(FfiStatus::MalformedInput));
+        assert_eq!(handshake_frame(KeySchedule::V1, &secret, &remote_key, &[0u8; 4]), Err(FfiStatus::MalformedInput));
+    }
+
+    #[test]
+    fn test_handshake_success() {
+        let (secret, _) = mi_crypto::gen_key_pair();
+        let (_, remote) = mi_crypto::gen_key_pair();
+        let remote_key = remote.to_encoded_point(false).as_bytes().to_vec();
+
+        let output = handshake_frame(KeySchedule::V1, &secret, &remote_key, &[0u8; 20]).unwrap();
+        assert_eq!(output.len(), 12 + 16 + 4); // token, did and CCM tag
+    }
+
+    #[test]
+    fn test_handle_is_big_endian() {
+        let encoded = encode_handle(0x0102_0304_0506_0708);
+        assert_eq!(encoded, [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(decode_handle(&encoded), Some(0x0102_0304_0506_0708));
+
+        // trailing payload is ignored, too short input is rejected
+        assert_eq!(decode_handle(&[0, 0, 0, 0, 0, 0, 0, 42, 0xFF]), Some(42));
+        assert_eq!(decode_handle(&encoded[..7]), None);
+    }
+
+    #[test]
+    fn test_handshake_context_is_single_use() {
+        let prepared = prepare_handshake();
+        let ctx = decode_handle(&prepared).unwrap() as i64;
+        let (_, scooter) = mi_crypto::gen_key_pair();
+        let scooter_key = scooter.to_encoded_point(false).as_bytes().to_vec();
+
+        let first = process_handshake(ctx, &scooter_key, &[0x01; 20], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, handshake_frame);
+        assert_eq!(first[0], FfiStatus::Success as u8);
+
+        let second = process_handshake(ctx, &scooter_key, &[0x01; 20], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, register_frame);
+        assert_eq!(&second[..], &[FfiStatus::ContextAlreadyUsed as u8]);
+    }
+
+    #[test]
+    fn test_handshake_unknown_context() {
+        let never_issued = (HANDSHAKES.next_id.load(Ordering::Relaxed) + 1_000) as i64;
+
+        for ctx in [0, never_issued] {
+            let output = process_handshake(ctx, &[], &[], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, handshake_frame);
+            assert_eq!(&output[..], &[FfiStatus::NullSession as u8]);
+        }
+    }
+
+    #[test]
+    fn test_handshake_expired_context() {
+        let prepared = prepare_handshake();
+        let ctx = decode_handle(&prepared).unwrap() as i64;
+        let (_, scooter) = mi_crypto::gen_key_pair();
+        let scooter_key = scooter.to_encoded_point(false).as_bytes().to_vec();
+
+        std::thread::sleep(Duration::from_millis(5));
+
+        let expired = process_handshake(ctx, &scooter_key, &[0x01; 20], 0, KeySchedule::V1, handshake_frame);
+        assert_eq!(&expired[..], &[FfiStatus::NullSession as u8]);
+
+        // expired context is dropped, not kept around for a retry with longer max age
+        let retried = process_handshake(ctx, &scooter_key, &[0x01; 20], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, handshake_frame);
+        assert_ne!(retried[0], FfiStatus::Success as u8);
+    }
+
+    #[test]
+    fn test_handshake_retry_after_malformed_key() {
+        let prepared = prepare_handshake();
+        let ctx = decode_handle(&prepared).unwrap() as i64;
+        let app_key = &prepared[8..];
+
+        let scooter_secret = EphemeralSecret::random(&mut rand_core::OsRng);
+        let scooter_key = scooter_secret.public_key().to_encoded_point(false).as_bytes().to_vec();
+        let remote_info = [0x01u8; 20];
+
+        let failed = process_handshake(ctx, &scooter_key[..40], &remote_info, DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, handshake_frame);
+        assert_eq!(&failed[..], &[FfiStatus::MalformedInput as u8]);
+
+        // same context, so token matches the one scooter derives from the public key handed out by prepare_handshake
+        let retried = process_handshake(ctx, &scooter_key, &remote_info, DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, handshake_frame);
+        assert_eq!(retried[0], FfiStatus::Success as u8);
+        let (_, scooter_token) = mi_crypto::calc_did(KeySchedule::V1, &scooter_secret, app_key, &remote_info).unwrap();
+        assert_eq!(&retried[1..13], &scooter_token);
+    }
+
+    #[test]
+    fn test_key_schedule_version() {
+        assert_eq!(key_schedule(DEFAULT_KEY_SCHEDULE), Ok(KeySchedule::V1));
+        assert_eq!(key_schedule(2), Ok(KeySchedule::V2));
+        for version in [0, 3, -1, 0x101] {
+            assert_eq!(key_schedule(version), Err(FfiStatus::MalformedInput));
+        }
+    }
+
+    #[test]
+    fn test_handshake_retry_after_unsupported_key_schedule() {
+        let prepared = prepare_handshake();
+        let ctx = decode_handle(&prepared).unwrap() as i64;
+        let (_, scooter) = mi_crypto::gen_key_pair();
+        let scooter_key = scooter.to_encoded_point(false).as_bytes().to_vec();
+
+        let unsupported = process_handshake(ctx, &scooter_key, &[0x01; 20], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V2, register_frame);
+        assert_eq!(&unsupported[..], &[FfiStatus::UnsupportedKeySchedule as u8]);
+
+        // nothing was derived with V2, so the context is still there for V1
+        let retried = process_handshake(ctx, &scooter_key, &[0x01; 20], DEFAULT_HANDSHAKE_MAX_AGE_MS, KeySchedule::V1, register_frame);
+        assert_eq!(retried[0], FfiStatus::Success as u8);
+    }
+
+    // Remote info scooter holding `token` answers login with
+    fn scooter_remote_info(token: &mi_crypto::AuthToken, rand_key: &[u8], remote_key: &[u8]) -> mi_crypto::Hash {
+        mi_crypto::calc_login_did(KeySchedule::V1, rand_key, remote_key, token).unwrap().1
+    }
+
+    #[test]
+    fn test_login_malformed_token() {
+        let result = login_session(KeySchedule::V1, &[0u8; 11], &[0u8; 16], &[0u8; 16], &[0u8; 32]);
+        assert_eq!(result.err(), Some(FfiStatus::MalformedInput));
+    }
+
+    #[test]
+    fn test_login_wrong_rand_key_length() {
+        let token = [0x42u8; 12];
+        let remote_info = scooter_remote_info(&token, &[0u8; 16], &[0u8; 16]);
+
+        assert_eq!(login_session(KeySchedule::V1, &token, &[0u8; 15], &[0u8; 16], &remote_info).err(), Some(FfiStatus::MalformedInput));
+        assert_eq!(login_session(KeySchedule::V1, &token, &[0u8; 16], &[0u8; 17], &remote_info).err(), Some(FfiStatus::MalformedInput));
+        assert_eq!(login_session(KeySchedule::V1, &token, &[0u8; 16], &[], &remote_info).err(), Some(FfiStatus::MalformedInput));
+        assert_eq!(login(KeySchedule::V1, &token, &[0u8; 32], &[0u8; 16], &remote_info), vec![FfiStatus::MalformedInput as u8]);
+        assert!(login_session(KeySchedule::V1, &token, &[0u8; 16], &[0u8; 16], &remote_info).is_ok());
+    }
+
+    #[test]
+    fn test_login_verifies_scooter() {
+        let token = [0x42u8; 12];
+        let (rand_key, remote_key) = ([0x01u8; 16], [0x02u8; 16]);
+
+        let genuine = scooter_remote_info(&token, &rand_key, &remote_key);
+        assert!(login_session(KeySchedule::V1, &token, &rand_key, &remote_key, &genuine).is_ok());
+
+        // scooter registered with other token, or somebody in the middle who doesn't know it
+        let forged = scooter_remote_info(&[0x43u8; 12], &rand_key, &remote_key);
+        assert_eq!(login_session(KeySchedule::V1, &token, &rand_key, &remote_key, &forged).err(), Some(FfiStatus::AuthFailure));
+        assert_eq!(login_session(KeySchedule::V1, &token, &rand_key, &remote_key, &[]).err(), Some(FfiStatus::AuthFailure));
+        assert_eq!(login(KeySchedule::V1, &token, &rand_key, &remote_key, &forged), vec![FfiStatus::AuthFailure as u8]);
+    }
+
+    #[test]
+    fn test_login_unsupported_key_schedule() {
+        let token = [0x42u8; 12];
+        let (rand_key, remote_key) = ([0x01u8; 16], [0x02u8; 16]);
+        let remote_info = scooter_remote_info(&token, &rand_key, &remote_key);
+
+        let result = login_session(KeySchedule::V2, &token, &rand_key, &remote_key, &remote_info);
+        assert_eq!(result.err(), Some(FfiStatus::UnsupportedKeySchedule));
+        assert_eq!(login(KeySchedule::V2, &token, &rand_key, &remote_key, &remote_info), vec![FfiStatus::UnsupportedKeySchedule as u8]);
+    }
+
+    #[test]
+    fn test_session_keys_zeroize() {
+        // Drop runs exactly this wipe, memory can't be inspected after drop without UB
+        let mut session = test_session();
+        Zeroize::zeroize(&mut session.keys);
+
+        assert_eq!(session.keys.dev.key, [0u8; 16]);
+        assert_eq!(session.keys.dev.iv, [0u8; 4]);
+        assert_eq!(session.keys.app.key, [0u8; 16]);
+        assert_eq!(session.keys.app.iv, [0u8; 4]);
+    }
+
+    #[test]
+    fn test_framed_secret() {
+        assert_eq!(*framed_secret(Ok(Zeroizing::new(vec![0xAA, 0xBB]))), vec![0x00, 0xAA, 0xBB]);
+        assert_eq!(*framed_secret(Err(FfiStatus::NullSession)), vec![0x03]);
+    }
+
+    #[test]
+    fn test_register_accepts_bare_key() {
+        let (secret, _) = mi_crypto::gen_key_pair();
+        let (_, remote) = mi_crypto::gen_key_pair();
+        let remote_key = remote.to_encoded_point(false).as_bytes().to_vec();
+        let remote_info = [0x01u8; 24];
+
+        let registered = register_frame(KeySchedule::V1, &secret, &remote_key[1..], &remote_info).unwrap();
+
+        assert_eq!(registered, handshake_frame(KeySchedule::V1, &secret, &remote_key, &remote_info).unwrap());
+        assert_eq!(registered, register_frame(KeySchedule::V1, &secret, &remote_key, &remote_info).unwrap());
+        assert_eq!(register_frame(KeySchedule::V1, &secret, &remote_key[..63], &remote_info), Err(FfiStatus::MalformedInput));
     }
 }
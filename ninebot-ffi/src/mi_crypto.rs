@@ -10,16 +10,87 @@ use p256::{PublicKey, ecdh::EphemeralSecret};
 use rand_core::{OsRng, RngCore};
 use anyhow::Result;
 use thiserror::Error;
+use zeroize::Zeroize;
+use subtle::ConstantTimeEq;
 
 type HmacSha256 = Hmac<Sha256>;
 type AesCcm = Ccm<Aes128, U4, U12>;
 
+/**
+ * HKDF info for registration, expands ECDH shared secret into token, bind key and DID encryption key.
+ * Registration uses no salt
+ */
+pub const SETUP_INFO : &[u8] = b"mible-setup-info";
+
+/**
+ * HKDF info for login, expands token into session keys. Salt is app rand key followed by scooter rand key
+ */
+pub const LOGIN_INFO : &[u8] = b"mible-login-info";
+
+/**
+ * Associated data authenticated along with DID ciphertext during registration
+ */
+pub const DID_AAD : &[u8] = b"devID";
+
+/**
+ * Key schedule scooter expects at registration and login. V1 expands with `SETUP_INFO` and `LOGIN_INFO`.
+ * Newer firmware is reported to use V2, but no V2 handshake has been captured yet, so its info strings are
+ * unknown and deriving with it fails with `UnsupportedKeySchedule` instead of guessing
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum KeySchedule {
+  #[default]
+  V1,
+  V2,
+}
+
+impl KeySchedule {
+  /**
+   * Schedule for its version number as passed over FFI, 1 or 2
+   */
+  pub fn from_version(version: u8) -> Option<KeySchedule> {
+    match version {
+      1 => Some(KeySchedule::V1),
+      2 => Some(KeySchedule::V2),
+      _ => None,
+    }
+  }
+
+  fn info(&self, salted: bool) -> Result<&'static [u8], MiCryptoError> {
+    match self {
+      KeySchedule::V1 if salted => Ok(LOGIN_INFO),
+      KeySchedule::V1 => Ok(SETUP_INFO),
+      KeySchedule::V2 => Err(MiCryptoError::UnsupportedKeySchedule(*self)),
+    }
+  }
+}
+
 #[derive(Error, Debug)]
 pub enum MiCryptoError {
   #[error("Header for message is invalid")]
   InvalidHeader,
-  #[error("Error when tried decrypt uart message: {0}")]
-  DecryptUart(ccm::aead::Error),
+  #[error("Encrypted frame has {0} bytes, too short to hold header, counter, tag and checksum")]
+  FrameTooShort(usize),
+  #[error("Encrypted frame carries {0} bytes of ciphertext, too short to hold random bytes and tag")]
+  ShortCiphertext(usize),
+  #[error("Authentication tag does not match, frame was tampered with or encrypted with other key")]
+  AuthTagMismatch,
+  #[error("Serialized keychain is invalid")]
+  InvalidKeychain,
+  #[error("Serialized keychain has version {0:#04x}, which this build can't read")]
+  UnsupportedVersion(u8),
+  #[error("Login rand key has {0} bytes, expected 16")]
+  InvalidKeyLength(usize),
+  #[error("Remote key has {0} bytes, expected uncompressed 65 byte point")]
+  BadRemoteKeyLength(usize),
+  #[error("Remote info has {0} bytes, expected 4 byte prefix followed by DID")]
+  InvalidRemoteInfo(usize),
+  #[error("Remote key is not a valid P-256 point")]
+  EcdhFailure,
+  #[error("Could not derive keys from shared secret")]
+  HkdfFailure,
+  #[error("Key schedule {0:?} is not supported yet")]
+  UnsupportedKeySchedule(KeySchedule),
   #[error("Crypto Failure: {0}")]
   Other(anyhow::Error)
 }
@@ -36,7 +107,7 @@ const NONCE : [u8; 12] = [
 ];
 
 fn encrypt_did(key: &[u8], did: &[u8]) -> Vec<u8> {
-  let aad = b"devID";
+  let aad = DID_AAD;
 
   let nonce = GenericArray::from_slice(&NONCE);
   let key = GenericArray::from_slice(key);
@@ -49,20 +120,16 @@ fn encrypt_did(key: &[u8], did: &[u8]) -> Vec<u8> {
   }).expect("Could not encrypt did")
 }
 
-fn derive_key(secret: &[u8], salt: Option<&[u8]>) -> [u8; 64] {
-  let mut info = b"mible-setup-info";
-
-  if salt.is_some() {
-    info = b"mible-login-info";
-  }
+fn derive_key(schedule: KeySchedule, secret: &[u8], salt: Option<&[u8]>) -> Result<[u8; 64], MiCryptoError> {
+  let info = schedule.info(salt.is_some())?;
 
   let hk = Hkdf::<Sha256>::new(salt, secret);
   let mut okm = [0u8; 64];
 
   hk.expand(info, &mut okm)
-    .expect("64 is a valid length for Sha256 to output");
+    .map_err(|_| MiCryptoError::HkdfFailure)?;
 
-  okm
+  Ok(okm)
 }
 
 pub type Hash = [u8; 32];
@@ -77,14 +144,27 @@ fn hash(secret : &[u8], data: &[u8]) -> Hash {
 
 pub type AuthToken = [u8; 12];
 
-pub fn calc_did(my_secret_key: &EphemeralSecret, remote_key_bytes: &[u8], remote_info: &[u8]) -> (Vec<u8>, AuthToken) {
-  let key_bytes = remote_key_bytes;
+/**
+ * Scooter sends its public key as uncompressed SEC1 point: 0x04, X and Y
+ */
+const REMOTE_KEY_LEN : usize = 65;
 
-  let remote_public_key = PublicKey::from_sec1_bytes(&key_bytes).expect("Key sent by scooter is invalid");
+/**
+ * App and scooter each send 16 random bytes at login, together they salt the key derivation
+ */
+const RAND_KEY_LEN : usize = 16;
 
-  let secret = my_secret_key.diffie_hellman(&remote_public_key);
+/**
+ * Derive registration payload from ECDH shared secret: DID ciphertext which is uploaded to scooter and token
+ * which has to be stored. Registration is done once per scooter, every later connection logs in with the token
+ */
+pub fn calc_register(schedule: KeySchedule, shared_secret: &[u8], remote_info: &[u8]) -> Result<(Vec<u8>, AuthToken), MiCryptoError> {
+  // first 4 bytes of remote info are not part of DID
+  if remote_info.len() <= 4 {
+    return Err(MiCryptoError::InvalidRemoteInfo(remote_info.len()))
+  }
 
-  let derived_key = derive_key(secret.as_bytes(), None); // HKDF!
+  let mut derived_key = derive_key(schedule, shared_secret, None)?; // HKDF!
 
   let token    = &derived_key[0..12];
   let _bind_key = &derived_key[12..28];
@@ -94,8 +174,24 @@ pub fn calc_did(my_secret_key: &EphemeralSecret, remote_key_bytes: &[u8], remote
 
   let mut final_token = [0u8; 12];
   final_token.copy_from_slice(token);
+  derived_key.zeroize();
+
+  Ok((did_ct, final_token))
+}
 
-  return (did_ct, final_token)
+pub fn calc_did(schedule: KeySchedule, my_secret_key: &EphemeralSecret, remote_key_bytes: &[u8], remote_info: &[u8]) -> Result<(Vec<u8>, AuthToken), MiCryptoError> {
+  let key_bytes = remote_key_bytes;
+
+  if key_bytes.len() != REMOTE_KEY_LEN {
+    return Err(MiCryptoError::BadRemoteKeyLength(key_bytes.len()))
+  }
+
+  let remote_public_key = PublicKey::from_sec1_bytes(key_bytes)
+    .map_err(|_| MiCryptoError::EcdhFailure)?;
+
+  let secret = my_secret_key.diffie_hellman(&remote_public_key);
+
+  calc_register(schedule, secret.as_bytes(), remote_info)
 }
 
 #[derive(Clone)]
@@ -113,7 +209,84 @@ pub struct LoginKeychain {
   pub app: EncryptionKey
 }
 
-pub fn calc_login_did(rand_key : &mut [u8], remote_key: &mut [u8], auth_token: &AuthToken) -> (Hash, Hash, LoginKeychain) {
+impl Zeroize for EncryptionKey {
+  fn zeroize(&mut self) {
+    self.key.zeroize();
+    self.iv.zeroize();
+  }
+}
+
+impl Zeroize for LoginKeychain {
+  fn zeroize(&mut self) {
+    self.dev.zeroize();
+    self.app.zeroize();
+  }
+}
+
+/**
+ * Keys are wiped when keychain is dropped, so they don't linger in freed memory after session ends
+ */
+impl Drop for LoginKeychain {
+  fn drop(&mut self) {
+    self.zeroize();
+  }
+}
+
+/**
+ * Leading byte of serialized keychain. Bump it whenever layout or key schedule changes, so old blobs
+ * are rejected instead of loading garbage keys
+ */
+const KEYCHAIN_MAGIC_V1 : u8 = 0xC1;
+const KEYCHAIN_LEN : usize = 1 + 2 * (16 + 4);
+
+impl LoginKeychain {
+  /**
+   * Serialize as [magic][dev key][dev iv][app key][app iv], so session can be restored without login
+   */
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes : Vec<u8> = Vec::with_capacity(KEYCHAIN_LEN);
+    bytes.push(KEYCHAIN_MAGIC_V1);
+    bytes.extend_from_slice(&self.dev.key);
+    bytes.extend_from_slice(&self.dev.iv);
+    bytes.extend_from_slice(&self.app.key);
+    bytes.extend_from_slice(&self.app.iv);
+    bytes
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<LoginKeychain, MiCryptoError> {
+    if bytes.len() != KEYCHAIN_LEN {
+      return Err(MiCryptoError::InvalidKeychain)
+    }
+
+    if bytes[0] != KEYCHAIN_MAGIC_V1 {
+      return Err(MiCryptoError::UnsupportedVersion(bytes[0]))
+    }
+
+    Ok(LoginKeychain {
+      dev: EncryptionKey {
+        key: bytes[1..17].try_into().unwrap(),
+        iv: bytes[17..21].try_into().unwrap(),
+      },
+      app: EncryptionKey {
+        key: bytes[21..37].try_into().unwrap(),
+        iv: bytes[37..41].try_into().unwrap(),
+      },
+    })
+  }
+}
+
+/**
+ * Derive session keys from token and both rand keys. Returns info which is sent to scooter, info scooter is
+ * expected to answer with and the keys
+ */
+pub fn calc_login_did(schedule: KeySchedule, rand_key : &[u8], remote_key: &[u8], auth_token: &AuthToken) -> Result<(Hash, Hash, LoginKeychain), MiCryptoError> {
+  // salt layout is fixed, keys of other lengths would derive keys scooter never agrees with
+  for key in [rand_key, remote_key] {
+    if key.len() != RAND_KEY_LEN {
+      return Err(MiCryptoError::InvalidKeyLength(key.len()))
+    }
+  }
+
   let mut salt : Vec<u8> = Vec::new();
 
   salt.extend_from_slice(rand_key);
@@ -124,7 +297,7 @@ pub fn calc_login_did(rand_key : &mut [u8], remote_key: &mut [u8], auth_token: &
   salt_inv.extend_from_slice(remote_key);
   salt_inv.extend_from_slice(rand_key);
 
-  let derived_key = derive_key(auth_token, Some(salt.as_slice()));
+  let mut derived_key = derive_key(schedule, auth_token, Some(salt.as_slice()))?;
 
   let dev_key = &derived_key[0..16];
   let app_key = &derived_key[16..32];
@@ -145,12 +318,33 @@ pub fn calc_login_did(rand_key : &mut [u8], remote_key: &mut [u8], auth_token: &
 
   let info = hash(app_key, &salt);
   let expected_remote_info = hash(dev_key, &salt_inv);
+  derived_key.zeroize();
+
+  Ok((info, expected_remote_info, keys))
+}
+
+/**
+ * Check remote info sent by scooter against the one derived from token. Runs in constant time, so timing
+ * does not leak how many leading bytes of a forged value were correct
+ */
+pub fn verify_remote_info(received: &[u8], expected: &Hash) -> bool {
+  received.ct_eq(expected).into()
+}
+
+/**
+ * Hand out keys from `calc_login_did` only when scooter answered with the remote info derived from the same token
+ * and rand keys. Wrong token or somebody in the middle is caught at login instead of at first decrypt
+ */
+pub fn verify_login(received: &[u8], expected: &Hash, keys: LoginKeychain) -> Result<LoginKeychain, MiCryptoError> {
+  if !verify_remote_info(received, expected) {
+    return Err(MiCryptoError::AuthTagMismatch)
+  }
 
-  (info, expected_remote_info, keys)
+  Ok(keys)
 }
 
 /**
- * Generate private and public key
+ * Generate private and public key. ECDH is the same for every `KeySchedule`, only the derivation differs
  */
 pub fn gen_key_pair() -> (EphemeralSecret, PublicKey) {
   let secret = EphemeralSecret::random(&mut OsRng);
@@ -166,7 +360,8 @@ const HEADER : [u8; 2] = [0x55, 0xab];
 pub fn encrypt_uart(encryption_key: &EncryptionKey, msg: &[u8], it : u32, rand: Option<[u8; 4]>) -> Vec<u8> {
   // tracing::debug!("Encrypting UART");
 
-  let it = it.to_be_bytes();
+  // Frame only carries low two bytes, scooter rebuilds the nonce from them followed by zeros
+  let it = it.to_le_bytes();
 
   let rand = rand.or_else(|| {
     let mut rand : [u8; 4] = [0u8; 4];
@@ -192,7 +387,9 @@ pub fn encrypt_uart(encryption_key: &EncryptionKey, msg: &[u8], it : u32, rand:
   let mut nonce : Vec<u8> = Vec::new();
   nonce.extend_from_slice(&encryption_key.iv);
   for _ in 0..4 { nonce.push(0); }
-  nonce.extend_from_slice(&it);
+  // same nonce decrypt_uart rebuilds from the frame, upper counter bytes would make frame undecryptable
+  nonce.extend_from_slice(&it[0..2]);
+  for _ in 0..2 { nonce.push(0); }
   // tracing::debug!("  nonce: {:?}", nonce.hex_dump());
 
   let key = GenericArray::from_slice(&encryption_key.key);
@@ -233,8 +430,22 @@ pub fn crc16(bytes: &[u8]) -> [u8; 2] {
   res
 }
 
+/**
+ * Smallest frame which can be decrypted: header (2), size (1), counter (2), CCM tag (4) and checksum (2)
+ */
+const MIN_UART_FRAME_LEN : usize = 11;
+
+/**
+ * Random bytes (4) and CCM tag (4), ciphertext of even an empty message is not shorter
+ */
+const MIN_CIPHERTEXT_LEN : usize = 8;
+
 pub fn decrypt_uart(encryption_key: &EncryptionKey, msg: &[u8]) -> Result<Vec<u8>, MiCryptoError> {
   // tracing::debug!("  Decrypting data: {:?}", msg.hex_dump());
+  if msg.len() < MIN_UART_FRAME_LEN {
+    return Err(MiCryptoError::FrameTooShort(msg.len()))
+  }
+
   let header = &msg[0..2];
 
   if header != HEADER {
@@ -245,6 +456,11 @@ pub fn decrypt_uart(encryption_key: &EncryptionKey, msg: &[u8]) -> Result<Vec<u8
   let it = &msg[3..5];
   let ct = &msg[5..msg.len() - 2];
 
+  // every frame ends with 4 random bytes encrypted along with data, followed by 4 byte tag
+  if ct.len() < MIN_CIPHERTEXT_LEN {
+    return Err(MiCryptoError::ShortCiphertext(ct.len()))
+  }
+
   // tracing::debug!("  it: {:?}", it.hex_dump());
   // tracing::debug!("  ct: {:?}", ct.hex_dump());
   // tracing::debug!("  key: {:?}", encryption_key.key.hex_dump());
@@ -267,9 +483,10 @@ pub fn decrypt_uart(encryption_key: &EncryptionKey, msg: &[u8]) -> Result<Vec<u8
     aad: &[], //of course returned aad is empty array, because fuck you thats why...
   }) {
     Ok(data) => data,
-    Err(err) => {
+    Err(_) => {
+      // CCM only fails when computed tag differs from the one in frame
       // tracing::error!("Decryption error: {}", err);
-      return Err(MiCryptoError::DecryptUart(err))
+      return Err(MiCryptoError::AuthTagMismatch)
     }
   };
 